@@ -0,0 +1,45 @@
+//! Runs the game as one pane of a split-screen dashboard.
+
+use ratatui::{
+    crossterm::event,
+    layout::{Constraint, Layout},
+    style::Stylize,
+    widgets::{Block, Paragraph},
+};
+use sokoban_rust::{App, Update};
+
+fn main() -> std::io::Result<()> {
+    let mut app = App::new(vec![]);
+    let mut terminal = ratatui::init();
+    let mut ticks = 0u64;
+    loop {
+        terminal.draw(|frame| {
+            let [game, side] =
+                Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .areas(frame.area());
+            let block = Block::bordered().title("Sokoban");
+            app.render(frame, block.inner(game));
+            frame.render_widget(block, game);
+            frame.render_widget(
+                Paragraph::new(format!("Ticks so far: {}", ticks))
+                    .dim()
+                    .block(Block::bordered().title("Dashboard")),
+                side,
+            );
+        })?;
+        let update = if event::poll(app.tick_rate())? {
+            app.handle_event(event::read()?)
+        } else {
+            ticks += 1;
+            app.tick()
+        };
+        if update == Update::Quit {
+            break;
+        }
+    }
+    let saved = app.shutdown(ratatui::restore);
+    if let Err(error) = saved {
+        eprintln!("{}", error);
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! What the screens and aids shown over a board do to the attempt being
+//! played, kept in one place. Screens that only show something stop the
+//! attempt's clock while they are open; aids that help solve the board mark
+//! the attempt as assisted, which its results say.
+
+/// Something that can be open, or turned on, over a board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Aid {
+    /// A question under the board: a restart count, a report, a level save.
+    Prompt,
+    InstantReplay,
+    Reachable,
+    DeadlockExplanation,
+    /// The solver saying whether the position can still be solved, with
+    /// `--check-solvable`.
+    SolvableCheck,
+    Heatmap,
+    Ruler,
+}
+
+/// What an aid does to the attempt while it is open.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Effect {
+    Nothing,
+    /// The clock stands still.
+    Pause,
+    /// The attempt counts as assisted from then on.
+    Assist,
+}
+
+/// Every aid is here, so a new one cannot be added without deciding.
+pub const fn effect(aid: Aid) -> Effect {
+    match aid {
+        Aid::Prompt | Aid::InstantReplay => Effect::Pause,
+        Aid::Reachable | Aid::DeadlockExplanation | Aid::SolvableCheck => Effect::Assist,
+        Aid::Heatmap | Aid::Ruler => Effect::Nothing,
+    }
+}
+
+impl Aid {
+    /// How the results screen names it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Aid::Prompt => "prompt",
+            Aid::InstantReplay => "instant replay",
+            Aid::Reachable => "reachable cells",
+            Aid::DeadlockExplanation => "deadlock explanation",
+            Aid::SolvableCheck => "solvability check",
+            Aid::Heatmap => "heatmap",
+            Aid::Ruler => "ruler",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every aid with what it should do, in declaration order.
+    const TABLE: [(Aid, Effect); 7] = [
+        (Aid::Prompt, Effect::Pause),
+        (Aid::InstantReplay, Effect::Pause),
+        (Aid::Reachable, Effect::Assist),
+        (Aid::DeadlockExplanation, Effect::Assist),
+        (Aid::SolvableCheck, Effect::Assist),
+        (Aid::Heatmap, Effect::Nothing),
+        (Aid::Ruler, Effect::Nothing),
+    ];
+
+    /// Where an aid sits in `TABLE`. Stops building when an aid is added,
+    /// so the table is kept whole.
+    fn row(aid: Aid) -> usize {
+        match aid {
+            Aid::Prompt => 0,
+            Aid::InstantReplay => 1,
+            Aid::Reachable => 2,
+            Aid::DeadlockExplanation => 3,
+            Aid::SolvableCheck => 4,
+            Aid::Heatmap => 5,
+            Aid::Ruler => 6,
+        }
+    }
+
+    #[test]
+    fn every_aid_has_its_effect() {
+        for (idx, (aid, expected)) in TABLE.into_iter().enumerate() {
+            assert_eq!(row(aid), idx, "{:?}", aid);
+            assert_eq!(effect(aid), expected, "{:?}", aid);
+        }
+    }
+}
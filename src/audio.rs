@@ -0,0 +1,185 @@
+//! Playing the game's sounds, from the sound pack or as tones made up on
+//! the spot.
+
+use std::{fs, io::Cursor, path::Path, time::Duration};
+
+use rodio::{source, OutputStream, OutputStreamHandle, Sink, Source};
+
+use super::{perf, sounds::SoundPack, Error, Settings};
+
+/// The default sounds, built from the `sounds` directory with
+/// `sokoban_rust --pack-sounds sounds src/sounds.pack`.
+pub const DEFAULT_SOUNDS: &[u8] = include_bytes!("sounds.pack");
+
+/// A sink on the default audio device, if there is one, along with the
+/// stream that must outlive it.
+pub fn open_audio() -> Option<(Sink, (OutputStream, OutputStreamHandle))> {
+    let (stream, handle) = OutputStream::try_default().ok()?;
+    let sink = Sink::try_new(&handle).ok()?;
+    Some((sink, (stream, handle)))
+}
+
+/// Drops whatever was queued while the sink was muted and unmutes it.
+pub fn restore_sound(sink: &Sink) {
+    sink.clear();
+    sink.play();
+    sink.set_volume(1.0);
+}
+
+pub enum SoundType {
+    Oof,
+    BarrelMove,
+    BarrelCorrect,
+    WinGame,
+    BarrelOof,
+    BarrelOffGoal,
+    PlayerMove,
+    UiTick,
+    UiConfirm,
+    UiCancel,
+}
+
+pub fn play_sound(sound_type: SoundType, sounds: &SoundPack, sink: &Sink) -> Result<(), Error> {
+    play_sound_at_speed(sound_type, 1.0, sounds, sink)
+}
+
+/// Sounds that are synthesised instead of read from a file, as tones of
+/// (frequency, milliseconds) played one after another.
+pub fn tones(sound_type: &SoundType) -> Option<&'static [(f32, u64)]> {
+    match sound_type {
+        // Falling, the opposite of the rising goal sound.
+        SoundType::BarrelOffGoal => Some(&[(660.0, 90), (440.0, 140)]),
+        SoundType::UiTick => Some(&[(1200.0, 12)]),
+        SoundType::UiConfirm => Some(&[(880.0, 40), (1320.0, 60)]),
+        SoundType::UiCancel => Some(&[(520.0, 60)]),
+        _ => None,
+    }
+}
+
+pub fn play_sound_at_speed(
+    sound_type: SoundType,
+    speed: f32,
+    sounds: &SoundPack,
+    sink: &Sink,
+) -> Result<(), Error> {
+    play_cue(
+        Cue {
+            sound: sound_type,
+            volume: 1.0,
+            delay: Duration::ZERO,
+            speed,
+        },
+        sounds,
+        sink,
+    )
+}
+
+/// One sound of a plan: what to play, how loud, after how long a pause and
+/// at what speed.
+pub struct Cue {
+    pub sound: SoundType,
+    pub volume: f32,
+    pub delay: Duration,
+    pub speed: f32,
+}
+
+impl Cue {
+    pub fn new(sound: SoundType) -> Cue {
+        Cue {
+            sound,
+            volume: 1.0,
+            delay: Duration::ZERO,
+            speed: 1.0,
+        }
+    }
+}
+
+/// The sounds of a push, played in order: the scrape of the box, quieter
+/// when it leaves a goal, then the goal chime or the falling off-goal tone.
+/// `goal_pitch` is how many boxes the attempt has placed, if the chime
+/// should rise with them.
+pub fn push_cues(from_goal: bool, onto_goal: bool, goal_pitch: Option<usize>) -> Vec<Cue> {
+    let mut cues = vec![Cue {
+        volume: if from_goal { 0.5 } else { 1.0 },
+        ..Cue::new(SoundType::BarrelMove)
+    }];
+    if onto_goal {
+        cues.push(Cue {
+            delay: Duration::from_millis(40),
+            speed: goal_pitch.map_or(1.0, semitones),
+            ..Cue::new(SoundType::BarrelCorrect)
+        });
+    } else if from_goal && goal_pitch.is_some() {
+        cues.push(Cue::new(SoundType::BarrelOffGoal));
+    }
+    cues
+}
+
+pub fn play_cue(cue: Cue, sounds: &SoundPack, sink: &Sink) -> Result<(), Error> {
+    let timer = perf::start();
+    let played = queue_cue(cue, sounds, sink);
+    perf::audio(timer);
+    played
+}
+
+fn queue_cue(cue: Cue, sounds: &SoundPack, sink: &Sink) -> Result<(), Error> {
+    if !cue.delay.is_zero() {
+        sink.append(source::Zero::<f32>::new(1, 44_100).take_duration(cue.delay));
+    }
+    if let Some(tones) = tones(&cue.sound) {
+        for (frequency, millis) in tones {
+            sink.append(
+                source::SineWave::new(*frequency)
+                    .take_duration(Duration::from_millis(*millis))
+                    .amplify(0.2 * cue.volume),
+            );
+        }
+        return Ok(());
+    }
+    let name = match cue.sound {
+        SoundType::Oof => "oof",
+        SoundType::BarrelMove => "metal-moving",
+        SoundType::BarrelOof => "box-crash",
+        SoundType::BarrelCorrect => "tada",
+        SoundType::WinGame => "level-win",
+        _ => return Ok(()),
+    };
+    let sound = sounds
+        .get(name)
+        .ok_or_else(|| Error::Audio(format!("there is no sound named {}", name)))?;
+    sink.append(
+        rodio::Decoder::new(Cursor::new(sound))?
+            .speed(cue.speed)
+            .amplify(cue.volume),
+    );
+    Ok(())
+}
+
+/// Playback speed that raises a sample by one semitone per box placed, up to
+/// an octave.
+pub fn semitones(placed: usize) -> f32 {
+    2f32.powf(placed.saturating_sub(1).min(12) as f32 / 12.0)
+}
+
+/// The sounds the game plays: the built-in pack, then the `--sound-pack`
+/// if one is given, then any loose files in the sounds directory, each
+/// replacing the sounds of the same name before it.
+pub fn load_sounds(settings: &Settings) -> Result<SoundPack, Error> {
+    let mut sounds = SoundPack::read(DEFAULT_SOUNDS)?;
+    if let Some(path) = &settings.sound_pack {
+        sounds.extend(read_sound_pack(path)?);
+    }
+    if let Some(dir) = settings.sounds_dir.as_ref().filter(|dir| dir.is_dir()) {
+        sounds.extend(SoundPack::from_dir(dir)?);
+    }
+    Ok(sounds)
+}
+
+pub fn read_sound_pack(path: &Path) -> Result<SoundPack, Error> {
+    let bytes =
+        fs::read(path).map_err(|error| Error::Audio(format!("{}: {}", path.display(), error)))?;
+    SoundPack::read(&bytes).map_err(|error| match error {
+        Error::Audio(reason) => Error::Audio(format!("{}: {}", path.display(), reason)),
+        error => error,
+    })
+}
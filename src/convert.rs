@@ -0,0 +1,380 @@
+//! `sokoban_rust convert`: reads level files in any form the game reads and
+//! writes them back out as plain or run-length encoded XSB, one pack or one
+//! file per level.
+//!
+//! ```text
+//! sokoban_rust convert [--to xsb|rle] [--out PATH] [--split] [--normalize]
+//!                      [--strip-metadata] [--renumber] INPUT...
+//! ```
+//!
+//! Each input is a level file or a directory of packs, read in name order
+//! like the level select menu; several inputs merge into one pack. Without
+//! `--out` the pack goes to standard output. `--split` writes each level to
+//! its own file in the `--out` directory instead. `--normalize` pads every
+//! row to the width of the board rather than trimming trailing floor,
+//! `--strip-metadata` drops titles and authors, and `--renumber` puts a
+//! `; N` line above each level, counting across the merged pack.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{boards, find_packs, game::Tile, parse_board, Error, LevelInfo};
+
+/// How the levels are written out.
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Xsb,
+    Rle,
+}
+
+struct Options {
+    format: Format,
+    out: Option<PathBuf>,
+    split: bool,
+    normalize: bool,
+    strip_metadata: bool,
+    renumber: bool,
+    inputs: Vec<PathBuf>,
+}
+
+impl Options {
+    fn from_args(args: &[String]) -> Result<Options, Error> {
+        let mut options = Options {
+            format: Format::Xsb,
+            out: None,
+            split: false,
+            normalize: false,
+            strip_metadata: false,
+            renumber: false,
+            inputs: vec![],
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--to" => {
+                    options.format = match args.next().map(String::as_str) {
+                        Some("xsb") => Format::Xsb,
+                        Some("rle") => Format::Rle,
+                        _ => return Err(usage("--to takes xsb or rle")),
+                    }
+                }
+                "--out" => match args.next() {
+                    Some(path) => options.out = Some(PathBuf::from(path)),
+                    None => return Err(usage("--out takes a path")),
+                },
+                "--split" => options.split = true,
+                "--normalize" => options.normalize = true,
+                "--strip-metadata" => options.strip_metadata = true,
+                "--renumber" => options.renumber = true,
+                flag if flag.starts_with("--") => {
+                    return Err(usage(&format!("unknown option {}", flag)))
+                }
+                path => options.inputs.push(PathBuf::from(path)),
+            }
+        }
+        if options.inputs.is_empty() {
+            return Err(usage("give at least one level file or directory"));
+        }
+        if options.split && options.out.is_none() {
+            return Err(usage("--split needs --out with the directory to write to"));
+        }
+        Ok(options)
+    }
+}
+
+fn usage(reason: &str) -> Error {
+    Error::Usage(format!("convert: {}", reason))
+}
+
+/// Runs the `convert` subcommand with the arguments after it, giving a
+/// summary of what it read, wrote and changed.
+pub fn convert(args: &[String]) -> Result<String, Error> {
+    let options = Options::from_args(args)?;
+    let mut files = vec![];
+    for input in &options.inputs {
+        if input.is_dir() {
+            files.extend(find_packs(input).into_iter().map(|pack| pack.path));
+        } else {
+            files.push(input.clone());
+        }
+    }
+    let mut levels = vec![];
+    for file in &files {
+        let text = fs::read_to_string(file)?;
+        let found = boards(&text);
+        if found.is_empty() {
+            return Err(Error::LevelParse(format!(
+                "{}: the file has no board in it",
+                file.display()
+            )));
+        }
+        for (idx, (lines, info)) in found.into_iter().enumerate() {
+            let grid = parse_board(lines).map_err(|error| {
+                Error::LevelParse(format!("{} level {}: {}", file.display(), idx + 1, error))
+            })?;
+            levels.push((grid, info));
+        }
+    }
+
+    let ragged = levels.iter().filter(|(grid, _)| is_ragged(grid)).count();
+    let titles = levels
+        .iter()
+        .filter(|(_, info)| info.title.is_some())
+        .count();
+    let authors = levels
+        .iter()
+        .filter(|(_, info)| info.author.is_some())
+        .count();
+    let written: Vec<String> = levels
+        .iter()
+        .enumerate()
+        .map(|(idx, (grid, info))| write_level(&options, idx + 1, grid, info))
+        .collect();
+    let output_files = match (&options.out, options.split) {
+        (Some(dir), true) => {
+            fs::create_dir_all(dir)?;
+            for (idx, level) in written.iter().enumerate() {
+                fs::write(level_file(dir, idx + 1), level)?;
+            }
+            written.len()
+        }
+        (Some(path), false) => {
+            fs::write(path, written.join("\n"))?;
+            1
+        }
+        (None, _) => {
+            print!("{}", written.join("\n"));
+            0
+        }
+    };
+
+    let mut summary = vec![format!(
+        "Read {} levels from {}.",
+        levels.len(),
+        plural(files.len(), "file")
+    )];
+    let format = match options.format {
+        Format::Xsb => "XSB",
+        Format::Rle => "run-length encoded XSB",
+    };
+    summary.push(match output_files {
+        0 => format!("Wrote them to standard output as {}.", format),
+        files => format!("Wrote {} as {}.", plural(files, "file"), format),
+    });
+    if options.normalize && ragged > 0 {
+        summary.push(format!(
+            "Padded the rows of {} to rectangles.",
+            plural(ragged, "ragged board")
+        ));
+    }
+    if options.strip_metadata && titles + authors > 0 {
+        summary.push(format!(
+            "Dropped {} and {}.",
+            plural(titles, "title"),
+            plural(authors, "author")
+        ));
+    }
+    if options.renumber {
+        summary.push(format!("Numbered the levels 1 to {}.", levels.len()));
+    }
+    Ok(summary.join("\n"))
+}
+
+fn plural(count: usize, thing: &str) -> String {
+    match count {
+        1 => format!("1 {}", thing),
+        _ => format!("{} {}s", count, thing),
+    }
+}
+
+/// Where `--split` writes level `number`.
+fn level_file(dir: &Path, number: usize) -> PathBuf {
+    dir.join(format!("level_{:03}.xsb", number))
+}
+
+/// Whether the rows of a board differ in length once trailing floor is
+/// left off, as most hand-written levels do.
+fn is_ragged(grid: &[Vec<Tile>]) -> bool {
+    let width = grid.first().map_or(0, Vec::len);
+    grid.iter().any(|row| trimmed(row).len() != width)
+}
+
+/// A row without its trailing floor.
+fn trimmed(row: &[Tile]) -> &[Tile] {
+    let end = row
+        .iter()
+        .rposition(|cell| *cell != Tile::Floor)
+        .map_or(0, |x| x + 1);
+    &row[..end]
+}
+
+/// One level as text: its number if renumbering, the board, and its title
+/// and author unless they are being dropped.
+fn write_level(options: &Options, number: usize, grid: &[Vec<Tile>], info: &LevelInfo) -> String {
+    let rows: Vec<&[Tile]> = grid
+        .iter()
+        .map(|row| match options.normalize {
+            true => row.as_slice(),
+            false => trimmed(row),
+        })
+        .collect();
+    let mut text = String::new();
+    if options.renumber {
+        text.push_str(&format!("; {}\n", number));
+    }
+    match options.format {
+        Format::Xsb => {
+            for row in rows {
+                text.extend(row.iter().map(|tile| char::from(*tile)));
+                text.push('\n');
+            }
+        }
+        Format::Rle => {
+            let rows: Vec<String> = rows.into_iter().map(rle).collect();
+            text.push_str(&rows.join("|"));
+            text.push('\n');
+        }
+    }
+    if !options.strip_metadata {
+        if let Some(title) = &info.title {
+            text.push_str(&format!("Title: {}\n", title));
+        }
+        if let Some(author) = &info.author {
+            text.push_str(&format!("Author: {}\n", author));
+        }
+    }
+    text
+}
+
+/// Run-length encodes one row, with `-` for floor so that the spacing
+/// survives editors that trim whitespace.
+fn rle(row: &[Tile]) -> String {
+    let mut text = String::new();
+    let mut cells = row.iter().map(|tile| match tile {
+        Tile::Floor => '-',
+        tile => char::from(*tile),
+    });
+    let mut run = cells.next().map(|cell| (cell, 1));
+    while let Some((cell, count)) = run {
+        let next = cells.next();
+        if next == Some(cell) {
+            run = Some((cell, count + 1));
+            continue;
+        }
+        if count > 1 {
+            text.push_str(&count.to_string());
+        }
+        text.push(cell);
+        run = next.map(|cell| (cell, 1));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = "\
+#####
+#@$.#
+#####
+Title: Corridor
+Author: Someone
+
+  #####
+###   #
+#.@$  ###
+#   $ .#
+#######
+";
+
+    fn read(text: &str) -> Vec<(Vec<Vec<Tile>>, LevelInfo)> {
+        boards(text)
+            .into_iter()
+            .map(|(lines, info)| (parse_board(lines).unwrap(), info))
+            .collect()
+    }
+
+    fn write(options: &Options, levels: &[(Vec<Vec<Tile>>, LevelInfo)]) -> String {
+        let written: Vec<String> = levels
+            .iter()
+            .enumerate()
+            .map(|(idx, (grid, info))| write_level(options, idx + 1, grid, info))
+            .collect();
+        written.join("\n")
+    }
+
+    fn options(format: Format, normalize: bool, strip_metadata: bool) -> Options {
+        Options {
+            format,
+            out: None,
+            split: false,
+            normalize,
+            strip_metadata,
+            renumber: false,
+            inputs: vec![],
+        }
+    }
+
+    fn metadata(levels: &[(Vec<Vec<Tile>>, LevelInfo)]) -> Vec<(Option<String>, Option<String>)> {
+        levels
+            .iter()
+            .map(|(_, info)| (info.title.clone(), info.author.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn every_format_reads_back_as_the_same_boards() {
+        let levels = read(PACK);
+        assert_eq!(levels.len(), 2);
+        for format in [Format::Xsb, Format::Rle] {
+            for normalize in [false, true] {
+                for strip_metadata in [false, true] {
+                    let options = options(format, normalize, strip_metadata);
+                    let written = write(&options, &levels);
+                    let read_back = read(&written);
+                    let grids = |levels: &[(Vec<Vec<Tile>>, LevelInfo)]| {
+                        levels
+                            .iter()
+                            .map(|(grid, _)| grid.clone())
+                            .collect::<Vec<_>>()
+                    };
+                    assert!(grids(&read_back) == grids(&levels), "{}", written);
+                    let expected = match strip_metadata {
+                        true => vec![(None, None); 2],
+                        false => metadata(&levels),
+                    };
+                    assert_eq!(metadata(&read_back), expected, "{}", written);
+                    assert_eq!(write(&options, &read_back), written);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn normalizing_pads_rows_and_otherwise_they_are_trimmed() {
+        let levels = read(PACK);
+        let trimmed = write(&options(Format::Xsb, false, false), &levels[1..]);
+        assert_eq!(trimmed, "  #####\n###   #\n#.@$  ###\n#   $ .#\n#######\n");
+        let padded = write(&options(Format::Xsb, true, false), &levels[1..]);
+        assert_eq!(
+            padded,
+            "  #####  \n###   #  \n#.@$  ###\n#   $ .# \n#######  \n"
+        );
+    }
+
+    #[test]
+    fn run_length_encoding_writes_floor_as_dashes() {
+        let levels = read(PACK);
+        assert_eq!(
+            write(&options(Format::Rle, false, false), &levels[..1]),
+            "5#|#@$.#|5#\nTitle: Corridor\nAuthor: Someone\n"
+        );
+        assert_eq!(
+            write(&options(Format::Rle, true, true), &levels[1..]),
+            "2-5#2-|3#3-#2-|#.@$2-3#|#3-$-.#-|7#2-\n"
+        );
+    }
+}
@@ -0,0 +1,1133 @@
+//! The rules of play on a bare board: where a move leads, which cells the
+//! player can reach, and when a box is stuck for good; the attempt at the
+//! level being played; and the records a solve earns. Nothing here draws
+//! or plays sound, so it runs the same without a terminal.
+
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    assist::Aid,
+    date,
+    levels::{pad_rows, Level, LevelId, LEVELS},
+    rules::{self, Outcome},
+    Metric, Profile, Ui, BUILT_IN,
+};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum MoveDirection {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+/// A cell of a board: `x` counts columns from the left and `y` rows from
+/// the top. A position may lie off the board; `index` and `tile` tell.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default)]
+pub struct Pos {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Pos {
+    pub fn new(x: i32, y: i32) -> Pos {
+        Pos { x, y }
+    }
+
+    /// The next cell over in `direction`, on the board or not.
+    pub fn step(self, direction: &MoveDirection) -> Pos {
+        match direction {
+            MoveDirection::Up => self.offset(0, -1),
+            MoveDirection::Right => self.offset(1, 0),
+            MoveDirection::Down => self.offset(0, 1),
+            MoveDirection::Left => self.offset(-1, 0),
+        }
+    }
+
+    pub fn offset(self, dx: i32, dy: i32) -> Pos {
+        Pos::new(self.x.saturating_add(dx), self.y.saturating_add(dy))
+    }
+
+    /// The row and column of the cell in `grid`, or `None` if it is off the
+    /// grid. Rows may differ in length, so each is checked on its own.
+    pub fn index(self, grid: &[Vec<Tile>]) -> Option<(usize, usize)> {
+        let y = usize::try_from(self.y).ok()?;
+        let x = usize::try_from(self.x).ok()?;
+        (x < grid.get(y)?.len()).then_some((y, x))
+    }
+
+    pub fn in_bounds(self, grid: &[Vec<Tile>]) -> bool {
+        self.index(grid).is_some()
+    }
+
+    /// What is in the cell, or `None` off the grid.
+    pub fn tile(self, grid: &[Vec<Tile>]) -> Option<Tile> {
+        self.index(grid).map(|(y, x)| grid[y][x])
+    }
+}
+
+/// One cell of a board. Boards are read and written in XSB notation, and
+/// `char::from` is the only place a tile turns back into its character.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Tile {
+    Wall,
+    Floor,
+    Goal,
+    Box,
+    BoxOnGoal,
+    Player,
+    PlayerOnGoal,
+}
+
+impl Tile {
+    pub fn is_goal(self) -> bool {
+        matches!(self, Tile::Goal | Tile::BoxOnGoal | Tile::PlayerOnGoal)
+    }
+
+    pub fn is_box(self) -> bool {
+        matches!(self, Tile::Box | Tile::BoxOnGoal)
+    }
+
+    pub fn is_player(self) -> bool {
+        matches!(self, Tile::Player | Tile::PlayerOnGoal)
+    }
+
+    /// Floor or goal, with nothing on it.
+    pub fn is_empty(self) -> bool {
+        matches!(self, Tile::Floor | Tile::Goal)
+    }
+
+    /// The tile with a box put on it, keeping the goal underneath.
+    pub fn with_box(self) -> Tile {
+        if self.is_goal() {
+            Tile::BoxOnGoal
+        } else {
+            Tile::Box
+        }
+    }
+
+    /// The tile with the player put on it, keeping the goal underneath.
+    pub fn with_player(self) -> Tile {
+        if self.is_goal() {
+            Tile::PlayerOnGoal
+        } else {
+            Tile::Player
+        }
+    }
+
+    /// What is left once a box or the player moves off.
+    pub fn emptied(self) -> Tile {
+        match self {
+            Tile::Wall => Tile::Wall,
+            _ if self.is_goal() => Tile::Goal,
+            _ => Tile::Floor,
+        }
+    }
+}
+
+/// XSB notation, with `-` and `_` as other ways of writing floor. Any other
+/// character is not a tile.
+impl TryFrom<char> for Tile {
+    type Error = char;
+
+    fn try_from(c: char) -> Result<Tile, char> {
+        Ok(match c {
+            '#' => Tile::Wall,
+            ' ' | '-' | '_' => Tile::Floor,
+            '.' => Tile::Goal,
+            '$' => Tile::Box,
+            '*' => Tile::BoxOnGoal,
+            '@' => Tile::Player,
+            '+' => Tile::PlayerOnGoal,
+            _ => return Err(c),
+        })
+    }
+}
+
+impl From<Tile> for char {
+    fn from(tile: Tile) -> char {
+        match tile {
+            Tile::Wall => '#',
+            Tile::Floor => ' ',
+            Tile::Goal => '.',
+            Tile::Box => '$',
+            Tile::BoxOnGoal => '*',
+            Tile::Player => '@',
+            Tile::PlayerOnGoal => '+',
+        }
+    }
+}
+
+/// A board together with where the player stands on it.
+pub type Snapshot = (Vec<Vec<Tile>>, Pos);
+
+/// The squares the player can walk to from `start` without pushing a box.
+pub fn reachable(grid: &[Vec<Tile>], start: Pos) -> HashSet<Pos> {
+    let mut seen = HashSet::from([start]);
+    let mut todo = vec![start];
+    while let Some(pos) = todo.pop() {
+        for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+            let Some(next) = offset(grid, pos, dx, dy) else {
+                continue;
+            };
+            if cell_at(grid, next).is_empty() && seen.insert(next) {
+                todo.push(next);
+            }
+        }
+    }
+    seen
+}
+
+/// Contents of a cell, treating anything off the grid as wall.
+pub fn cell_at(grid: &[Vec<Tile>], pos: Pos) -> Tile {
+    pos.tile(grid).unwrap_or(Tile::Wall)
+}
+
+pub fn set_grid_cell(grid: &mut [Vec<Tile>], pos: Pos, contents: Tile) {
+    if let Some((y, x)) = pos.index(grid) {
+        grid[y][x] = contents;
+    }
+}
+
+/// Where a step in `direction` from `current` lands, staying put rather
+/// than leaving the grid.
+pub fn next_position(direction: &MoveDirection, current: Pos, grid: &[Vec<Tile>]) -> Pos {
+    Some(current.step(direction))
+        .filter(|next| next.in_bounds(grid))
+        .unwrap_or(current)
+}
+
+/// `pos` moved by `(dx, dy)`, or `None` if that would leave the grid.
+pub fn offset(grid: &[Vec<Tile>], pos: Pos, dx: i32, dy: i32) -> Option<Pos> {
+    Some(pos.offset(dx, dy)).filter(|moved| moved.in_bounds(grid))
+}
+
+pub fn goals_filled(grid: &[Vec<Tile>]) -> usize {
+    grid.iter()
+        .flatten()
+        .filter(|tile| **tile == Tile::BoxOnGoal)
+        .count()
+}
+
+/// FNV-1a over a level's starting board, so saved heat is dropped once the
+/// level it was collected on changes.
+pub fn level_hash(grid: &[Vec<Tile>]) -> u64 {
+    grid.iter()
+        .flat_map(|row| row.iter().map(|tile| char::from(*tile)).chain(['\n']))
+        .fold(0xcbf29ce484222325, |hash, c| {
+            (hash ^ c as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Why the last push did not go ahead.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Blocked {
+    Wall,
+    /// Another box, at this position, is in the way.
+    Box(Pos),
+}
+
+/// Why a box can never reach a goal again, as found by `find_deadlock`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Deadlock {
+    /// The box is wedged into the corner formed by these two walls.
+    Corner { at: Pos, walls: [Pos; 2] },
+    /// The box is pressed against a wall with no opening or goal along it
+    /// before the way is blocked in both directions.
+    WallRun { at: Pos, walls: Vec<Pos> },
+}
+
+impl Deadlock {
+    pub fn at(&self) -> Pos {
+        match self {
+            Deadlock::Corner { at, .. } | Deadlock::WallRun { at, .. } => *at,
+        }
+    }
+
+    pub fn walls(&self) -> &[Pos] {
+        match self {
+            Deadlock::Corner { walls, .. } => walls,
+            Deadlock::WallRun { walls, .. } => walls,
+        }
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Deadlock::Corner { .. } => {
+                "Boxes can only be pushed, and a box in a corner has no free side to push it from."
+            }
+            Deadlock::WallRun { .. } => {
+                "This box can only slide along the wall, and the wall has no gap or goal before both ends close off."
+            }
+        }
+    }
+}
+
+/// Looks for a box off its goal that no sequence of pushes can free, using
+/// static wall patterns only. Boxes are never treated as obstacles, so a
+/// reported deadlock is always real.
+pub fn find_deadlock(grid: &[Vec<Tile>]) -> Option<Deadlock> {
+    let offset = |pos: Pos, (dx, dy): (i32, i32)| pos.offset(dx, dy);
+    let is_wall = |pos: Pos| cell_at(grid, pos) == Tile::Wall;
+    let boxes = grid.iter().enumerate().flat_map(|(y, row)| {
+        row.iter()
+            .enumerate()
+            .filter(|(_, tile)| **tile == Tile::Box)
+            .map(move |(x, _)| Pos::new(x as i32, y as i32))
+    });
+
+    for at in boxes {
+        for vertical in [(0, -1), (0, 1)] {
+            for horizontal in [(-1, 0), (1, 0)] {
+                let walls = [offset(at, vertical), offset(at, horizontal)];
+                if walls.iter().all(|wall| is_wall(*wall)) {
+                    return Some(Deadlock::Corner { at, walls });
+                }
+            }
+        }
+
+        'side: for side in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            if !is_wall(offset(at, side)) {
+                continue;
+            }
+            let mut walls = vec![offset(at, side)];
+            for along in [(side.1, side.0), (-side.1, -side.0)] {
+                let mut pos = offset(at, along);
+                while !is_wall(pos) {
+                    let open = !is_wall(offset(pos, side));
+                    if open || cell_at(grid, pos).is_goal() {
+                        continue 'side;
+                    }
+                    walls.push(offset(pos, side));
+                    pos = offset(pos, along);
+                }
+            }
+            return Some(Deadlock::WallRun { at, walls });
+        }
+    }
+    None
+}
+
+/// One of the eight ways to lay a board onto itself: mirrored left to right
+/// if `mirror`, then turned clockwise by `turns` quarter turns.
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub mirror: bool,
+    pub turns: u8,
+}
+
+impl Transform {
+    /// Any but the identity, picked by mixing up the seed's bits.
+    pub fn from_seed(seed: u64) -> Transform {
+        let mut mixed = seed.wrapping_add(0x9e3779b97f4a7c15);
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d049bb133111eb);
+        let pick = (mixed ^ (mixed >> 31)) % 7 + 1;
+        Transform {
+            mirror: pick & 1 == 1,
+            turns: (pick >> 1) as u8,
+        }
+    }
+
+    /// The board transformed, padded out to a rectangle first so the
+    /// outline keeps its shape.
+    pub fn grid(self, grid: &[Vec<Tile>]) -> Vec<Vec<Tile>> {
+        let mut cells = pad_rows(grid.to_vec());
+        if self.mirror {
+            cells.iter_mut().for_each(|row| row.reverse());
+        }
+        for _ in 0..self.turns {
+            let width = cells.first().map_or(0, Vec::len);
+            cells = (0..width)
+                .map(|x| cells.iter().rev().map(|row| row[x]).collect())
+                .collect();
+        }
+        cells
+    }
+
+    /// Where a move on the level's own board goes on the transformed one.
+    pub fn direction(self, direction: &MoveDirection) -> MoveDirection {
+        let mirrored = match (self.mirror, direction) {
+            (true, MoveDirection::Left) => MoveDirection::Right,
+            (true, MoveDirection::Right) => MoveDirection::Left,
+            (_, direction) => direction.clone(),
+        };
+        (0..self.turns).fold(mirrored, |direction, _| clockwise(&direction))
+    }
+
+    /// Where a move on the transformed board goes on the level's own.
+    pub fn undo(self, direction: &MoveDirection) -> MoveDirection {
+        let turned =
+            (0..(4 - self.turns) % 4).fold(direction.clone(), |direction, _| clockwise(&direction));
+        Transform {
+            mirror: self.mirror,
+            turns: 0,
+        }
+        .direction(&turned)
+    }
+}
+
+pub fn clockwise(direction: &MoveDirection) -> MoveDirection {
+    match direction {
+        MoveDirection::Up => MoveDirection::Right,
+        MoveDirection::Right => MoveDirection::Down,
+        MoveDirection::Down => MoveDirection::Left,
+        MoveDirection::Left => MoveDirection::Up,
+    }
+}
+
+/// Moves as a lower-case LURD string, the usual Sokoban solution notation.
+pub fn lurd(moves: &[MoveDirection]) -> String {
+    moves
+        .iter()
+        .map(|direction| match direction {
+            MoveDirection::Up => 'u',
+            MoveDirection::Right => 'r',
+            MoveDirection::Down => 'd',
+            MoveDirection::Left => 'l',
+        })
+        .collect()
+}
+
+pub fn moves_from_lurd(lurd: &str) -> Option<Vec<MoveDirection>> {
+    lurd.chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            'u' => Some(MoveDirection::Up),
+            'r' => Some(MoveDirection::Right),
+            'd' => Some(MoveDirection::Down),
+            'l' => Some(MoveDirection::Left),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The level being played right now. Replaced wholesale by `Attempt::start`
+/// whenever a level starts over, so nothing from one attempt leaks into the
+/// next. `level` is `None` while no level is being played.
+#[derive(Clone, Default)]
+pub struct Attempt {
+    pub level: Option<Level>,
+    /// The board and player position the attempt started from, which its
+    /// `moves` are replayed on.
+    pub start: Snapshot,
+    pub grid: Vec<Vec<Tile>>,
+    pub player_position: Pos,
+    pub moves: Vec<MoveDirection>,
+    /// How long into the attempt each of `moves` was made.
+    pub move_times: Vec<Duration>,
+    /// Moves counted towards the score.
+    pub move_count: i32,
+    pub started: Option<Instant>,
+    pub deadlock: Option<Deadlock>,
+    pub pristine: bool,
+    pub wall_bumps: u32,
+    pub goals_filled_at_start: usize,
+    pub ghost: Option<Ghost>,
+    pub blocked: Option<Blocked>,
+    pub regressions: u32,
+    pub death_recorded: bool,
+    pub trivia: Trivia,
+    /// How the board was turned or flipped from the level's own, with
+    /// `--scramble`, or by the variant picked on level select.
+    pub scramble: Option<Transform>,
+    pub variant: Variant,
+    /// `level_hash` of the level's own board, before padding or scrambling.
+    pub layout_hash: u64,
+    /// Since when a screen over the board has stopped the clock.
+    pub paused: Option<Instant>,
+    /// The aids that helped, in the order they were first turned on.
+    pub assists: Vec<Aid>,
+}
+
+/// What a solved attempt achieved, for `finish_if_solved` to weigh against
+/// the profile.
+pub struct AttemptResult {
+    pub level: Level,
+    pub moves: Vec<MoveDirection>,
+    pub move_times: Vec<Duration>,
+    pub move_count: i32,
+    pub pristine: bool,
+    pub wall_bumps: u32,
+    pub regressions: u32,
+    pub trivia: Trivia,
+    pub assists: Vec<Aid>,
+}
+
+impl Attempt {
+    /// A fresh attempt at `level`, from its starting board.
+    pub fn start(level: Level, (grid, player_position): Snapshot) -> Attempt {
+        Attempt {
+            level: Some(level),
+            goals_filled_at_start: goals_filled(&grid),
+            start: (grid.clone(), player_position),
+            grid,
+            player_position,
+            pristine: true,
+            started: Some(Instant::now()),
+            trivia: Trivia {
+                last_move: Some(Instant::now()),
+                ..Trivia::default()
+            },
+            ..Attempt::default()
+        }
+    }
+
+    /// Ends the attempt, leaving no level in play.
+    pub fn finish(&mut self) -> Option<AttemptResult> {
+        let level = self.level?;
+        let attempt = std::mem::take(self);
+        Some(AttemptResult {
+            level,
+            moves: attempt.moves,
+            move_times: attempt.move_times,
+            move_count: attempt.move_count,
+            pristine: attempt.pristine,
+            wall_bumps: attempt.wall_bumps,
+            regressions: attempt.regressions,
+            trivia: attempt.trivia,
+            assists: attempt.assists,
+        })
+    }
+
+    /// The board and player position before the first of `moves` and after
+    /// every one, played again from `start`.
+    pub fn frames(&self) -> Vec<Snapshot> {
+        let mut replay = Attempt {
+            grid: self.start.0.clone(),
+            player_position: self.start.1,
+            ..Attempt::default()
+        };
+        let mut frames = vec![self.start.clone()];
+        for direction in &self.moves {
+            player_move(direction, &mut replay);
+            frames.push((replay.grid.clone(), replay.player_position));
+        }
+        frames
+    }
+
+    /// Whether no box is left off a goal.
+    pub fn solved(&self) -> bool {
+        !self.grid.iter().flatten().any(|cell| *cell == Tile::Box)
+    }
+
+    /// Starts the clock again after it stood still for `away`.
+    pub fn resume(&mut self, away: Duration) {
+        self.started = self.started.map(|started| started + away);
+        self.trivia.last_move = self.trivia.last_move.map(|last| last + away);
+    }
+}
+
+/// The game being played: the attempt, everything kept between attempts,
+/// and what is on screen around the board.
+#[derive(Clone)]
+pub struct GameState {
+    pub attempt: Attempt,
+    pub profile: Profile,
+    pub ui: Ui,
+}
+
+/// What solving a board came to, once its records are kept, for the
+/// results screen and the win sound.
+pub struct Win {
+    pub result: AttemptResult,
+    pub variant: Variant,
+    pub pushes: i32,
+    /// The records the solve was up against, in moves and in pushes.
+    pub high_score: Option<i32>,
+    pub push_record: Option<i32>,
+    /// Whether it beat the record that `--pushes` or its absence headlines.
+    pub record: bool,
+    pub first_try: bool,
+    /// A clean solve with `--strict`: no box ever left a goal.
+    pub clean: bool,
+    /// For a daily puzzle already solved in fewer moves today, that many.
+    pub daily_best: Option<usize>,
+}
+
+impl GameState {
+    /// Finishes the attempt if every box is on a goal and keeps what it
+    /// achieved in the profile: records, best runs, badges and when the
+    /// level was solved. Showing and sounding the win is up to the caller.
+    pub fn finish_if_solved(&mut self) -> Option<Win> {
+        if !self.attempt.solved() {
+            return None;
+        }
+        let variant = self.attempt.variant;
+        let pushes = push_count(&self.attempt.frames());
+        let result = self.attempt.finish()?;
+        // A scrambled board is not the level the records are for, and a
+        // variant has records of its own.
+        let id = level_id(self, result.level).filter(|_| self.profile.settings.scramble.is_none());
+        let ranked = id.is_some() && variant == Variant::Normal;
+        let profile = &mut self.profile;
+        let first_try = ranked && result.pristine;
+        let clean = ranked && profile.settings.strict && result.regressions == 0;
+        let cur_score = result.move_count;
+        let (high_score, push_record) = match (&id, variant) {
+            (None, _) => (None, None),
+            (Some(id), Variant::Normal) => (
+                profile.scores.get(id).copied(),
+                profile.push_records.get(id).copied(),
+            ),
+            (Some(id), _) => {
+                let record = profile.variant_records.get(&(id.clone(), variant));
+                (
+                    record.map(|(moves, _)| *moves),
+                    record.map(|(_, pushes)| *pushes),
+                )
+            }
+        };
+        let fewer_moves = id.is_some() && high_score.is_none_or(|best| cur_score < best);
+        let fewer_pushes = id.is_some() && push_record.is_none_or(|best| pushes < best);
+        let record = match profile.settings.metric {
+            Metric::Moves => fewer_moves,
+            Metric::Pushes => fewer_pushes,
+        };
+        match (&id, variant) {
+            (None, _) => {}
+            (Some(id), Variant::Normal) => {
+                let at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_millis() as u64);
+                profile.solved_at.insert(id.clone(), at);
+                if first_try {
+                    profile.first_tries.insert(id.clone());
+                    profile.pristine_completions += 1;
+                }
+                if clean {
+                    profile.clean_solves.insert(id.clone());
+                }
+                if fewer_moves {
+                    profile.scores.insert(id.clone(), cur_score);
+                }
+                if fewer_pushes {
+                    profile.push_records.insert(id.clone(), pushes);
+                }
+                if record {
+                    profile.best_runs.insert(id.clone(), result.moves.clone());
+                }
+            }
+            (Some(id), _) if fewer_moves || fewer_pushes => {
+                profile.variant_records.insert(
+                    (id.clone(), variant),
+                    (
+                        high_score.map_or(cur_score, |best| best.min(cur_score)),
+                        push_record.map_or(pushes, |best| best.min(pushes)),
+                    ),
+                );
+            }
+            _ => {}
+        }
+        let mut daily_best = None;
+        if result.level == Level::Daily {
+            let day = date(self.ui.daily);
+            match self.profile.dailies.get(&day) {
+                Some(best) if best.len() <= result.moves.len() => daily_best = Some(best.len()),
+                _ => {
+                    self.profile.dailies.insert(day, result.moves.clone());
+                }
+            }
+        }
+        if let Some((key, _)) = place(self, result.level) {
+            if let Some(last) = self.profile.last_played.get_mut(&key) {
+                last.completed = true;
+            }
+        }
+        Some(Win {
+            result,
+            variant,
+            pushes,
+            high_score,
+            push_record,
+            record,
+            first_try,
+            clean,
+            daily_best,
+        })
+    }
+}
+
+/// The id records of `level` are kept under: for `Level::File`, the board
+/// of the loaded collection in play. Boards read from standard input or
+/// drawn in the editor have no file to name them by, so they get none and
+/// their records are not kept; nor are those of random boards, and the
+/// daily keeps its own in `Profile::dailies`.
+pub fn level_id(game_state: &GameState, level: Level) -> Option<LevelId> {
+    match level {
+        Level::Random | Level::Daily => None,
+        Level::File => {
+            let collection = game_state.profile.collection.as_ref()?;
+            let path = collection.path.as_ref()?;
+            Some(LevelId::Pack {
+                file: path.to_string_lossy().into_owned(),
+                index: collection.index,
+            })
+        }
+        level => Some(LevelId::BuiltIn(level)),
+    }
+}
+
+/// The `Profile::last_played` key for `level` and its index there.
+pub fn place(game_state: &GameState, level: Level) -> Option<(String, usize)> {
+    match level {
+        Level::File => {
+            let collection = game_state.profile.collection.as_ref()?;
+            let path = collection.path.as_ref()?;
+            Some((path.to_string_lossy().into_owned(), collection.index))
+        }
+        level => Some((
+            BUILT_IN.to_string(),
+            LEVELS.iter().position(|l| *l == level)?,
+        )),
+    }
+}
+
+/// How many of the steps between the frames of a replay pushed a box.
+pub fn push_count(frames: &[Snapshot]) -> i32 {
+    frames
+        .windows(2)
+        .filter(|pair| cell_at(&pair[0].0, pair[1].1).is_box())
+        .count() as i32
+}
+
+/// A fixed way to lay a level out afresh, picked on level select, for when
+/// the board itself has been learnt by heart.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Variant {
+    #[default]
+    Normal,
+    MirrorH,
+    MirrorV,
+    Rot90,
+}
+
+/// The variants in the order level select cycles through them.
+pub const VARIANTS: [Variant; 4] = [
+    Variant::Normal,
+    Variant::MirrorH,
+    Variant::MirrorV,
+    Variant::Rot90,
+];
+
+impl Variant {
+    pub fn next(self) -> Variant {
+        let idx = VARIANTS.iter().position(|variant| *variant == self);
+        VARIANTS[idx.map_or(0, |idx| (idx + 1) % VARIANTS.len())]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Normal => "as designed",
+            Variant::MirrorH => "mirrored left to right",
+            Variant::MirrorV => "mirrored top to bottom",
+            Variant::Rot90 => "turned a quarter clockwise",
+        }
+    }
+
+    /// How the board is laid out, `None` for the level's own board. A flip
+    /// top to bottom is a flip left to right turned half way round.
+    pub fn transform(self) -> Option<Transform> {
+        let (mirror, turns) = match self {
+            Variant::Normal => return None,
+            Variant::MirrorH => (true, 0),
+            Variant::MirrorV => (true, 2),
+            Variant::Rot90 => (false, 1),
+        };
+        Some(Transform { mirror, turns })
+    }
+}
+
+/// Fun facts about an attempt for the results screen. Only the pauses are
+/// collected while playing; the rest is worked out from the moves once the
+/// level is solved, so undone moves never count.
+#[derive(Clone, Default)]
+pub struct Trivia {
+    pub last_move: Option<Instant>,
+    pub longest_pause: Duration,
+}
+
+/// Where the player stood after each move of an earlier run, drawn under the
+/// current attempt so the two can be raced.
+#[derive(Clone)]
+pub struct Ghost {
+    pub trail: Vec<Pos>,
+}
+
+/// What one step of `player_move` came to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveResult {
+    /// Into a wall, or off the board.
+    Bumped,
+    /// A box that would not budge, and what stopped it.
+    Blocked(Blocked),
+    Moved,
+    /// A box pushed ahead, maybe off a goal and maybe onto one.
+    Pushed {
+        off_goal: bool,
+        onto_goal: bool,
+    },
+}
+
+/// Steps the player in `direction`, pushing the box ahead if it can go.
+/// Only the board and the player's position change; the sounds, counters
+/// and move history follow from the result, see `play_out`.
+pub fn player_move(direction: &MoveDirection, attempt: &mut Attempt) -> MoveResult {
+    let current_player_position = attempt.player_position;
+    let next_player_position = next_position(direction, current_player_position, &attempt.grid);
+    let beyond_position = next_position(direction, next_player_position, &attempt.grid);
+    // Stepping off the grid leaves the player in place; count it as a wall.
+    let ahead = if next_player_position == current_player_position {
+        Tile::Wall
+    } else {
+        cell_at(&attempt.grid, next_player_position)
+    };
+    let beyond = if beyond_position == next_player_position {
+        Tile::Wall
+    } else {
+        cell_at(&attempt.grid, beyond_position)
+    };
+    let rule = rules::step(ahead, beyond);
+    let result = match rule.outcome {
+        Outcome::Bump => return MoveResult::Bumped,
+        Outcome::BlockedByWall => return MoveResult::Blocked(Blocked::Wall),
+        Outcome::BlockedByBox => return MoveResult::Blocked(Blocked::Box(beyond_position)),
+        Outcome::Walk => MoveResult::Moved,
+        Outcome::Push => {
+            set_grid_cell(&mut attempt.grid, beyond_position, rule.beyond_after);
+            MoveResult::Pushed {
+                off_goal: ahead == Tile::BoxOnGoal,
+                onto_goal: beyond == Tile::Goal,
+            }
+        }
+    };
+    set_grid_cell(&mut attempt.grid, next_player_position, rule.ahead_after);
+    let vacated = cell_at(&attempt.grid, current_player_position).emptied();
+    set_grid_cell(&mut attempt.grid, current_player_position, vacated);
+    attempt.player_position = next_player_position;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An attempt at a board written in XSB, one row per string.
+    fn start(rows: &[&str]) -> Attempt {
+        let grid: Vec<Vec<Tile>> = rows
+            .iter()
+            .map(|row| row.chars().map(|c| Tile::try_from(c).unwrap()).collect())
+            .collect();
+        let player = (0..grid.len())
+            .flat_map(|y| (0..grid[y].len()).map(move |x| Pos::new(x as i32, y as i32)))
+            .find(|pos| cell_at(&grid, *pos).is_player())
+            .unwrap();
+        Attempt::start(Level::File, (grid, player))
+    }
+
+    fn rows(attempt: &Attempt) -> Vec<String> {
+        attempt
+            .grid
+            .iter()
+            .map(|row| row.iter().map(|tile| char::from(*tile)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn push_moves_the_box_and_the_player() {
+        let mut attempt = start(&["#####", "#@$ #", "#####"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Pushed {
+                off_goal: false,
+                onto_goal: false
+            }
+        );
+        assert_eq!(rows(&attempt), ["#####", "# @$#", "#####"]);
+        assert_eq!(attempt.player_position, Pos::new(2, 1));
+    }
+
+    #[test]
+    fn push_onto_and_off_a_goal() {
+        let mut attempt = start(&["######", "#@$. #", "######"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Pushed {
+                off_goal: false,
+                onto_goal: true
+            }
+        );
+        assert_eq!(rows(&attempt), ["######", "# @* #", "######"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Pushed {
+                off_goal: true,
+                onto_goal: false
+            }
+        );
+        assert_eq!(rows(&attempt), ["######", "#  +$#", "######"]);
+    }
+
+    #[test]
+    fn walking_into_a_wall_bumps() {
+        let mut attempt = start(&["###", "#@#", "###"]);
+        for direction in [
+            MoveDirection::Up,
+            MoveDirection::Right,
+            MoveDirection::Down,
+            MoveDirection::Left,
+        ] {
+            assert_eq!(player_move(&direction, &mut attempt), MoveResult::Bumped);
+        }
+        assert_eq!(rows(&attempt), ["###", "#@#", "###"]);
+    }
+
+    #[test]
+    fn stepping_off_the_board_bumps() {
+        let mut attempt = start(&["@ "]);
+        assert_eq!(
+            player_move(&MoveDirection::Left, &mut attempt),
+            MoveResult::Bumped
+        );
+        assert_eq!(attempt.player_position, Pos::new(0, 0));
+    }
+
+    #[test]
+    fn a_box_against_a_wall_or_a_box_is_blocked() {
+        let mut attempt = start(&["####", "#@$#", "####"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Blocked(Blocked::Wall)
+        );
+        assert_eq!(rows(&attempt), ["####", "#@$#", "####"]);
+        let mut attempt = start(&["######", "#@$$ #", "######"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Blocked(Blocked::Box(Pos::new(3, 1)))
+        );
+        assert_eq!(attempt.player_position, Pos::new(1, 1));
+    }
+
+    #[test]
+    fn a_box_on_a_goal_is_blocked_the_same_way() {
+        let mut attempt = start(&["####", "#@*#", "####"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Blocked(Blocked::Wall)
+        );
+        let mut attempt = start(&["######", "#@$* #", "######"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Blocked(Blocked::Box(Pos::new(3, 1)))
+        );
+        let mut attempt = start(&["#######", "#@*$  #", "#######"]);
+        assert_eq!(
+            player_move(&MoveDirection::Right, &mut attempt),
+            MoveResult::Blocked(Blocked::Box(Pos::new(3, 1)))
+        );
+        assert_eq!(rows(&attempt), ["#######", "#@*$  #", "#######"]);
+    }
+
+    #[test]
+    fn solved_once_every_box_is_on_a_goal() {
+        let mut attempt = start(&["#####", "#@$.#", "#####"]);
+        assert!(!attempt.solved());
+        player_move(&MoveDirection::Right, &mut attempt);
+        assert!(attempt.solved());
+        let result = attempt.finish().unwrap();
+        assert_eq!(result.level, Level::File);
+        assert!(attempt.level.is_none());
+    }
+
+    /// A game with Level One started, played without sound.
+    fn level_one(args: &[&str]) -> GameState {
+        let mut game_state = crate::tests::game(args);
+        let grid = pad_rows(crate::levels::level_layout(Level::One));
+        let player = crate::levels::validate_level(&grid).ok().unwrap();
+        game_state.attempt = Attempt::start(Level::One, (grid, player));
+        game_state
+    }
+
+    fn steps(game_state: &mut GameState, lurd: &str) {
+        for direction in moves_from_lurd(lurd).unwrap() {
+            player_move(&direction, &mut game_state.attempt);
+            game_state.attempt.moves.push(direction);
+            game_state.attempt.move_count += 1;
+        }
+    }
+
+    #[test]
+    fn no_win_until_the_board_is_solved() {
+        let mut game_state = level_one(&[]);
+        steps(&mut game_state, "u");
+        assert!(game_state.finish_if_solved().is_none());
+        assert_eq!(game_state.attempt.level, Some(Level::One));
+    }
+
+    #[test]
+    fn a_first_solve_is_a_record_and_a_first_try() {
+        let mut game_state = level_one(&[]);
+        steps(&mut game_state, "l");
+        let win = game_state.finish_if_solved().unwrap();
+        assert!(win.record && win.first_try);
+        assert_eq!((win.pushes, win.high_score), (1, None));
+        assert!(game_state.attempt.level.is_none());
+        let one = LevelId::BuiltIn(Level::One);
+        let profile = &game_state.profile;
+        assert_eq!(profile.scores.get(&one), Some(&1));
+        assert_eq!(profile.push_records.get(&one), Some(&1));
+        assert_eq!(
+            profile.best_runs.get(&one).map(|run| lurd(run)),
+            Some("l".to_string())
+        );
+        assert!(profile.first_tries.contains(&one));
+        assert!(profile.solved_at.contains_key(&one));
+    }
+
+    #[test]
+    fn a_longer_solve_keeps_the_record_it_did_not_beat() {
+        let mut game_state = level_one(&[]);
+        steps(&mut game_state, "l");
+        game_state.finish_if_solved().unwrap();
+        let grid = pad_rows(crate::levels::level_layout(Level::One));
+        game_state.attempt = Attempt::start(Level::One, (grid, Pos::new(3, 2)));
+        game_state.attempt.pristine = false;
+        steps(&mut game_state, "dul");
+        let win = game_state.finish_if_solved().unwrap();
+        assert!(!win.record && !win.first_try);
+        assert_eq!(win.high_score, Some(1));
+        let one = LevelId::BuiltIn(Level::One);
+        assert_eq!(game_state.profile.scores.get(&one), Some(&1));
+        assert_eq!(
+            game_state.profile.best_runs.get(&one).map(|run| lurd(run)),
+            Some("l".to_string())
+        );
+        assert_eq!(game_state.profile.pristine_completions, 1);
+    }
+
+    #[test]
+    fn a_scrambled_solve_keeps_no_records() {
+        let mut game_state = level_one(&[]);
+        game_state.profile.settings.scramble = Some(7);
+        steps(&mut game_state, "l");
+        let win = game_state.finish_if_solved().unwrap();
+        assert!(!win.record && !win.first_try);
+        assert!(game_state.profile.scores.is_empty());
+        assert!(game_state.profile.first_tries.is_empty());
+    }
+
+    fn grid(rows: &[&str]) -> Vec<Vec<Tile>> {
+        rows.iter()
+            .map(|row| row.chars().map(|c| Tile::try_from(c).unwrap()).collect())
+            .collect()
+    }
+
+    fn text(grid: &[Vec<Tile>]) -> Vec<String> {
+        grid.iter()
+            .map(|row| row.iter().map(|tile| char::from(*tile)).collect())
+            .collect()
+    }
+
+    /// All eight transforms, the identity first.
+    fn transforms() -> Vec<Transform> {
+        [false, true]
+            .into_iter()
+            .flat_map(|mirror| (0..4).map(move |turns| Transform { mirror, turns }))
+            .collect()
+    }
+
+    const DIRECTIONS: [MoveDirection; 4] = [
+        MoveDirection::Up,
+        MoveDirection::Right,
+        MoveDirection::Down,
+        MoveDirection::Left,
+    ];
+
+    #[test]
+    fn transforms_carry_the_corners_of_a_board_that_is_not_square() {
+        let board = grid(&["#@.", "$* "]);
+        let expect = [
+            (false, 0, vec!["#@.", "$* "]),
+            (false, 1, vec!["$#", "*@", " ."]),
+            (false, 2, vec![" *$", ".@#"]),
+            (false, 3, vec![". ", "@*", "#$"]),
+            (true, 0, vec![".@#", " *$"]),
+            (true, 1, vec![" .", "*@", "$#"]),
+            (true, 2, vec!["$* ", "#@."]),
+            (true, 3, vec!["#$", "@*", ". "]),
+        ];
+        for (mirror, turns, rows) in expect {
+            let transform = Transform { mirror, turns };
+            assert_eq!(text(&transform.grid(&board)), rows, "{:?}", transform);
+        }
+    }
+
+    #[test]
+    fn a_ragged_board_is_padded_before_it_is_transformed() {
+        let board = grid(&["###", "#"]);
+        let mirror = Transform {
+            mirror: true,
+            turns: 0,
+        };
+        assert_eq!(text(&mirror.grid(&board)), ["###", "  #"]);
+        let turn = Transform {
+            mirror: false,
+            turns: 1,
+        };
+        assert_eq!(text(&turn.grid(&board)), ["##", " #", " #"]);
+    }
+
+    #[test]
+    fn a_quarter_turn_and_a_mirror_remap_lurd() {
+        let turn = Transform {
+            mirror: false,
+            turns: 1,
+        };
+        let mirror = Transform {
+            mirror: true,
+            turns: 0,
+        };
+        let remapped = |transform: Transform| {
+            let moves: Vec<MoveDirection> = DIRECTIONS
+                .iter()
+                .map(|direction| transform.direction(direction))
+                .collect();
+            lurd(&moves)
+        };
+        assert_eq!(remapped(turn), "rdlu");
+        assert_eq!(remapped(mirror), "uldr");
+        let both = Transform {
+            mirror: true,
+            turns: 1,
+        };
+        assert_eq!(remapped(both), "ruld");
+    }
+
+    #[test]
+    fn a_move_transformed_does_on_the_transformed_board_what_it_did_on_the_original() {
+        let board = grid(&["#######", "#  .  #", "# @$  #", "#     #", "#######"]);
+        for transform in transforms() {
+            for direction in &DIRECTIONS {
+                let mut original = Attempt::start(Level::File, (board.clone(), Pos::new(2, 2)));
+                player_move(direction, &mut original);
+                let turned = transform.grid(&board);
+                let player = (0..turned.len())
+                    .flat_map(|y| (0..turned[y].len()).map(move |x| Pos::new(x as i32, y as i32)))
+                    .find(|pos| cell_at(&turned, *pos).is_player())
+                    .unwrap();
+                let mut moved = Attempt::start(Level::File, (turned, player));
+                player_move(&transform.direction(direction), &mut moved);
+                assert_eq!(
+                    text(&moved.grid),
+                    text(&transform.grid(&original.grid)),
+                    "{:?} {:?}",
+                    transform,
+                    direction
+                );
+                assert_eq!(transform.undo(&transform.direction(direction)), *direction);
+            }
+        }
+    }
+}
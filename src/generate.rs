@@ -0,0 +1,151 @@
+//! Random levels that can always be solved. A room is carved out, every
+//! box is put on a goal, and the player then pulls boxes about at random.
+//! A pull undone is a push, so playing the pulls backwards solves the
+//! board.
+
+use std::collections::HashSet;
+
+use super::game::{cell_at, offset, reachable, set_grid_cell, Pos, Tile};
+
+/// Rooms tried before giving up on a seed.
+const TRIES: usize = 100;
+/// Pulls made per box.
+const PULLS: usize = 40;
+
+/// SplitMix64, so the same seed always makes the same board.
+#[derive(Clone)]
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut mixed = self.0;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d049bb133111eb);
+        mixed ^ (mixed >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// A board with `boxes` boxes in a room of `width` by `height` cells inside
+/// its walls. Boards where some box never left its goal are thrown away,
+/// as are rooms with nowhere to pull to, until one comes out right.
+pub fn generate(
+    seed: u64,
+    boxes: usize,
+    (width, height): (usize, usize),
+) -> Result<Vec<Vec<Tile>>, String> {
+    if boxes == 0 {
+        return Err("a random level needs at least one box".to_string());
+    }
+    let mut rng = Rng(seed);
+    (0..TRIES)
+        .find_map(|_| scramble(&mut rng, boxes, width, height))
+        .ok_or_else(|| {
+            format!(
+                "no {}x{} room with {} boxes could be made, try fewer boxes or a bigger room",
+                width, height, boxes
+            )
+        })
+}
+
+/// One try: a room with a few pillars, boxes on goals, then the pulls.
+fn scramble(rng: &mut Rng, boxes: usize, width: usize, height: usize) -> Option<Vec<Vec<Tile>>> {
+    let mut grid = vec![vec![Tile::Wall; width + 2]; height + 2];
+    for row in &mut grid[1..=height] {
+        row[1..=width].fill(Tile::Floor);
+    }
+    // Pillars, each kept only if every floor cell can still be reached.
+    for _ in 0..width * height / 6 {
+        let pos = Pos::new(
+            (rng.below(width) + 1) as i32,
+            (rng.below(height) + 1) as i32,
+        );
+        set_grid_cell(&mut grid, pos, Tile::Wall);
+        let floor = floor(&grid);
+        if floor.is_empty() || reachable(&grid, floor[0]).len() != floor.len() {
+            set_grid_cell(&mut grid, pos, Tile::Floor);
+        }
+    }
+
+    let mut cells = floor(&grid);
+    if cells.len() <= boxes {
+        return None;
+    }
+    for idx in (1..cells.len()).rev() {
+        cells.swap(idx, rng.below(idx + 1));
+    }
+    let goals: HashSet<Pos> = cells[..boxes].iter().copied().collect();
+    let mut crates: Vec<Pos> = cells[..boxes].to_vec();
+    let mut player = cells[boxes];
+    let mut moved = vec![false; boxes];
+
+    for _ in 0..boxes * PULLS {
+        let mut board = grid.clone();
+        for pos in &crates {
+            set_grid_cell(&mut board, *pos, Tile::Box);
+        }
+        let reach = reachable(&board, player);
+        // The player stands next to a box and steps back, taking it along.
+        let mut pulls = vec![];
+        for (idx, pos) in crates.iter().enumerate() {
+            for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                let Some(stand) = offset(&board, *pos, dx, dy) else {
+                    continue;
+                };
+                let Some(to) = offset(&board, stand, dx, dy) else {
+                    continue;
+                };
+                if reach.contains(&stand) && cell_at(&board, to) == Tile::Floor {
+                    pulls.push((idx, stand, to));
+                }
+            }
+        }
+        if pulls.is_empty() {
+            break;
+        }
+        let (idx, stand, to) = pulls[rng.below(pulls.len())];
+        crates[idx] = stand;
+        player = to;
+        moved[idx] = true;
+    }
+    if moved.contains(&false) || crates.iter().all(|pos| goals.contains(pos)) {
+        return None;
+    }
+
+    // Walking is undone by walking back, so the player may start anywhere
+    // they can reach.
+    let mut board = grid.clone();
+    for pos in &crates {
+        set_grid_cell(&mut board, *pos, Tile::Box);
+    }
+    let mut reach: Vec<Pos> = reachable(&board, player).into_iter().collect();
+    reach.sort();
+    player = reach[rng.below(reach.len())];
+
+    for pos in &goals {
+        set_grid_cell(&mut grid, *pos, Tile::Goal);
+    }
+    for pos in &crates {
+        let tile = cell_at(&grid, *pos).with_box();
+        set_grid_cell(&mut grid, *pos, tile);
+    }
+    let tile = cell_at(&grid, player).with_player();
+    set_grid_cell(&mut grid, player, tile);
+    Some(grid)
+}
+
+fn floor(grid: &[Vec<Tile>]) -> Vec<Pos> {
+    let mut cells = vec![];
+    for (y, row) in grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if *cell == Tile::Floor {
+                cells.push(Pos::new(x as i32, y as i32));
+            }
+        }
+    }
+    cells
+}
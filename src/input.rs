@@ -0,0 +1,334 @@
+//! Keys to commands: the default bindings, the player's own from
+//! `keys.txt`, and chords of two keys.
+
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use super::{game::MoveDirection, levels::Level, Command};
+
+/// Bindings are one key, or a two-key chord whose first key is held pending
+/// until the second arrives or `CHORD_TIMEOUT` passes.
+pub const DEFAULT_BINDINGS: &[(&[KeyCode], Command)] = &[
+    (&[KeyCode::Char('q')], Command::Quit),
+    (&[KeyCode::Char('m')], Command::LevelChoose),
+    (
+        &[KeyCode::Char('g'), KeyCode::Char('l')],
+        Command::LevelChoose,
+    ),
+    (&[KeyCode::Char('w')], Command::Move(MoveDirection::Up)),
+    (&[KeyCode::Char('a')], Command::Move(MoveDirection::Left)),
+    (&[KeyCode::Char('s')], Command::Move(MoveDirection::Down)),
+    (&[KeyCode::Char('d')], Command::Move(MoveDirection::Right)),
+    (&[KeyCode::Char('1')], Command::LevelSelect(Level::One)),
+    (&[KeyCode::Char('2')], Command::LevelSelect(Level::Two)),
+    (&[KeyCode::Char('3')], Command::LevelSelect(Level::Three)),
+    (&[KeyCode::Char('4')], Command::LevelSelect(Level::Four)),
+    (&[KeyCode::Char('5')], Command::LevelSelect(Level::Five)),
+    (&[KeyCode::Char('6')], Command::LevelSelect(Level::Random)),
+    (&[KeyCode::Char('7')], Command::LevelSelect(Level::Daily)),
+    (&[KeyCode::Char('r')], Command::Reset),
+    (&[KeyCode::Char('R')], Command::OpenRestartPrompt),
+    (&[KeyCode::Char('b')], Command::ReverseMove),
+    (&[KeyCode::Char('!')], Command::ExplainDeadlock),
+    (&[KeyCode::Char('C')], Command::ToggleRuler),
+    (&[KeyCode::Char('H')], Command::ToggleHeatmap),
+    (&[KeyCode::Char('o')], Command::ToggleReachable),
+    (&[KeyCode::Char('N')], Command::StopNudge),
+    (&[KeyCode::Char('i')], Command::InstantReplay),
+    (&[KeyCode::Up], Command::MenuUp),
+    (&[KeyCode::Down], Command::MenuDown),
+    (&[KeyCode::Enter], Command::MenuConfirm),
+    (&[KeyCode::Esc], Command::MenuCancel),
+    (&[KeyCode::Char('/')], Command::OpenFilter),
+    (&[KeyCode::Char('W')], Command::WhatsNew),
+    (&[KeyCode::Char('n')], Command::NextLevel),
+    (&[KeyCode::Char('p')], Command::PrevLevel),
+    (&[KeyCode::Char('P')], Command::OpenReport),
+    (&[KeyCode::F(10)], Command::Screenshot),
+    (&[KeyCode::Char('E')], Command::ExportMoves),
+    (&[KeyCode::Char('e')], Command::ExportBoard),
+    (&[KeyCode::Char('A')], Command::ArcadeScores),
+    (&[KeyCode::Char('c')], Command::Continue),
+    (&[KeyCode::Char('V')], Command::ForgetViews),
+    (&[KeyCode::Char('S')], Command::OpenSaveLevel),
+    (&[KeyCode::Char('t')], Command::CycleVariant),
+    (&[KeyCode::Char('K')], Command::ToggleKids),
+    (&[KeyCode::Char('D')], Command::OpenEditor),
+    (
+        &[KeyCode::Char('z'), KeyCode::Char('z')],
+        Command::CenterCamera,
+    ),
+];
+
+/// The commands `keys.txt` can bind, by the names it gives them.
+pub const COMMAND_NAMES: &[(&str, Command)] = &[
+    ("quit", Command::Quit),
+    ("level_select", Command::LevelChoose),
+    ("up", Command::Move(MoveDirection::Up)),
+    ("left", Command::Move(MoveDirection::Left)),
+    ("down", Command::Move(MoveDirection::Down)),
+    ("right", Command::Move(MoveDirection::Right)),
+    ("level_1", Command::LevelSelect(Level::One)),
+    ("level_2", Command::LevelSelect(Level::Two)),
+    ("level_3", Command::LevelSelect(Level::Three)),
+    ("level_4", Command::LevelSelect(Level::Four)),
+    ("level_5", Command::LevelSelect(Level::Five)),
+    ("random", Command::LevelSelect(Level::Random)),
+    ("daily", Command::LevelSelect(Level::Daily)),
+    ("reset", Command::Reset),
+    ("restart_at", Command::OpenRestartPrompt),
+    ("undo", Command::ReverseMove),
+    ("explain_deadlock", Command::ExplainDeadlock),
+    ("ruler", Command::ToggleRuler),
+    ("heatmap", Command::ToggleHeatmap),
+    ("reachable", Command::ToggleReachable),
+    ("stop_nudge", Command::StopNudge),
+    ("instant_replay", Command::InstantReplay),
+    ("menu_up", Command::MenuUp),
+    ("menu_down", Command::MenuDown),
+    ("confirm", Command::MenuConfirm),
+    ("cancel", Command::MenuCancel),
+    ("filter", Command::OpenFilter),
+    ("whats_new", Command::WhatsNew),
+    ("next_level", Command::NextLevel),
+    ("prev_level", Command::PrevLevel),
+    ("report", Command::OpenReport),
+    ("screenshot", Command::Screenshot),
+    ("export_moves", Command::ExportMoves),
+    ("export_board", Command::ExportBoard),
+    ("arcade_scores", Command::ArcadeScores),
+    ("continue", Command::Continue),
+    ("forget_views", Command::ForgetViews),
+    ("save_level", Command::OpenSaveLevel),
+    ("variant", Command::CycleVariant),
+    ("kids", Command::ToggleKids),
+    ("center_camera", Command::CenterCamera),
+    ("editor", Command::OpenEditor),
+];
+
+pub const CHORD_TIMEOUT: Duration = Duration::from_millis(800);
+
+/// Keys that answer yes/no prompts. A chord starting with one of them would
+/// hold the answer back for the whole chord timeout.
+pub const RESERVED_KEYS: [KeyCode; 3] = [KeyCode::Char('y'), KeyCode::Char('n'), KeyCode::Esc];
+
+#[derive(Clone)]
+pub struct Keymap {
+    pub bindings: Vec<(Vec<KeyCode>, Command)>,
+    pub pending: Option<(KeyCode, Instant)>,
+}
+
+impl Keymap {
+    pub fn new(bindings: &[(&[KeyCode], Command)]) -> Result<Keymap, String> {
+        Keymap::from_bindings(
+            bindings
+                .iter()
+                .map(|(keys, command)| (keys.to_vec(), command.clone()))
+                .collect(),
+        )
+    }
+
+    fn from_bindings(bindings: Vec<(Vec<KeyCode>, Command)>) -> Result<Keymap, String> {
+        for (keys, _) in &bindings {
+            match keys.as_slice() {
+                [_] => {}
+                [prefix, _] if RESERVED_KEYS.contains(prefix) => {
+                    return Err(format!("chord prefix {} shadows a reserved key", prefix));
+                }
+                [_, _] => {}
+                _ => return Err("a binding must be one key or a two-key chord".to_string()),
+            }
+        }
+        Ok(Keymap {
+            bindings,
+            pending: None,
+        })
+    }
+
+    /// The default bindings with the player's own from `path` on top, or
+    /// just the defaults without the file.
+    pub fn load(path: Option<&Path>) -> Result<Keymap, String> {
+        match path.and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => Keymap::with_bindings(&text),
+            None => Keymap::new(DEFAULT_BINDINGS),
+        }
+    }
+
+    /// Each line of `text` is one key, or the two keys of a chord, then the
+    /// name of a command from `COMMAND_NAMES`; `#` starts a comment. Keys
+    /// the player binds lose their default binding.
+    pub fn with_bindings(text: &str) -> Result<Keymap, String> {
+        let mut bindings: Vec<(Vec<KeyCode>, Command)> = DEFAULT_BINDINGS
+            .iter()
+            .map(|(keys, command)| (keys.to_vec(), command.clone()))
+            .collect();
+        for (number, line) in text.lines().enumerate() {
+            let mut words: Vec<&str> = line.split('#').next().unwrap().split_whitespace().collect();
+            let Some(name) = words.pop() else {
+                continue;
+            };
+            let command = COMMAND_NAMES
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, command)| command.clone())
+                .ok_or_else(|| format!("line {}: no command called {}", number + 1, name))?;
+            let keys = words
+                .iter()
+                .map(|word| {
+                    key_code(word)
+                        .ok_or_else(|| format!("line {}: no key called {}", number + 1, word))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            bindings.retain(|(bound, _)| *bound != keys);
+            bindings.push((keys, command));
+        }
+        Keymap::from_bindings(bindings)
+    }
+
+    pub fn lookup(&self, keys: &[KeyCode]) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == keys)
+            .map(|(_, command)| command.clone())
+    }
+
+    pub fn starts_chord(&self, key: KeyCode) -> bool {
+        self.bindings
+            .iter()
+            .any(|(bound, _)| bound.len() == 2 && bound[0] == key)
+    }
+
+    /// The keys bound to `command` for on-screen hints, such as `[m] or [g l]`.
+    pub fn hint(&self, command: &Command) -> String {
+        let hints: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| bound == command)
+            .map(|(keys, _)| {
+                let keys: Vec<String> = keys.iter().map(KeyCode::to_string).collect();
+                format!("[{}]", keys.join(" "))
+            })
+            .collect();
+        if hints.is_empty() {
+            "(unbound)".to_string()
+        } else {
+            hints.join(" or ")
+        }
+    }
+
+    /// Gives up on a chord whose second key never came, falling back to
+    /// whatever the prefix means on its own.
+    pub fn expire_chord(&mut self) -> Option<Command> {
+        match self.pending {
+            Some((prefix, since)) if since.elapsed() >= CHORD_TIMEOUT => {
+                self.pending = None;
+                self.lookup(&[prefix])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A key as `keys.txt` writes it: the character itself, or the name
+/// crossterm shows for it, such as `Enter` or `F10`.
+fn key_code(word: &str) -> Option<KeyCode> {
+    let mut chars = word.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let named = match word.to_ascii_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        name => return name.strip_prefix('f')?.parse().ok().map(KeyCode::F),
+    };
+    Some(named)
+}
+
+pub fn read_input(keymap: &mut Keymap, key: KeyEvent) -> Vec<Command> {
+    if key.kind != KeyEventKind::Press {
+        return vec![];
+    }
+    // Bindings are plain keys; Ctrl+Z means undo everywhere, so it is fixed.
+    if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        keymap.pending = None;
+        return vec![Command::UndoReset];
+    }
+    let mut commands = vec![];
+    if let Some((prefix, _)) = keymap.pending.take() {
+        if let Some(command) = keymap.lookup(&[prefix, key.code]) {
+            return vec![command];
+        }
+        commands.extend(keymap.lookup(&[prefix]));
+    }
+    if keymap.starts_chord(key.code) {
+        keymap.pending = Some((key.code, Instant::now()));
+    } else {
+        commands.extend(keymap.lookup(&[key.code]));
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(keymap: &mut Keymap, c: char) -> Vec<Command> {
+        read_input(keymap, KeyEvent::from(KeyCode::Char(c)))
+    }
+
+    #[test]
+    fn z_z_centres_the_camera() {
+        let mut keymap = Keymap::new(DEFAULT_BINDINGS).unwrap();
+        assert!(press(&mut keymap, 'z').is_empty());
+        assert!(press(&mut keymap, 'z') == vec![Command::CenterCamera]);
+    }
+
+    #[test]
+    fn a_chord_from_keys_txt_is_bound() {
+        let mut keymap = Keymap::with_bindings("# mine\nx c undo # take it back\n").unwrap();
+        assert!(press(&mut keymap, 'x').is_empty());
+        assert!(press(&mut keymap, 'c') == vec![Command::ReverseMove]);
+        assert!(
+            keymap.lookup(&[KeyCode::Char('g'), KeyCode::Char('l')]) == Some(Command::LevelChoose)
+        );
+    }
+
+    #[test]
+    fn a_key_from_keys_txt_replaces_its_default() {
+        let keymap = Keymap::with_bindings("r undo\nF2 reset").unwrap();
+        assert!(keymap.lookup(&[KeyCode::Char('r')]) == Some(Command::ReverseMove));
+        assert_eq!(keymap.hint(&Command::Reset), "[F2]");
+    }
+
+    #[test]
+    fn a_chord_from_keys_txt_may_not_start_with_a_reserved_key() {
+        let error = Keymap::with_bindings("Esc q quit").err().unwrap();
+        assert_eq!(error, "chord prefix Esc shadows a reserved key");
+        assert!(Keymap::with_bindings("y u undo").is_err());
+    }
+
+    #[test]
+    fn keys_txt_names_the_line_it_cannot_read() {
+        assert_eq!(
+            Keymap::with_bindings("u undo\nu jump").err().unwrap(),
+            "line 2: no command called jump"
+        );
+        assert_eq!(
+            Keymap::with_bindings("\nF99x undo").err().unwrap(),
+            "line 2: no key called F99x"
+        );
+        assert!(Keymap::with_bindings("a b c undo").is_err());
+    }
+}
@@ -0,0 +1,463 @@
+//! The built-in levels and level files: reading boards in XSB and RLE
+//! notation and checking that a board can be played.
+
+use std::collections::HashSet;
+
+use super::{
+    game::{cell_at, offset, Pos, Tile},
+    LevelInfo,
+};
+
+#[derive(PartialEq, Clone, Copy, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum Level {
+    One,
+    Two,
+    Three,
+    Four,
+    Five,
+    /// The level read from the file named on the command line.
+    File,
+    /// A board made up from `Ui::random_seed`, see `generate`.
+    Random,
+    /// The same random board for everyone on a given day, see `Ui::daily`.
+    Daily,
+}
+
+/// Which level a record is for: a built-in one, or a board of a level file
+/// by the file's absolute path and its place in the file. Every board of a
+/// file plays as `Level::File`; this tells them apart.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum LevelId {
+    BuiltIn(Level),
+    Pack { file: String, index: usize },
+}
+
+pub const LEVELS: [Level; 5] = [
+    Level::One,
+    Level::Two,
+    Level::Three,
+    Level::Four,
+    Level::Five,
+];
+
+pub const LEVEL_MENU: [(Level, &str); 6] = [
+    (Level::One, "Tutorial"),
+    (Level::Two, "Easy"),
+    (Level::Three, "Medium"),
+    (Level::Four, "Hard"),
+    (Level::Random, "Random"),
+    (Level::Daily, "Daily"),
+];
+
+pub fn level_name(level: Level) -> &'static str {
+    LEVEL_MENU
+        .iter()
+        .find(|(listed, _)| *listed == level)
+        .map_or("", |(_, name)| name)
+}
+
+/// Pads every row with floor to the width of the widest, so any cell inside
+/// the board's bounding box can be looked at.
+pub fn pad_rows(mut grid: Vec<Vec<Tile>>) -> Vec<Vec<Tile>> {
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut grid {
+        row.resize(width, Tile::Floor);
+    }
+    grid
+}
+
+/// The board of a built-in level. Where the player starts is read off the
+/// grid, see `validate_level`.
+pub fn level_layout(level: Level) -> Vec<Vec<Tile>> {
+    let rows: Vec<Vec<char>> = match level {
+        // Read from a file into the profile, see `load_collection`, or
+        // made up in `start_level`.
+        Level::File | Level::Random | Level::Daily => vec![],
+        Level::One => vec![
+            vec!['#', '#', '#', '#', '#'],
+            vec!['#', ' ', ' ', ' ', '#'],
+            vec!['#', '.', '$', '@', '#'],
+            vec!['#', ' ', ' ', ' ', '#'],
+            vec!['#', '#', '#', '#', '#'],
+        ],
+        Level::Two => vec![
+            vec![' ', ' ', ' ', ' ', ' ', '#', '#', '#', '#'],
+            vec!['#', '#', '#', '#', '#', '#', ' ', ' ', '#'],
+            vec!['#', ' ', ' ', ' ', ' ', ' ', ' ', ' ', '#'],
+            vec!['#', ' ', ' ', ' ', ' ', ' ', ' ', '.', '#'],
+            vec!['#', '@', ' ', '#', '#', '#', '#', '#', '#', '#'],
+            vec!['#', '#', ' ', ' ', ' ', ' ', ' ', ' ', ' ', '#'],
+            vec![' ', '#', ' ', '#', ' ', '#', ' ', ' ', ' ', '#'],
+            vec![' ', '#', ' ', ' ', ' ', ' ', ' ', '$', ' ', '#'],
+            vec![' ', '#', ' ', ' ', ' ', '#', '#', '#', '#', '#'],
+            vec![' ', '#', '#', '#', '#', '#'],
+        ],
+        Level::Three => vec![
+            vec![
+                '#', '#', '#', '#', '#', ' ', ' ', '#', '#', '#', '#', ' ', ' ', '#', '#', '#',
+                '#', '#',
+            ],
+            vec![
+                '#', ' ', ' ', ' ', '#', '#', '#', '#', ' ', ' ', '#', '#', '#', '#', ' ', ' ',
+                ' ', '#',
+            ],
+            vec![
+                '#', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
+                ' ', '#',
+            ],
+            vec![
+                '#', '#', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', '#', '#', '#', ' ', ' ', ' ',
+                '#', '#',
+            ],
+            vec![
+                ' ', '#', '#', ' ', '$', ' ', ' ', '#', ' ', '.', '.', ' ', '$', ' ', '@', '#', '#',
+            ],
+            vec![
+                '#', '#', ' ', ' ', '#', '#', ' ', ' ', ' ', '#', '#', '#', '#', ' ', ' ', ' ',
+                '#', '#',
+            ],
+            vec![
+                '#', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
+                ' ', '#',
+            ],
+            vec![
+                '#', ' ', ' ', ' ', '#', '#', '#', '#', '#', '#', '#', '#', '#', '#', ' ', ' ',
+                ' ', '#',
+            ],
+            vec![
+                '#', '#', '#', '#', '#', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', '#', '#', '#',
+                '#', '#',
+            ],
+        ],
+        Level::Four => vec![
+            vec![' ', '#', '#', '#', '#', '#'],
+            vec!['#', '#', ' ', ' ', ' ', '#'],
+            vec!['#', ' ', ' ', ' ', ' ', '#', '#'],
+            vec!['#', ' ', ' ', '#', ' ', ' ', '#'],
+            vec!['#', ' ', '$', '#', ' ', '.', '#', '#', '#'],
+            vec!['#', ' ', ' ', '#', '*', '.', ' ', ' ', '#'],
+            vec!['#', ' ', '$', ' ', '$', '.', ' ', ' ', '#'],
+            vec!['#', ' ', ' ', '#', '$', '.', '#', '#', '#'],
+            vec!['#', '#', '#', '#', ' ', '.', '#'],
+            vec![' ', ' ', '#', '#', '$', '.', '#'],
+            vec![' ', ' ', '#', ' ', '$', '*', '#'],
+            vec![' ', ' ', '#', ' ', ' ', '@', '#'],
+            vec![' ', ' ', '#', '#', '#', '#', '#'],
+        ],
+        Level::Five => vec![
+            vec![' ', '#', '#', '#', '#'],
+            vec!['#', '#', ' ', ' ', '#', '#', '#'],
+            vec!['#', ' ', ' ', ' ', ' ', ' ', '#', '#', '#'],
+            vec!['#', ' ', '#', '*', '*', '*', '.', ' ', '#'],
+            vec!['#', ' ', ' ', '*', ' ', ' ', '#', ' ', '#'],
+            vec!['#', ' ', ' ', '*', ' ', ' ', ' ', ' ', '#'],
+            vec!['#', ' ', ' ', '*', '*', '*', '#', '#', '#', '#'],
+            vec!['#', '#', '#', '#', ' ', ' ', '*', ' ', ' ', '#'],
+            vec![' ', '#', ' ', '*', ' ', ' ', '*', ' ', ' ', '#'],
+            vec![' ', '#', ' ', '$', '*', '*', ' ', ' ', ' ', '#'],
+            vec![' ', '#', ' ', ' ', ' ', '@', '#', ' ', ' ', '#'],
+            vec![' ', '#', '#', '#', '#', '#', '#', '#', '#', '#'],
+        ],
+    };
+    // The boards above only hold XSB characters.
+    rows.into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|c| Tile::try_from(c).unwrap_or(Tile::Wall))
+                .collect()
+        })
+        .collect()
+}
+
+/// Parses the lines of one board, expanding run-length encoding. `-` and
+/// `_` also stand for floor.
+pub fn parse_board(lines: BoardLines) -> Result<Vec<Vec<Tile>>, String> {
+    let mut grid = vec![];
+    for (number, line) in lines {
+        let rows = expand_rle(line).map_err(|error| format!("{} on line {}", error, number + 1))?;
+        for text in rows {
+            let row = text
+                .chars()
+                .map(Tile::try_from)
+                .collect::<Result<_, _>>()
+                .map_err(|c| format!("unknown character '{}' on line {}", c, number + 1))?;
+            grid.push(row);
+        }
+    }
+    let grid = pad_rows(grid);
+    validate_level(&grid).map_err(|error| error.to_string())?;
+    Ok(grid)
+}
+
+/// Why a board cannot be played.
+pub enum LevelError {
+    NoPlayer,
+    SeveralPlayers(usize),
+    NothingToSolve,
+    BoxesAndGoals { boxes: usize, goals: usize },
+    NotEnclosed,
+}
+
+impl std::fmt::Display for LevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LevelError::NoPlayer => write!(f, "the board has no player (@ or +)"),
+            LevelError::SeveralPlayers(count) => write!(f, "the board has {} players", count),
+            LevelError::NothingToSolve => write!(f, "every box is already on a goal"),
+            LevelError::BoxesAndGoals { boxes, goals } => {
+                write!(f, "the board has {} boxes but {} goals", boxes, goals)
+            }
+            LevelError::NotEnclosed => write!(f, "the player can walk off the board"),
+        }
+    }
+}
+
+/// Checks that a board can be played and won: one player, as many boxes as
+/// goals with at least one box still to place, and walls all around the
+/// player's area. Gives where the player starts, on `@` or on a goal as `+`.
+pub fn validate_level(grid: &[Vec<Tile>]) -> Result<Pos, LevelError> {
+    let cells = || {
+        grid.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, cell)| (Pos::new(x as i32, y as i32), *cell))
+        })
+    };
+    let count = |wanted: fn(&Tile) -> bool| cells().filter(|(_, cell)| wanted(cell)).count();
+    let players: Vec<Pos> = cells()
+        .filter(|(_, cell)| cell.is_player())
+        .map(|(pos, _)| pos)
+        .collect();
+    let (boxes, goals) = (count(|cell| cell.is_box()), count(|cell| cell.is_goal()));
+    match players.as_slice() {
+        [] => Err(LevelError::NoPlayer),
+        [_, _, ..] => Err(LevelError::SeveralPlayers(players.len())),
+        _ if count(|cell| *cell == Tile::Box) == 0 => Err(LevelError::NothingToSolve),
+        _ if boxes != goals => Err(LevelError::BoxesAndGoals { boxes, goals }),
+        [player] if !enclosed(grid, *player) => Err(LevelError::NotEnclosed),
+        [player] => Ok(*player),
+    }
+}
+
+/// Whether walls shut in every cell reachable from `start`, found by a flood
+/// fill that fails as soon as it steps off the grid.
+pub fn enclosed(grid: &[Vec<Tile>], start: Pos) -> bool {
+    let mut seen = HashSet::from([start]);
+    let mut todo = vec![start];
+    while let Some(pos) = todo.pop() {
+        for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+            let Some(next) = offset(grid, pos, dx, dy) else {
+                return false;
+            };
+            if cell_at(grid, next) != Tile::Wall && seen.insert(next) {
+                todo.push(next);
+            }
+        }
+    }
+    true
+}
+
+/// The lines of one board in a level file, with their line numbers.
+pub type BoardLines<'a> = Vec<(usize, &'a str)>;
+
+/// The boards in a level file, with the `Title:` and `Author:` lines that
+/// follow each. A board line starts with a wall once leading floor is
+/// skipped, or in run-length encoded form holds only counts, `|` and board
+/// characters; anything else, such as titles, comments and blank lines,
+/// separates levels.
+pub fn boards(text: &str) -> Vec<(BoardLines<'_>, LevelInfo)> {
+    let mut boards: Vec<(BoardLines, LevelInfo)> = vec![];
+    let mut previous_was_board = false;
+    for (number, line) in text.lines().enumerate() {
+        if let (Some((key, value)), Some((_, info))) = (line.split_once(':'), boards.last_mut()) {
+            let value = Some(value.trim().to_string()).filter(|value| !value.is_empty());
+            match key.trim().to_lowercase().as_str() {
+                "title" => info.title = value,
+                "author" => info.author = value,
+                _ => {}
+            }
+        }
+        let is_board = if line.contains(|c: char| c.is_ascii_digit() || c == '|') {
+            line.contains('#') && line.chars().all(|c| "0123456789|#@+$*.-_ ".contains(c))
+        } else {
+            line.trim_start_matches([' ', '-', '_']).starts_with('#')
+        };
+        match boards.last_mut() {
+            Some((board, _)) if is_board && previous_was_board => board.push((number, line)),
+            _ if is_board => boards.push((vec![(number, line)], LevelInfo::default())),
+            _ => {}
+        }
+        previous_was_board = is_board;
+    }
+    boards
+}
+
+/// Longest run a count in run-length encoded XSB may ask for.
+pub const MAX_RUN: usize = 999;
+
+/// Expands run-length encoded XSB, where `3#` is `###` and `|` starts a new
+/// row, into plain rows. Lines without counts come back as they are.
+pub fn expand_rle(line: &str) -> Result<Vec<String>, String> {
+    let mut rows = vec![String::new()];
+    let mut count: Option<usize> = None;
+    for c in line.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            let run = count.unwrap_or(0) * 10 + digit as usize;
+            if run > MAX_RUN {
+                return Err(format!("run of more than {} cells", MAX_RUN));
+            }
+            count = Some(run);
+            continue;
+        }
+        match (c, count.take()) {
+            (_, Some(0)) => return Err(format!("count of 0 before '{}'", c)),
+            ('|', Some(run)) => return Err(format!("count {} before a row break", run)),
+            ('|', None) => rows.push(String::new()),
+            (_, run) => rows
+                .last_mut()
+                .unwrap()
+                .extend(std::iter::repeat_n(c, run.unwrap_or(1))),
+        }
+    }
+    if rows.len() > 1 && rows.last().is_some_and(String::is_empty) {
+        // A trailing `|` ends the last row rather than starting another.
+        rows.pop();
+    }
+    match count {
+        Some(run) => Err(format!("count {} at the end of the row", run)),
+        None => Ok(rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Vec<Vec<Tile>> {
+        pad_rows(
+            rows.iter()
+                .map(|row| row.chars().map(|c| Tile::try_from(c).unwrap()).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn a_playable_board_gives_where_the_player_starts() {
+        assert!(matches!(
+            validate_level(&grid(&["#####", "#@$.#", "#####"])),
+            Ok(pos) if pos == Pos::new(1, 1)
+        ));
+        assert!(matches!(
+            validate_level(&grid(&["#####", "#+$*#", "# $.#", "#####"])),
+            Ok(pos) if pos == Pos::new(1, 1)
+        ));
+    }
+
+    #[test]
+    fn every_built_in_level_starts_where_its_player_is_drawn() {
+        for level in LEVELS {
+            let grid = pad_rows(level_layout(level));
+            let drawn: Vec<Pos> = grid
+                .iter()
+                .enumerate()
+                .flat_map(|(y, row)| {
+                    row.iter()
+                        .enumerate()
+                        .filter(|(_, cell)| matches!(char::from(**cell), '@' | '+'))
+                        .map(move |(x, _)| Pos::new(x as i32, y as i32))
+                })
+                .collect();
+            assert!(
+                matches!(validate_level(&grid), Ok(pos) if drawn == [pos]),
+                "{:?}",
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn a_board_needs_exactly_one_player() {
+        assert!(matches!(
+            validate_level(&grid(&["#####", "# $.#", "#####"])),
+            Err(LevelError::NoPlayer)
+        ));
+        assert!(matches!(
+            validate_level(&grid(&["######", "#@$.+#", "######"])),
+            Err(LevelError::SeveralPlayers(2))
+        ));
+    }
+
+    #[test]
+    fn a_board_needs_as_many_boxes_as_goals_and_one_to_place() {
+        assert!(matches!(
+            validate_level(&grid(&["######", "#@$$.#", "######"])),
+            Err(LevelError::BoxesAndGoals { boxes: 2, goals: 1 })
+        ));
+        assert!(matches!(
+            validate_level(&grid(&["######", "#@$..#", "######"])),
+            Err(LevelError::BoxesAndGoals { boxes: 1, goals: 2 })
+        ));
+        assert!(matches!(
+            validate_level(&grid(&["#####", "#@* #", "#####"])),
+            Err(LevelError::NothingToSolve)
+        ));
+    }
+
+    #[test]
+    fn a_board_must_shut_the_player_in() {
+        assert!(matches!(
+            validate_level(&grid(&["#####", "#@$. ", "#####"])),
+            Err(LevelError::NotEnclosed)
+        ));
+        assert!(matches!(
+            validate_level(&grid(&["## ##", "#@$.#", "#####"])),
+            Err(LevelError::NotEnclosed)
+        ));
+        // Open floor outside the walls is never reached.
+        assert!(validate_level(&grid(&["  #####", "  #@$.#", "  #####"])).is_ok());
+    }
+
+    #[test]
+    fn counts_repeat_the_cell_after_them() {
+        assert_eq!(expand_rle("3#").unwrap(), ["###"]);
+        assert_eq!(expand_rle("#@2$.#").unwrap(), ["#@$$.#"]);
+        assert_eq!(expand_rle("12-#").unwrap(), ["------------#"]);
+        assert_eq!(expand_rle("#@$.#").unwrap(), ["#@$.#"]);
+    }
+
+    #[test]
+    fn bars_split_rows_of_mixed_runs() {
+        assert_eq!(
+            expand_rle("5#|#@$.#|5#|").unwrap(),
+            ["#####", "#@$.#", "#####"]
+        );
+        assert_eq!(expand_rle("2-3#|10 #").unwrap(), ["--###", "          #"]);
+        assert_eq!(expand_rle("#||#").unwrap(), ["#", "", "#"]);
+    }
+
+    #[test]
+    fn malformed_counts_are_errors() {
+        assert_eq!(expand_rle("0#").unwrap_err(), "count of 0 before '#'");
+        assert_eq!(
+            expand_rle("#3|#").unwrap_err(),
+            "count 3 before a row break"
+        );
+        assert_eq!(
+            expand_rle("##4").unwrap_err(),
+            "count 4 at the end of the row"
+        );
+    }
+
+    #[test]
+    fn runs_are_capped() {
+        assert_eq!(
+            expand_rle(&format!("{}#", MAX_RUN)).unwrap()[0].len(),
+            MAX_RUN
+        );
+        assert_eq!(
+            expand_rle(&format!("{}#", MAX_RUN + 1)).unwrap_err(),
+            format!("run of more than {} cells", MAX_RUN)
+        );
+        assert!(expand_rle("99999999999999999999999#").is_err());
+    }
+}
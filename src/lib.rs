@@ -0,0 +1,6117 @@
+//! A terminal Sokoban game. `App` runs the whole game inside any area of a
+//! ratatui frame, leaving the terminal itself to the host.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{self, File, TryLockError},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    vec,
+};
+
+use ratatui::{
+    buffer::Buffer,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::Rect,
+    style::Stylize,
+    text::{Line, Span},
+    Frame,
+};
+
+use assist::{Aid, Effect};
+use rodio::Sink;
+use std::io::{IsTerminal, Read, Write};
+
+mod assist;
+mod audio;
+mod convert;
+mod game;
+mod generate;
+mod input;
+mod levels;
+mod perf;
+mod rules;
+mod shutdown;
+mod solver;
+mod sounds;
+mod tips;
+mod ui;
+
+use audio::{
+    load_sounds, open_audio, play_cue, play_sound, play_sound_at_speed, push_cues, read_sound_pack,
+    restore_sound, SoundType, DEFAULT_SOUNDS,
+};
+use game::{
+    cell_at, find_deadlock, goals_filled, level_hash, level_id, lurd, moves_from_lurd, place,
+    player_move, reachable, set_grid_cell, Attempt, AttemptResult, Blocked, GameState, Ghost,
+    MoveDirection, MoveResult, Pos, Snapshot, Tile, Transform, Variant, Win, VARIANTS,
+};
+use input::{read_input, Keymap, DEFAULT_BINDINGS};
+use levels::{
+    boards, level_layout, level_name, pad_rows, parse_board, validate_level, Level, LevelId,
+    LEVELS, LEVEL_MENU,
+};
+use perf::Perf;
+use shutdown::{Failure, Shutdown, SHUTDOWN_TIMEOUT};
+use tips::{Tips, Topic};
+use ui::{coord_name, draw, screenshot, PreviewCache};
+
+pub use convert::convert;
+use solver::Verdict;
+pub use sounds::pack_sounds;
+use sounds::SoundPack;
+
+const TICK_RATE: Duration = Duration::from_millis(100);
+/// Tick interval in low-power mode while nothing is being replayed.
+const LOW_POWER_TICK_RATE: Duration = Duration::from_millis(250);
+const ATTRACT_STEP: Duration = Duration::from_millis(250);
+/// Boxes on a random board unless `--random-boxes` says otherwise.
+const DEFAULT_RANDOM_BOXES: usize = 3;
+const MAX_RANDOM_BOXES: usize = 12;
+/// The room of a random board, inside its walls.
+const DEFAULT_RANDOM_SIZE: (usize, usize) = (7, 5);
+/// The daily puzzle is the same size for everyone, whatever the options.
+const DAILY_BOXES: usize = 4;
+const DAILY_SIZE: (usize, usize) = (8, 6);
+/// Points each level of an arcade run starts with.
+const ARCADE_START: u32 = 1000;
+/// Points an arcade level loses every second it is being played.
+const ARCADE_DRAIN: u32 = 10;
+const ARCADE_PUSH_COST: u32 = 5;
+/// How many arcade runs the high-score screen keeps.
+const ARCADE_TABLE: usize = 5;
+/// How long each frame of a pack's celebration is shown.
+const CELEBRATION_FRAME: Duration = Duration::from_millis(200);
+const CELEBRATION_MAX_FRAMES: usize = 50;
+/// Columns and rows a celebration frame may take up.
+const CELEBRATION_MAX_SIZE: (usize, usize) = (60, 12);
+/// Positions the solver may look at while rating one board before it gives
+/// the board up as too hard to rate.
+const RATING_BUDGET: usize = 100_000;
+/// The most pushes earning each number of stars, from one up; anything
+/// longer gets five.
+const RATING_STARS: [u32; 4] = [3, 10, 25, 60];
+/// Positions the solver may look at to tell whether a board can still be
+/// solved. Kept small, since the answer is wanted before the next push.
+const SOLVABLE_BUDGET: usize = 200_000;
+/// How long quitting, or leaving kids mode, must be held down in kids mode.
+const KIDS_HOLD: Duration = Duration::from_secs(3);
+/// The longest gap between the repeats of a held key; a longer one means
+/// the key was let go.
+const KIDS_HOLD_GAP: Duration = Duration::from_millis(750);
+/// The most boxes a level may have to be listed in kids mode.
+const KIDS_MAX_BOXES: usize = 1;
+/// Played on every solve in kids mode, unless the pack has its own.
+const KIDS_CELEBRATION: &str = "
+     *
+    ***
+     *
+%%
+   \\ * /
+  -- *** --
+   / * \\
+%%
+ *  \\ | /  *
+  -- YAY! --
+ *  / | \\  *
+";
+const ATTRACT_HOLD: Duration = Duration::from_secs(2);
+const DEFAULT_ATTRACT_MINUTES: u64 = 2;
+
+/// Everything that outlives an attempt: records, badges, counters, and the
+/// settings and level file the game was started with.
+#[derive(Clone)]
+struct Profile {
+    /// Fewest moves each solved level took.
+    scores: HashMap<LevelId, i32>,
+    push_records: HashMap<LevelId, i32>,
+    best_runs: HashMap<LevelId, Vec<MoveDirection>>,
+    first_tries: HashSet<LevelId>,
+    clean_solves: HashSet<LevelId>,
+    pristine_completions: u32,
+    lifetime_wall_bumps: u32,
+    /// Milliseconds since the Unix epoch when each level was last solved.
+    solved_at: HashMap<LevelId, u64>,
+    /// Whether a move was ever taken back, so the tips on undo can stop
+    /// coming up so often.
+    used_undo: bool,
+    /// Fewest moves and fewest pushes on each variant of a level other than
+    /// `Variant::Normal`, kept apart from the records on its own board.
+    variant_records: HashMap<(LevelId, Variant), (i32, i32)>,
+    /// The shortest solve of each day's daily puzzle, by its UTC date.
+    /// Days are only ever added, so setting the clock back loses none.
+    dailies: BTreeMap<String, Vec<MoveDirection>>,
+    /// The best finished arcade runs, best first.
+    arcade_scores: Vec<ArcadeScore>,
+    /// Where each pack, by path, and the built-in levels, as `BUILT_IN`,
+    /// were last played.
+    last_played: HashMap<String, LastPlayed>,
+    /// The display toggles last used on each board, by `level_hash`.
+    views: HashMap<u64, View>,
+    /// Only boards with an id collect heat, so each board of a level file
+    /// keeps its own.
+    heat: HashMap<LevelId, Heat>,
+    last_version: Option<String>,
+    settings: Settings,
+    save: Save,
+    /// The boards behind `Level::File`, if a level file or pack was loaded.
+    collection: Option<Collection>,
+}
+
+/// The level of a pack last started, and whether it was solved.
+#[derive(Clone, Copy)]
+struct LastPlayed {
+    index: usize,
+    completed: bool,
+    /// Milliseconds since the Unix epoch, so the most recent can be
+    /// continued.
+    at: u64,
+}
+
+/// Which panels were open on a board, put back when it is started again.
+#[derive(Clone, Copy)]
+struct View {
+    ruler: bool,
+    show_heat: bool,
+    /// Milliseconds since the Unix epoch when the board was last played, so
+    /// the views of boards long gone can be dropped first.
+    used: u64,
+}
+
+/// How many boards have their view remembered.
+const VIEW_LIMIT: usize = 100;
+
+/// The `Profile::last_played` key of the built-in levels. Packs are keyed by
+/// their absolute path, which this can never be.
+const BUILT_IN: &str = "built-in";
+
+/// The levels of a loaded file, in order, and which of them `Level::File`
+/// currently stands for.
+#[derive(Clone)]
+struct Collection {
+    /// The file name without its extension.
+    name: String,
+    /// Where the file is, made absolute, unless it was read from stdin.
+    path: Option<PathBuf>,
+    boards: Vec<(Vec<Vec<Tile>>, LevelInfo)>,
+    index: usize,
+    /// Frames played on the results screen in place of the banner, read
+    /// from `<name>.celebration.txt` next to the file.
+    celebration: Option<Vec<Vec<String>>>,
+}
+
+/// What a level file says about one of its boards.
+#[derive(Clone, Default)]
+struct LevelInfo {
+    title: Option<String>,
+    author: Option<String>,
+}
+
+impl Collection {
+    /// The title of board `index`, or where it sits in the file if it has
+    /// none, followed by its author if known.
+    fn title(&self, index: usize) -> String {
+        let info = &self.boards[index].1;
+        let title = match &info.title {
+            Some(title) => title.clone(),
+            None => format!("Level {} of {}", index + 1, self.name),
+        };
+        match &info.author {
+            Some(author) => format!("{} by {}", title, author),
+            None => title,
+        }
+    }
+}
+
+/// What is on screen around the board: text screens, menus, prompts,
+/// messages and overlays.
+#[derive(Clone)]
+struct Ui {
+    screen: Vec<Line<'static>>,
+    menu_cursor: Option<usize>,
+    whats_new_scroll: Option<u16>,
+    prompt: Option<Prompt>,
+    message: Option<String>,
+    keymap: Keymap,
+    explain_deadlock: bool,
+    ruler: bool,
+    show_heat: bool,
+    /// Shade the squares the player can walk to without pushing a box.
+    show_reachable: bool,
+    nudges: Nudges,
+    pointer: Option<(u16, u16)>,
+    previews: RefCell<PreviewCache>,
+    /// The packs listed on level select, found when it was opened.
+    packs: Vec<Pack>,
+    /// How the levels started from level select are laid out.
+    variant: Variant,
+    /// What the last render drew, for screenshots.
+    last_frame: RefCell<Buffer>,
+    last_ui_tick: Option<Instant>,
+    last_input: Instant,
+    retry: Option<(Level, Vec<MoveDirection>)>,
+    /// The per-move CSV of the attempt on the results screen, until it is
+    /// left.
+    export: Option<(Level, String)>,
+    instant_replay: Option<InstantReplay>,
+    attract: Option<Attract>,
+    /// Which trivia fact the next results screen starts from.
+    trivia_rotation: usize,
+    /// The pack's celebration, playing while the results screen is up.
+    celebration: Option<Animation>,
+    arcade: Option<ArcadeRun>,
+    /// Whether the terminal has focus, as far as it reports.
+    focused: bool,
+    /// The level editor, while it is open. Keys go to it instead of the
+    /// board unless its draft is being playtested.
+    editor: Option<Editor>,
+    /// Every sound the game plays, loaded at startup by `load_sounds`.
+    sounds: SoundPack,
+    /// Seed of the random board last started, shown so it can be replayed.
+    random_seed: u64,
+    /// Day of the daily puzzle last started, counted from 1970-01-01 in
+    /// UTC. It is the daily's seed, and is kept across midnight.
+    daily: u64,
+    /// The key being held down to quit or leave kids mode.
+    hold: Option<Hold>,
+    /// The attempt the last reset threw away, and when, until a move on the
+    /// fresh board or a change of level makes it stale.
+    trashed: Option<(Attempt, Instant)>,
+    ratings: Ratings,
+    solvability: Solvability,
+    tips: Tips,
+    /// The cell a board too big for the terminal is scrolled to centre,
+    /// until the next level. Without it the board is drawn from its top
+    /// left corner.
+    camera: Option<Pos>,
+}
+
+/// Where progress is persisted. A file written by a newer release is loaded
+/// but never written back, so running an older binary cannot destroy it.
+///
+/// Only the copy of the game holding the lock on `scores.lock` writes the
+/// save. Any other copy leaves its progress in `scores.pending` instead, and
+/// every write merges what is already on disk, keeping the best of each.
+#[derive(Clone)]
+struct Save {
+    path: Option<PathBuf>,
+    read_only: bool,
+    lock: Option<Rc<File>>,
+}
+
+/// Options taken from the command line at startup.
+#[derive(Clone, Debug)]
+struct Settings {
+    /// How long the menu may sit idle before best runs start replaying.
+    attract_idle: Option<Duration>,
+    /// Raise the pitch of the goal sound with every box placed.
+    goal_pitch: bool,
+    /// Show the wall-bump counter under the board while playing.
+    show_bumps: bool,
+    /// Take back a push as soon as the deadlock detector flags it.
+    auto_undo: bool,
+    /// Play ticks and chimes when moving around menus.
+    ui_sounds: bool,
+    /// Capture the mouse to show what is under the pointer.
+    mouse: bool,
+    /// How much one press of undo takes back.
+    undo_step: UndoStep,
+    /// Count pushes of boxes off goals, and award clean solves.
+    strict: bool,
+    /// Which count decides whether a solve is a new record.
+    metric: Metric,
+    /// Tick less often and redraw only on change, for running on battery.
+    low_power: bool,
+    /// Keep `status.json` up to date next to the saved scores.
+    status_file: bool,
+    /// Keep colours in screenshots as ANSI escapes.
+    ansi_screenshots: bool,
+    /// Where level select looks for level packs.
+    levels_dir: Option<PathBuf>,
+    /// Seed picking how every level is turned or flipped, see `Transform`.
+    scramble: Option<u64>,
+    /// Play levels against a draining points budget, see `ArcadeRun`.
+    arcade: bool,
+    /// Offer tips after repeated failures of a level, see `Nudges`.
+    nudges: bool,
+    /// A sound pack played in place of the built-in sounds.
+    sound_pack: Option<PathBuf>,
+    /// Where loose sound files that replace those of the packs are kept.
+    sounds_dir: Option<PathBuf>,
+    /// Seed of the first random board, taken once it is used.
+    seed: Option<u64>,
+    /// Boxes on a random board.
+    random_boxes: usize,
+    /// Width and height of a random board inside its walls.
+    random_size: (usize, usize),
+    /// Small levels, auto-undo, a celebration on every solve, quitting only
+    /// by holding the key down, and a profile of its own. Chosen on the
+    /// welcome screen and remembered, see `set_kids_mode`.
+    kids: bool,
+    /// Ask the solver after every push whether the board can still be
+    /// solved, see `Solvability`.
+    check_solvable: bool,
+    /// Print the timings of the last commands on quitting, see `perf`.
+    perf_report: bool,
+}
+
+/// How much one press of undo takes back: a single move, everything back to
+/// and including the last push, or everything since the last box was put
+/// on a goal.
+#[derive(Clone, Copy, Debug)]
+enum UndoStep {
+    Move,
+    Push,
+    Box,
+}
+
+/// The count a solve is ranked by. Both are always recorded, so switching
+/// only changes which record is compared and headlined.
+#[derive(Clone, Copy, Debug)]
+enum Metric {
+    Moves,
+    Pushes,
+}
+
+/// A look back at the last few moves, drawn from boards simulated on a copy
+/// of the game so the live attempt is never touched.
+#[derive(Clone)]
+struct InstantReplay {
+    frames: Vec<Snapshot>,
+    frame: usize,
+    interval: Duration,
+    last_frame: Instant,
+}
+
+/// A key being held down in kids mode: terminals only report the repeats,
+/// so it counts as held while they keep coming.
+#[derive(Clone)]
+struct Hold {
+    command: Command,
+    started: Instant,
+    last: Instant,
+}
+
+/// Playback of a stored best run while the menu sits idle. `resume` is the
+/// state to put back as soon as a key is pressed.
+#[derive(Clone)]
+struct Attract {
+    resume: Box<GameState>,
+    level: Level,
+    replay: Vec<MoveDirection>,
+    step: usize,
+    last_step: Instant,
+}
+
+/// A run of levels in arcade mode. Each level starts with `ARCADE_START`
+/// points, which drain while it is played and with every push; what is left
+/// on solving it is banked, and running out ends the run.
+#[derive(Clone, Default)]
+struct ArcadeRun {
+    banked: u32,
+    levels: u32,
+    /// Time spent playing the current level, not counting menus, replays
+    /// and the terminal losing focus.
+    spent: Duration,
+    pushes: u32,
+    last_tick: Option<Instant>,
+}
+
+impl ArcadeRun {
+    /// Points left on the current level.
+    fn left(&self) -> u32 {
+        let drained = self.spent.as_millis() * u128::from(ARCADE_DRAIN) / 1000;
+        ARCADE_START
+            .saturating_sub(drained.min(u128::from(ARCADE_START)) as u32)
+            .saturating_sub(self.pushes * ARCADE_PUSH_COST)
+    }
+}
+
+/// A finished arcade run, as kept in the save.
+#[derive(Clone, PartialEq)]
+struct ArcadeScore {
+    points: u32,
+    levels: u32,
+    /// Seconds since the Unix epoch when the run ended, which also tells
+    /// apart runs with the same score when saves are merged.
+    finished: u64,
+}
+
+/// A board being drawn in the level editor, always a rectangle.
+#[derive(Clone)]
+struct Editor {
+    draft: Vec<Vec<Tile>>,
+    cursor: Pos,
+    /// The draft before each stamp, for undo.
+    history: Vec<Vec<Vec<Tile>>>,
+    /// Whether the draft is being played, with the editor waiting behind it.
+    playtesting: bool,
+    /// The collection that was loaded before playtesting took its place.
+    stash: Option<Collection>,
+}
+
+/// How far the editor lets a draft grow, in columns and rows.
+const EDITOR_MAX_SIZE: (usize, usize) = (60, 30);
+
+/// Text frames played once at `CELEBRATION_FRAME` from when it was made,
+/// holding on the last.
+#[derive(Clone)]
+struct Animation {
+    frames: Vec<Vec<String>>,
+    started: Instant,
+}
+
+impl Animation {
+    fn new(frames: Vec<Vec<String>>) -> Animation {
+        Animation {
+            frames,
+            started: Instant::now(),
+        }
+    }
+
+    /// How many frames have come up so far, the current one included.
+    fn shown(&self) -> usize {
+        (self.started.elapsed().as_millis() / CELEBRATION_FRAME.as_millis()) as usize + 1
+    }
+
+    fn frame(&self) -> &[String] {
+        let last = self.frames.len().saturating_sub(1);
+        self.frames
+            .get(self.shown().min(last + 1) - 1)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether a frame is still to be drawn for the first time.
+    fn playing(&self) -> bool {
+        self.shown() <= self.frames.len()
+    }
+}
+
+#[derive(PartialEq, Clone)]
+enum Command {
+    Quit,
+    Move(MoveDirection),
+    LevelChoose,
+    LevelSelect(Level),
+    Reset,
+    ReverseMove,
+    ExplainDeadlock,
+    ToggleRuler,
+    ToggleHeatmap,
+    ToggleReachable,
+    StopNudge,
+    CycleVariant,
+    InstantReplay,
+    MenuUp,
+    MenuDown,
+    MenuConfirm,
+    MenuCancel,
+    OpenFilter,
+    WhatsNew,
+    NextLevel,
+    PrevLevel,
+    OpenReport,
+    FileReport(String),
+    Screenshot,
+    ExportMoves,
+    ExportBoard,
+    ArcadeScores,
+    Continue,
+    ForgetViews,
+    OpenSaveLevel,
+    ToggleKids,
+    /// Scroll a board too big for the terminal so the player is in the
+    /// middle, see `Ui::camera`.
+    CenterCamera,
+    /// Draw a level of your own, from level select.
+    OpenEditor,
+    /// Bring back the attempt the last reset threw away, see `Ui::trashed`.
+    UndoReset,
+    SaveLevel {
+        file: String,
+        title: String,
+        overwrite: bool,
+    },
+    PlayPack(usize),
+    OpenRestartPrompt,
+    RestartAt {
+        moves: usize,
+        from_best: bool,
+    },
+}
+
+/// One line of level select: a built-in level, or a pack from the levels
+/// directory by its index in `Ui::packs`.
+#[derive(Clone, Copy, PartialEq)]
+enum MenuEntry {
+    Level(Level),
+    Pack(usize),
+}
+
+/// A level collection found in the levels directory.
+#[derive(Clone)]
+struct Pack {
+    name: String,
+    path: PathBuf,
+    levels: usize,
+    /// The boards that parse, to be rated.
+    grids: Vec<Vec<Vec<Tile>>>,
+}
+
+/// How hard boards are, by `level_hash`: the fewest pushes the solver
+/// found, or none if it gave up. Rated on a thread of their own so level
+/// select never waits, and kept in `ratings.txt` in the data directory.
+#[derive(Clone, Default)]
+struct Ratings {
+    known: HashMap<u64, Option<u32>>,
+    pending: HashSet<u64>,
+    worker: Option<Rc<RatingWorker>>,
+}
+
+/// The thread rating boards, one after another, until the game drops its
+/// end of `jobs` or sets `stop`, which also calls off the board being rated.
+struct RatingWorker {
+    jobs: Sender<(u64, Vec<Vec<Tile>>)>,
+    done: Receiver<(u64, Option<u32>)>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// Whether boards played can still be solved, by `position_key`, found on a
+/// thread of their own. Unlike `find_deadlock`, which knows a few patterns,
+/// the solver proves it either way, or gives up. Only the position on the
+/// board is ever searched: moving on calls off the search of the last one.
+#[derive(Clone, Default)]
+struct Solvability {
+    known: HashMap<u64, Verdict>,
+    /// The position on the board, once it has been asked about.
+    current: Option<u64>,
+    /// Set to call off the search running for `current`.
+    cancel: Option<Arc<AtomicBool>>,
+    worker: Option<Rc<SolvableWorker>>,
+}
+
+/// The thread searching positions until the game drops its end of `jobs`.
+struct SolvableWorker {
+    jobs: Sender<(u64, Vec<Vec<Tile>>, Arc<AtomicBool>)>,
+    done: Receiver<(u64, Verdict)>,
+    thread: JoinHandle<()>,
+}
+
+/// A one-line question shown under the board. While it is open, keys go to
+/// the prompt instead of the keymap.
+#[derive(Clone)]
+enum Prompt {
+    RestartAt {
+        count: String,
+        from_best: bool,
+    },
+    Filter {
+        query: String,
+    },
+    Report {
+        description: String,
+    },
+    /// Saving a board to the levels directory: its file name, then its
+    /// title, then whether to overwrite a file of that name.
+    SaveLevel {
+        file: String,
+        title: String,
+        step: SaveStep,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SaveStep {
+    File,
+    Title,
+    Overwrite,
+}
+
+/// The whole game, drawn into whatever area it is given. The host owns the
+/// terminal: it feeds in events, calls `tick` whenever `tick_rate` passes
+/// without one, and renders when asked to.
+pub struct App {
+    game_state: GameState,
+    sink: Sink,
+    /// Keeps the audio device, or the queue behind a silent sink, alive.
+    _audio: Box<dyn std::any::Any>,
+    last_redraw: Instant,
+    /// Drawing only borrows the game, so its timing needs a cell.
+    perf: RefCell<Perf>,
+}
+
+/// What the host should do after handing the game an event.
+#[derive(PartialEq, Debug)]
+pub enum Update {
+    Redraw,
+    /// Nothing visible changed yet; the next tick will catch up.
+    Skip,
+    /// The player quit. The host should stop feeding in events and call
+    /// `App::shutdown` before restoring the terminal.
+    Quit,
+}
+
+/// Anything that can go wrong. Most errors are shown in the message line
+/// and play goes on; those that reach the host end the game with
+/// `exit_code`.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Audio(String),
+    LevelParse(String),
+    Save(std::io::Error),
+    Terminal(std::io::Error),
+    /// The command line asks for something that cannot be done.
+    Usage(String),
+}
+
+impl Error {
+    /// The process exit code for this kind of error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Io(_) => 1,
+            Error::Terminal(_) => 2,
+            Error::Audio(_) => 3,
+            Error::LevelParse(_) => 4,
+            Error::Save(_) => 5,
+            Error::Usage(_) => 6,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Audio(reason) => write!(f, "could not play a sound: {}", reason),
+            Error::LevelParse(reason) => write!(f, "{}", reason),
+            Error::Save(error) => write!(f, "could not save progress: {}", error),
+            Error::Terminal(error) => write!(f, "terminal error: {}", error),
+            Error::Usage(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) | Error::Save(error) | Error::Terminal(error) => Some(error),
+            Error::Audio(_) | Error::LevelParse(_) | Error::Usage(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<rodio::decoder::DecoderError> for Error {
+    fn from(error: rodio::decoder::DecoderError) -> Error {
+        Error::Audio(error.to_string())
+    }
+}
+
+impl App {
+    /// How often `tick` should run when no events arrive. Low-power mode
+    /// stretches it unless a replay is playing.
+    pub fn tick_rate(&self) -> Duration {
+        let ui = &self.game_state.ui;
+        if self.game_state.profile.settings.low_power
+            && ui.attract.is_none()
+            && ui.instant_replay.is_none()
+        {
+            LOW_POWER_TICK_RATE
+        } else {
+            TICK_RATE
+        }
+    }
+
+    /// Catches command lines that cannot start a game, before the host sets
+    /// up the terminal: a `--sound-pack` that cannot be read, or `--stdin`
+    /// with nothing piped in or with a level file as well.
+    pub fn check_args(args: &[String]) -> Result<(), Error> {
+        if let Some(path) = args
+            .iter()
+            .position(|arg| arg == "--sound-pack")
+            .and_then(|idx| args.get(idx + 1))
+        {
+            read_sound_pack(Path::new(path))?;
+        }
+        if args.iter().any(|arg| arg == "--perf-report") && !perf::ENABLED {
+            return Err(Error::Usage(
+                "--perf-report needs the game built with `--features perf`".to_string(),
+            ));
+        }
+        if !args.iter().any(|arg| arg == "--stdin") {
+            return Ok(());
+        }
+        if level_path(args).is_some() {
+            return Err(Error::Usage(
+                "give either a level file or --stdin, not both".to_string(),
+            ));
+        }
+        if std::io::stdin().is_terminal() {
+            return Err(Error::Usage(
+                "--stdin reads a level piped in, as in `cat level.xsb | sokoban_rust --stdin`"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Ends the game in order once it has quit: tells the background
+    /// threads to stop, saves progress, stops any sound still playing and
+    /// waits for the threads, then calls `restore` to hand the terminal
+    /// back. `restore` runs even if a step fails or panics, and the whole
+    /// takes at most `SHUTDOWN_TIMEOUT`. The first failure is given back
+    /// rather than shown, since there is no longer a message line to show
+    /// it in.
+    pub fn shutdown(&mut self, restore: impl FnOnce()) -> Result<(), Error> {
+        let mut shutdown = Shutdown::new(SHUTDOWN_TIMEOUT);
+        for (name, thread) in stop_workers(&mut self.game_state) {
+            shutdown.thread(name, thread);
+        }
+        shutdown.flush("saving progress", |app: &mut App| {
+            write_progress(&mut app.game_state)
+        });
+        shutdown.flush("stopping sound", |app: &mut App| {
+            app.sink.stop();
+            Ok(())
+        });
+        let failures = shutdown.run(self, |_| restore());
+        match failures.into_iter().next() {
+            None => Ok(()),
+            Some((_, Failure::Failed(error))) => Err(error),
+            Some((step, Failure::Panicked(message))) => Err(Error::Io(std::io::Error::other(
+                format!("{} panicked: {}", step, message),
+            ))),
+            Some((step, Failure::TimedOut)) => Err(Error::Io(std::io::Error::other(format!(
+                "{} took too long",
+                step
+            )))),
+        }
+    }
+
+    /// Starts the game with the given command-line flags, as described on
+    /// `Settings::from_args`. Without an audio device the game plays silently.
+    pub fn new(args: Vec<String>) -> App {
+        let (sink, audio): (Sink, Box<dyn std::any::Any>) = match open_audio() {
+            Some((sink, stream)) => (sink, Box::new(stream)),
+            None => {
+                let (sink, output) = Sink::new_idle();
+                (sink, Box::new(output))
+            }
+        };
+        App {
+            game_state: new_game(args),
+            sink,
+            _audio: audio,
+            last_redraw: Instant::now(),
+            perf: RefCell::new(Perf::default()),
+        }
+    }
+
+    /// The timings `--perf-report` asks for, for the host to print once the
+    /// terminal is handed back.
+    pub fn perf_report(&self) -> Option<String> {
+        self.game_state
+            .profile
+            .settings
+            .perf_report
+            .then(|| self.perf.borrow().report())
+    }
+
+    /// Whether the host should enable mouse capture, which `--mouse` asks for.
+    pub fn wants_mouse(&self) -> bool {
+        self.game_state.profile.settings.mouse
+    }
+
+    /// Whether the host should enable focus change events, which arcade mode
+    /// pauses on.
+    pub fn wants_focus(&self) -> bool {
+        self.game_state.profile.settings.arcade
+    }
+
+    /// Handles a key press or mouse event. Mouse positions are in terminal
+    /// cells, the same as the area passed to `render`.
+    pub fn handle_event(&mut self, event: Event) -> Update {
+        let timer = perf::start();
+        let game_state = &mut self.game_state;
+        let commands = match event {
+            // Terminals on Windows report keys let go as well as pressed.
+            // Only a press is input, so a release neither counts as activity
+            // nor ends attract mode or redraws.
+            Event::Key(key) if key.kind == KeyEventKind::Release => return Update::Skip,
+            Event::Key(key) => {
+                game_state.ui.last_input = Instant::now();
+                if game_state.ui.attract.is_some() {
+                    stop_attract(game_state, &self.sink);
+                    vec![]
+                } else if game_state.ui.instant_replay.is_some() {
+                    control_instant_replay(game_state, key);
+                    vec![]
+                } else if game_state.ui.prompt.is_some() {
+                    answer_prompt(game_state, key).into_iter().collect()
+                } else {
+                    read_input(&mut game_state.ui.keymap, key)
+                }
+            }
+            Event::Mouse(mouse) => {
+                game_state.ui.pointer = Some((mouse.column, mouse.row));
+                // Pointer motion floods in; redraw a few times a second at most.
+                if self.last_redraw.elapsed() < HOVER_REDRAW {
+                    return Update::Skip;
+                }
+                vec![]
+            }
+            Event::FocusGained | Event::FocusLost => {
+                game_state.ui.focused = event == Event::FocusGained;
+                return Update::Skip;
+            }
+            _ => vec![],
+        };
+        self.run(commands, perf::elapsed(timer))
+    }
+
+    /// Advances replays, attract mode and pending key chords.
+    pub fn tick(&mut self) -> Update {
+        let ui = &self.game_state.ui;
+        let replaying = ui.attract.is_some()
+            || ui.instant_replay.is_some()
+            || ui.celebration.as_ref().is_some_and(Animation::playing);
+        let chord = ui.keymap.pending.is_some();
+        let arcade = ui.arcade.is_some();
+        let holding = ui.hold.is_some();
+        let rated = ui.ratings.known.len();
+        let checked = ui.solvability.known.len();
+        let tip = ui.tips.shown.as_ref().map(|(_, _, since)| *since);
+        let timer = perf::start();
+        let commands: Vec<Command> = tick(&mut self.game_state, &self.sink).into_iter().collect();
+        let input = perf::elapsed(timer);
+        // In low-power mode, only redraw when the tick changed something.
+        if self.game_state.profile.settings.low_power
+            && !replaying
+            && commands.is_empty()
+            && chord == self.game_state.ui.keymap.pending.is_some()
+            && arcade == self.game_state.ui.arcade.is_some()
+            && holding == self.game_state.ui.hold.is_some()
+            && rated == self.game_state.ui.ratings.known.len()
+            && checked == self.game_state.ui.solvability.known.len()
+            && tip
+                == self
+                    .game_state
+                    .ui
+                    .tips
+                    .shown
+                    .as_ref()
+                    .map(|(_, _, since)| *since)
+        {
+            return Update::Skip;
+        }
+        self.run(commands, input)
+    }
+
+    /// Draws the game into `area` and nowhere else.
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let timer = perf::start();
+        draw(frame, area, &self.game_state);
+        let drawn = frame.buffer_mut();
+        let area = area.intersection(drawn.area);
+        let mut shot = Buffer::empty(area);
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                shot[(x, y)] = drawn[(x, y)].clone();
+            }
+        }
+        *self.game_state.ui.last_frame.borrow_mut() = shot;
+        self.perf.borrow_mut().rendered(perf::elapsed(timer));
+    }
+
+    /// Runs the commands read from one event, or one tick, which took
+    /// `input` to read.
+    fn run(&mut self, commands: Vec<Command>, input: Duration) -> Update {
+        let timed = !commands.is_empty();
+        perf::take_audio();
+        let timer = perf::start();
+        // A prompt answered or a replay ended before the commands run.
+        track_aids(&mut self.game_state);
+        for command in commands {
+            self.game_state.ui.message = None;
+            self.game_state.attempt.blocked = None;
+            let ret = do_action(&mut self.game_state, command, &self.sink);
+            if ret == 1 {
+                return Update::Quit;
+            }
+            finish_if_solved(&mut self.game_state, &self.sink);
+        }
+        track_aids(&mut self.game_state);
+        check_solvable(&mut self.game_state);
+        if timed {
+            self.perf.borrow_mut().ran(input, perf::elapsed(timer));
+        }
+        self.last_redraw = Instant::now();
+        Update::Redraw
+    }
+}
+
+fn tick(game_state: &mut GameState, sink: &Sink) -> Option<Command> {
+    if let Some(hold) = &game_state.ui.hold {
+        if hold.last.elapsed() > KIDS_HOLD_GAP {
+            game_state.ui.hold = None;
+            game_state.ui.message = None;
+        }
+    }
+    collect_ratings(game_state);
+    collect_solvable(game_state);
+    rotate_tip(game_state);
+    step_arcade(game_state);
+    if game_state.ui.attract.is_some() {
+        step_attract(game_state, sink);
+        return None;
+    }
+    if game_state.ui.instant_replay.is_some() {
+        step_instant_replay(game_state);
+        return None;
+    }
+    if let Some(idle) = game_state.profile.settings.attract_idle {
+        if game_state.attempt.level.is_none()
+            && game_state.ui.editor.is_none()
+            && game_state.ui.prompt.is_none()
+            && game_state.ui.last_input.elapsed() >= idle
+        {
+            start_attract(game_state, sink, None);
+        }
+    }
+    game_state.ui.keymap.expire_chord()
+}
+
+/// Drains the arcade level in play by the time since the last tick, starting
+/// a run with the first level played in arcade mode and ending it once the
+/// points run out.
+fn step_arcade(game_state: &mut GameState) {
+    let ui = &game_state.ui;
+    let playing = game_state.attempt.level.is_some()
+        && ui.screen.is_empty()
+        && ui.prompt.is_none()
+        && ui.attract.is_none()
+        && ui.instant_replay.is_none()
+        && ui.focused;
+    if game_state.profile.settings.arcade && playing && ui.arcade.is_none() {
+        game_state.ui.arcade = Some(ArcadeRun::default());
+    }
+    let Some(run) = &mut game_state.ui.arcade else {
+        return;
+    };
+    if !playing {
+        run.last_tick = None;
+        return;
+    }
+    let now = Instant::now();
+    if let Some(last_tick) = run.last_tick {
+        run.spent += now - last_tick;
+    }
+    run.last_tick = Some(now);
+    if run.left() == 0 {
+        end_arcade_run(game_state);
+    }
+}
+
+/// Ends the arcade run out of points and enters it in the high scores.
+fn end_arcade_run(game_state: &mut GameState) {
+    let Some(run) = game_state.ui.arcade.take() else {
+        return;
+    };
+    game_state.attempt = Attempt::default();
+    let score = ArcadeScore {
+        points: run.banked,
+        levels: run.levels,
+        finished: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs()),
+    };
+    add_arcade_score(&mut game_state.profile.arcade_scores, score.clone());
+    let place = game_state
+        .profile
+        .arcade_scores
+        .iter()
+        .position(|entry| *entry == score);
+    let mut screen = vec![
+        Line::from("Out of points - the arcade run is over.")
+            .bold()
+            .red(),
+        stat_line("Levels solved: ", run.levels.to_string()),
+        stat_line("Points banked: ", run.banked.to_string()),
+    ];
+    if let (Some(place), true) = (place, run.banked > 0) {
+        screen.push(Line::from(format!("New arcade high score, number {}!", place + 1)).green());
+    }
+    screen.push(Line::from(""));
+    screen.push(Line::from(format!(
+        "Press {} for the arcade high scores or {} to go back to the main menu.",
+        game_state.ui.keymap.hint(&Command::ArcadeScores),
+        game_state.ui.keymap.hint(&Command::LevelChoose)
+    )));
+    game_state.ui.screen = screen;
+    save_progress(game_state);
+}
+
+/// Enters a finished run in the high-score table, unless it is there
+/// already, keeping the best `ARCADE_TABLE`.
+fn add_arcade_score(scores: &mut Vec<ArcadeScore>, score: ArcadeScore) {
+    if !scores.contains(&score) {
+        scores.push(score);
+    }
+    scores.sort_by(|a, b| (b.points, b.levels, a.finished).cmp(&(a.points, a.levels, b.finished)));
+    scores.truncate(ARCADE_TABLE);
+}
+
+fn show_arcade_scores(game_state: &mut GameState) {
+    let mut screen = vec![Line::from("Arcade high scores").bold(), Line::from("")];
+    if game_state.profile.arcade_scores.is_empty() {
+        screen.push(Line::from(
+            "No arcade runs yet - start the game with --arcade.",
+        ));
+    }
+    for (idx, score) in game_state.profile.arcade_scores.iter().enumerate() {
+        screen.push(Line::from(format!(
+            "{}. {} points, {} levels",
+            idx + 1,
+            score.points,
+            score.levels
+        )));
+    }
+    screen.push(Line::from(""));
+    screen.push(Line::from(format!(
+        "Press {} to go back to the main menu.",
+        game_state.ui.keymap.hint(&Command::LevelChoose)
+    )));
+    game_state.ui.screen = screen;
+}
+
+fn start_attract(game_state: &mut GameState, sink: &Sink, after: Option<Level>) {
+    let start = after.map_or(0, |level| {
+        LEVELS.iter().position(|l| *l == level).unwrap() + 1
+    });
+    let Some(level) = (0..LEVELS.len())
+        .map(|i| LEVELS[(start + i) % LEVELS.len()])
+        .find(|level| {
+            let id = LevelId::BuiltIn(*level);
+            game_state.profile.best_runs.contains_key(&id)
+        })
+    else {
+        return;
+    };
+
+    let resume = match game_state.ui.attract.take() {
+        Some(attract) => attract.resume,
+        None => Box::new(game_state.clone()),
+    };
+    let replay = game_state.profile.best_runs[&LevelId::BuiltIn(level)].clone();
+    sink.set_volume(0.0);
+    start_level(game_state, level);
+    let replay = scrambled(game_state, replay);
+    game_state.ui.attract = Some(Attract {
+        resume,
+        level,
+        replay,
+        step: 0,
+        last_step: Instant::now(),
+    });
+}
+
+fn step_attract(game_state: &mut GameState, sink: &Sink) {
+    let attract = game_state.ui.attract.as_mut().unwrap();
+    if attract.step == attract.replay.len() {
+        if attract.last_step.elapsed() >= ATTRACT_HOLD {
+            let level = attract.level;
+            start_attract(game_state, sink, Some(level));
+        }
+        return;
+    }
+    if attract.last_step.elapsed() < ATTRACT_STEP {
+        return;
+    }
+    let direction = attract.replay[attract.step].clone();
+    attract.step += 1;
+    attract.last_step = Instant::now();
+    let result = player_move(&direction, &mut game_state.attempt);
+    play_out(game_state, direction, result, sink);
+}
+
+fn stop_attract(game_state: &mut GameState, sink: &Sink) {
+    if let Some(attract) = game_state.ui.attract.take() {
+        *game_state = *attract.resume;
+        game_state.ui.last_input = Instant::now();
+        restore_sound(sink);
+    }
+}
+
+/// How far back an instant replay starts.
+const INSTANT_REPLAY_MOVES: usize = 10;
+const INSTANT_REPLAY_INTERVAL: Duration = Duration::from_millis(400);
+
+fn start_instant_replay(game_state: &mut GameState) {
+    let Some(level) = game_state.attempt.level else {
+        return;
+    };
+    // Before the first move of a daily already solved, it shows the whole
+    // solution instead.
+    let solution = match (level, game_state.attempt.moves.is_empty()) {
+        (Level::Daily, true) => game_state.profile.dailies.get(&date(game_state.ui.daily)),
+        _ => None,
+    };
+    let moves = solution.unwrap_or(&game_state.attempt.moves);
+    let mut frames = replay_frames(game_state, level, moves);
+    if frames.len() < 2 {
+        return;
+    }
+    if solution.is_none() {
+        frames.drain(..frames.len().saturating_sub(INSTANT_REPLAY_MOVES + 1));
+    }
+    game_state.ui.instant_replay = Some(InstantReplay {
+        frames,
+        frame: 0,
+        interval: INSTANT_REPLAY_INTERVAL,
+        last_frame: Instant::now(),
+    });
+}
+
+fn step_instant_replay(game_state: &mut GameState) {
+    let replay = game_state.ui.instant_replay.as_mut().unwrap();
+    if replay.last_frame.elapsed() < replay.interval {
+        return;
+    }
+    if replay.frame + 1 == replay.frames.len() {
+        game_state.ui.instant_replay = None;
+        return;
+    }
+    replay.frame += 1;
+    replay.last_frame = Instant::now();
+}
+
+fn control_instant_replay(game_state: &mut GameState, key: KeyEvent) {
+    if key.kind != KeyEventKind::Press {
+        return;
+    }
+    let replay = game_state.ui.instant_replay.as_mut().unwrap();
+    match key.code {
+        KeyCode::Esc => game_state.ui.instant_replay = None,
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            replay.interval = (replay.interval / 2).max(Duration::from_millis(50));
+        }
+        KeyCode::Char('-') => {
+            replay.interval = (replay.interval * 2).min(Duration::from_secs(2));
+        }
+        _ => {}
+    }
+}
+
+fn prompt_text(prompt: &Prompt) -> String {
+    let (before, after) = prompt_parts(prompt);
+    format!("{}_{}", before, after)
+}
+
+/// A prompt's text on either side of where the next key typed goes, shown
+/// as `_`.
+fn prompt_parts(prompt: &Prompt) -> (String, String) {
+    match prompt {
+        Prompt::RestartAt { count, from_best } => (
+            format!("Restart keeping the first {}", count),
+            format!(
+                " moves of {} (Tab: switch, Enter: go, Esc: cancel)",
+                if *from_best {
+                    "your best run"
+                } else {
+                    "this attempt"
+                },
+            ),
+        ),
+        Prompt::Filter { query } => (
+            format!("/{}", query),
+            " (Enter: play top match, Esc: clear)".to_string(),
+        ),
+        Prompt::Report { description } => (
+            format!("Describe the problem: {}", description),
+            " (Enter: save a draft, Esc: cancel)".to_string(),
+        ),
+        Prompt::SaveLevel { file, title, step } => match step {
+            SaveStep::File => (
+                format!("Save as: {}", file),
+                ".xsb (Enter: next, Esc: cancel)".to_string(),
+            ),
+            SaveStep::Title => (
+                format!("Title: {}", title),
+                " (Enter: save, Esc: cancel)".to_string(),
+            ),
+            SaveStep::Overwrite => (
+                format!("{}.xsb exists. Overwrite it? (y/n) ", file),
+                String::new(),
+            ),
+        },
+    }
+}
+
+fn answer_prompt(game_state: &mut GameState, key: KeyEvent) -> Option<Command> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+    match &mut game_state.ui.prompt {
+        Some(Prompt::RestartAt { count, from_best }) => match key.code {
+            KeyCode::Char(c) if c.is_ascii_digit() && count.len() < 5 => count.push(c),
+            KeyCode::Backspace => {
+                count.pop();
+            }
+            KeyCode::Tab => *from_best = !*from_best,
+            KeyCode::Enter => {
+                let command = Command::RestartAt {
+                    moves: count.parse().unwrap_or(0),
+                    from_best: *from_best,
+                };
+                game_state.ui.prompt = None;
+                return Some(command);
+            }
+            KeyCode::Esc => game_state.ui.prompt = None,
+            _ => {}
+        },
+        Some(Prompt::Filter { query }) => {
+            match key.code {
+                KeyCode::Char(c) if query.len() < 30 => query.push(c),
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Up => return Some(Command::MenuUp),
+                KeyCode::Down => return Some(Command::MenuDown),
+                KeyCode::Enter => {
+                    let top = menu_entries(game_state).first().copied();
+                    game_state.ui.prompt = None;
+                    return top.map(|entry| match entry {
+                        MenuEntry::Level(level) => Command::LevelSelect(level),
+                        MenuEntry::Pack(idx) => Command::PlayPack(idx),
+                    });
+                }
+                KeyCode::Esc => game_state.ui.prompt = None,
+                _ => return None,
+            }
+            game_state.ui.menu_cursor = Some(0);
+            choose_level(game_state);
+        }
+        Some(Prompt::Report { description }) => match key.code {
+            KeyCode::Char(c) if description.len() < 200 => description.push(c),
+            KeyCode::Backspace => {
+                description.pop();
+            }
+            KeyCode::Enter => {
+                let command = Command::FileReport(description.clone());
+                game_state.ui.prompt = None;
+                return Some(command);
+            }
+            KeyCode::Esc => game_state.ui.prompt = None,
+            _ => {}
+        },
+        Some(Prompt::SaveLevel { file, title, step }) => {
+            let command = |overwrite| Command::SaveLevel {
+                file: file.trim().to_string(),
+                title: title.trim().to_string(),
+                overwrite,
+            };
+            match (*step, key.code) {
+                (SaveStep::File, KeyCode::Char(c))
+                    if file.len() < 40 && (c.is_alphanumeric() || "-_ ".contains(c)) =>
+                {
+                    file.push(c)
+                }
+                (SaveStep::File, KeyCode::Backspace) => {
+                    file.pop();
+                }
+                (SaveStep::File, KeyCode::Enter) if !file.trim().is_empty() => {
+                    *step = SaveStep::Title
+                }
+                (SaveStep::Title, KeyCode::Char(c)) if title.len() < 60 => title.push(c),
+                (SaveStep::Title, KeyCode::Backspace) => {
+                    title.pop();
+                }
+                (SaveStep::Title, KeyCode::Enter) => {
+                    let command = command(false);
+                    game_state.ui.prompt = None;
+                    return Some(command);
+                }
+                (SaveStep::Overwrite, KeyCode::Char('y')) => {
+                    let command = command(true);
+                    game_state.ui.prompt = None;
+                    return Some(command);
+                }
+                (SaveStep::Overwrite, _) | (_, KeyCode::Esc) => game_state.ui.prompt = None,
+                _ => {}
+            }
+        }
+        None => {}
+    }
+    None
+}
+
+/// Restarts the level and silently replays the opening `moves` moves of the
+/// current attempt or of the best run, stopping at the first blocked one.
+fn restart_at(game_state: &mut GameState, moves: usize, from_best: bool, sink: &Sink) {
+    let Some(level) = game_state.attempt.level else {
+        return;
+    };
+    let best = from_best.then(|| {
+        level_id(game_state, level)
+            .and_then(|id| game_state.profile.best_runs.get(&id))
+            .cloned()
+            .unwrap_or_default()
+    });
+    let played = std::mem::take(&mut game_state.attempt.moves);
+    start_level(game_state, level);
+    // Best runs are kept as played on the level's own board.
+    let history = match best {
+        Some(best) => scrambled(game_state, best),
+        None => played,
+    };
+    game_state.attempt.pristine = false;
+
+    sink.set_volume(0.0);
+    for (idx, direction) in history.into_iter().take(moves).enumerate() {
+        let result = player_move(&direction, &mut game_state.attempt);
+        play_out(game_state, direction, result, sink);
+        if let MoveResult::Bumped | MoveResult::Blocked(_) = result {
+            game_state.ui.message = Some(format!(
+                "Replay stopped at move {}: it is blocked in this position.",
+                idx + 1
+            ));
+            break;
+        }
+    }
+    restore_sound(sink);
+    update_deadlock(game_state);
+    game_state.attempt.regressions = regressions(&game_state.attempt.frames());
+}
+
+/// How many of the steps between the frames of a replay pushed a box off a
+/// goal.
+fn regressions(frames: &[Snapshot]) -> u32 {
+    frames
+        .windows(2)
+        .filter(|pair| cell_at(&pair[0].0, pair[1].1) == Tile::BoxOnGoal)
+        .count() as u32
+}
+
+/// How many moves to keep when undoing one `step`, found by walking back
+/// through the boards of the attempt so far.
+fn undo_target(frames: &[Snapshot], step: UndoStep) -> usize {
+    let moves = frames.len() - 1;
+    let pushed = |idx: &usize| cell_at(&frames[idx - 1].0, frames[*idx].1).is_box();
+    let placed = |idx: &usize| goals_filled(&frames[*idx].0) > goals_filled(&frames[idx - 1].0);
+    match step {
+        UndoStep::Move => moves.saturating_sub(1),
+        UndoStep::Push => (1..=moves).rev().find(pushed).map_or(0, |idx| idx - 1),
+        UndoStep::Box => (1..moves).rev().find(placed).unwrap_or(0),
+    }
+}
+
+/// Takes the attempt back to its first `keep` moves, to the board `frames`
+/// of them shows after those, so pushed boxes come back too. Unlike a
+/// restart, the ghost, the clock and the attempt's other counters carry on.
+fn rewind(game_state: &mut GameState, frames: &[Snapshot], keep: usize) {
+    let attempt = &mut game_state.attempt;
+    (attempt.grid, attempt.player_position) = frames[keep].clone();
+    attempt.moves.truncate(keep);
+    attempt.move_times.truncate(keep);
+    attempt.move_count = keep as i32;
+    attempt.regressions = regressions(&frames[..=keep]);
+    attempt.pristine = false;
+    update_deadlock(game_state);
+}
+
+/// Counts where the boxes still off their goals stand as an attempt dies,
+/// by a reset or a deadlock, at most once per attempt.
+fn record_death(game_state: &mut GameState, level: Level) {
+    if game_state.attempt.death_recorded {
+        return;
+    }
+    game_state.attempt.death_recorded = true;
+    offer_nudge(game_state);
+    if game_state.attempt.scramble.is_some() {
+        return;
+    }
+    let Some(heat) =
+        level_id(game_state, level).and_then(|id| game_state.profile.heat.get_mut(&id))
+    else {
+        return;
+    };
+    heat.attempts += 1;
+    for (y, row) in game_state.attempt.grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if *cell == Tile::Box {
+                *heat.cells.entry(Pos::new(x as i32, y as i32)).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+/// Failures of one level in a session before the first tip is offered, and
+/// how many more earn each stronger one.
+const NUDGE_AFTER: u32 = 3;
+const NUDGE_STEP: u32 = 2;
+
+/// Help offered to a player who keeps failing a level.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Nudge {
+    Undo,
+    Reachable,
+    /// The first push of a solution the solver finds from the start.
+    SolverHint,
+}
+
+/// Every tip, mildest first.
+const NUDGES: [Nudge; 3] = [Nudge::Undo, Nudge::Reachable, Nudge::SolverHint];
+
+/// Positions the solver may look at for a hint. The search runs right away
+/// when the tip is earned, which is rare enough that a short wait is fine.
+const HINT_BUDGET: usize = 50_000;
+
+/// Which tips this session has earned: failures count per level layout, and
+/// every `NUDGE_STEP` past `NUDGE_AFTER` unlocks the next tip. A declined
+/// tip is not offered again until the game is restarted.
+#[derive(Clone, Default)]
+struct Nudges {
+    failures: HashMap<u64, u32>,
+    declined: HashSet<Nudge>,
+    /// The last tip offered, which `StopNudge` declines.
+    offered: Option<Nudge>,
+}
+
+impl Nudges {
+    /// Counts a failed attempt at the level laid out as `layout`, giving the
+    /// strongest tip earned so far that has not been declined.
+    fn failed(&mut self, layout: u64) -> Option<Nudge> {
+        let failures = self.failures.entry(layout).or_insert(0);
+        *failures += 1;
+        let earned = match failures.checked_sub(NUDGE_AFTER) {
+            Some(past) => (past / NUDGE_STEP + 1) as usize,
+            None => 0,
+        };
+        self.offered = NUDGES[..earned.min(NUDGES.len())]
+            .iter()
+            .rev()
+            .find(|nudge| !self.declined.contains(nudge))
+            .copied();
+        self.offered
+    }
+
+    /// Stops offering the last tip, giving it back if there was one.
+    fn decline(&mut self) -> Option<Nudge> {
+        let nudge = self.offered.take()?;
+        self.declined.insert(nudge);
+        Some(nudge)
+    }
+}
+
+/// Puts the tip the failures of this level have earned, if any, in the
+/// message line.
+fn offer_nudge(game_state: &mut GameState) {
+    if !game_state.profile.settings.nudges || game_state.ui.arcade.is_some() {
+        return;
+    }
+    let Some(nudge) = game_state.ui.nudges.failed(game_state.attempt.layout_hash) else {
+        return;
+    };
+    let keymap = &game_state.ui.keymap;
+    let tip = match nudge {
+        Nudge::Undo => format!(
+            "Stuck? {} takes back a move, so one bad push need not cost the level.",
+            keymap.hint(&Command::ReverseMove)
+        ),
+        Nudge::Reachable => format!(
+            "Press {} to shade every square you can reach without pushing a box.",
+            keymap.hint(&Command::ToggleReachable)
+        ),
+        Nudge::SolverHint => match solver::first_push(&game_state.attempt.start.0, HINT_BUDGET) {
+            Some((pos, direction)) => format!(
+                "Hint: from the start, push the box on {} {} first.",
+                coord_name(pos),
+                direction_name(&direction)
+            ),
+            None => "Even the solver could not find a way through this board quickly.".to_string(),
+        },
+    };
+    game_state.ui.message = Some(format!(
+        "{} ({} to stop this tip.)",
+        tip,
+        keymap.hint(&Command::StopNudge)
+    ));
+}
+
+/// Moves of history kept in an issue draft; longer ones keep their end.
+const ISSUE_MOVES: usize = 500;
+
+/// A markdown bug report for the issue tracker: the player's description
+/// and everything needed to reproduce what was on screen.
+fn issue_draft(game_state: &GameState, description: &str) -> String {
+    let mut draft = vec![
+        format!(
+            "## {}",
+            Some(description.trim())
+                .filter(|description| !description.is_empty())
+                .unwrap_or("(no description)")
+        ),
+        String::new(),
+        format!("- Version: {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "- Level: {}",
+            game_state
+                .attempt
+                .level
+                .map_or("none (menu)".to_string(), |level| format!("{:?}", level))
+        ),
+        format!(
+            "- Player at: {}",
+            coord_name(game_state.attempt.player_position)
+        ),
+        String::new(),
+        "### Board".to_string(),
+        String::new(),
+        "```".to_string(),
+    ];
+    let board = if game_state.attempt.grid.is_empty() {
+        vec!["(no board on screen)".to_string()]
+    } else {
+        game_state
+            .attempt
+            .grid
+            .iter()
+            .map(|row| row.iter().map(|tile| char::from(*tile)).collect())
+            .collect()
+    };
+    draft.extend(board);
+    draft.push("```".to_string());
+    draft.push(String::new());
+    draft.push("### Moves".to_string());
+    draft.push(String::new());
+    let moves = &game_state.attempt.moves;
+    let shown = &moves[moves.len().saturating_sub(ISSUE_MOVES)..];
+    if shown.len() < moves.len() {
+        draft.push(format!(
+            "Only the last {} of {} moves are shown.",
+            shown.len(),
+            moves.len()
+        ));
+        draft.push(String::new());
+    }
+    draft.push(format!("`{}`", lurd(shown)));
+    draft.push(String::new());
+    draft.push("### Settings".to_string());
+    draft.push(String::new());
+    draft.push(format!("```\n{:#?}\n```", game_state.profile.settings));
+    draft.push(String::new());
+    draft.push("### To reproduce".to_string());
+    draft.push(String::new());
+    draft.push(
+        "Start the level above with the same settings and play the moves, written in LURD \
+         notation (u, d, l, r for up, down, left and right)."
+            .to_string(),
+    );
+    draft.join("\n") + "\n"
+}
+
+fn do_action(game_state: &mut GameState, command: Command, sink: &Sink) -> i32 {
+    if game_state.profile.settings.kids {
+        match command {
+            Command::Quit => return i32::from(held(game_state, Command::Quit)),
+            Command::ToggleKids => {
+                if held(game_state, Command::ToggleKids) {
+                    set_kids_mode(game_state, false);
+                }
+                return 0;
+            }
+            _ => game_state.ui.hold = None,
+        }
+    }
+    match &game_state.ui.editor {
+        Some(editor) if !editor.playtesting => return edit(game_state, command),
+        Some(_) if command == Command::LevelChoose => {
+            stop_playtest(game_state);
+            return 0;
+        }
+        _ => {}
+    }
+    match command {
+        Command::Quit => 1,
+        Command::Reset => {
+            if let Some(cur_level) = game_state.attempt.level {
+                record_death(game_state, cur_level);
+                let trashed = game_state.attempt.clone();
+                let ghost = game_state.attempt.ghost.take();
+                start_level(game_state, cur_level);
+                game_state.attempt.ghost = ghost;
+                game_state.attempt.pristine = false;
+                if !trashed.moves.is_empty() {
+                    game_state.ui.trashed = Some((trashed, Instant::now()));
+                    game_state.ui.message =
+                        Some("Board reset - press Ctrl+Z to take it back.".to_string());
+                }
+            } else if let Some((level, moves)) = game_state.ui.retry.take() {
+                let trail = ghost_trail(game_state, level, &moves);
+                start_level(game_state, level);
+                game_state.attempt.ghost = Some(Ghost { trail });
+            }
+            0
+        }
+        Command::LevelChoose => {
+            game_state.ui.retry = None;
+            game_state.ui.packs = game_state
+                .profile
+                .settings
+                .levels_dir
+                .as_deref()
+                .map(find_packs)
+                .unwrap_or_default();
+            request_ratings(game_state);
+            choose_level(game_state);
+            0
+        }
+        Command::Move(direction) => {
+            let Some(level) = game_state.attempt.level else {
+                return 0;
+            };
+            game_state.ui.trashed = None;
+            note_pause(game_state);
+            let was_deadlocked = game_state.attempt.deadlock.is_some();
+            let auto_undo = (game_state.profile.settings.auto_undo
+                || game_state.profile.settings.kids)
+                && !was_deadlocked;
+            let result = player_move(&direction, &mut game_state.attempt);
+            play_out(game_state, direction, result, sink);
+            match result {
+                MoveResult::Blocked(Blocked::Wall) => {
+                    game_state.ui.message = Some("Box blocked by a wall.".to_string())
+                }
+                MoveResult::Blocked(Blocked::Box(_)) => {
+                    game_state.ui.message = Some("Box blocked by another box.".to_string())
+                }
+                _ => {}
+            }
+            update_deadlock(game_state);
+            let undone = auto_undo && game_state.attempt.deadlock.is_some();
+            if undone {
+                // Taken back the way an undo of one move is, though it was
+                // not the player's undo and costs no first try.
+                let pristine = game_state.attempt.pristine;
+                let frames = game_state.attempt.frames();
+                rewind(game_state, &frames, undo_target(&frames, UndoStep::Move));
+                game_state.attempt.pristine = pristine;
+                let played = play_sound(SoundType::BarrelOof, &game_state.ui.sounds, sink);
+                report(game_state, played);
+                game_state.ui.message = Some(
+                    "That push would dead-end the level - undone (auto-undo is on).".to_string(),
+                );
+            }
+            // A push auto-undo took back counts for neither.
+            if let (MoveResult::Pushed { off_goal, .. }, false) = (result, undone) {
+                if off_goal {
+                    game_state.attempt.regressions += 1;
+                }
+                if let Some(run) = &mut game_state.ui.arcade {
+                    run.pushes += 1;
+                }
+            }
+            if game_state.attempt.deadlock.is_some() && !was_deadlocked {
+                record_death(game_state, level);
+            }
+            0
+        }
+        Command::LevelSelect(level) if game_state.profile.settings.kids && !kid_sized(level) => {
+            game_state.ui.message = Some("Kids mode only has the small levels.".to_string());
+            0
+        }
+        Command::LevelSelect(level) => {
+            if level == Level::Random {
+                // `--seed` picks the first random board; later ones are new.
+                game_state.ui.random_seed = match game_state.profile.settings.seed.take() {
+                    Some(seed) => seed,
+                    None => SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |since| since.as_nanos() as u64),
+                };
+            }
+            if level == Level::Daily {
+                game_state.ui.daily = today();
+            }
+            start_level(game_state, level);
+            let solved = match level {
+                Level::Daily => game_state.profile.dailies.get(&date(game_state.ui.daily)),
+                _ => None,
+            };
+            if let Some(best) = solved {
+                game_state.ui.message = Some(format!(
+                    "Already solved today in {} moves - press {} to watch it. Only a shorter solve replaces it.",
+                    best.len(),
+                    game_state.ui.keymap.hint(&Command::InstantReplay)
+                ));
+            }
+            0
+        }
+        Command::ReverseMove => {
+            if game_state.attempt.level.is_none() || game_state.attempt.moves.is_empty() {
+                return 0;
+            }
+            note_pause(game_state);
+            game_state.profile.used_undo = true;
+            let frames = game_state.attempt.frames();
+            let keep = undo_target(&frames, game_state.profile.settings.undo_step);
+            rewind(game_state, &frames, keep);
+            0
+        }
+        Command::UndoReset => {
+            // Only from the fresh board, not a menu shown over it.
+            if game_state.attempt.level.is_none() || !game_state.ui.screen.is_empty() {
+                return 0;
+            }
+            let Some((mut attempt, trashed_at)) = game_state.ui.trashed.take() else {
+                return 0;
+            };
+            // The clock stood still while the attempt was in the trash.
+            attempt.resume(trashed_at.elapsed());
+            let fresh = std::mem::replace(&mut game_state.attempt, attempt);
+            game_state.attempt.ghost = fresh.ghost;
+            // Taking a reset back does not undo having reset.
+            game_state.attempt.pristine = false;
+            game_state.ui.explain_deadlock = false;
+            game_state.ui.message = Some("Reset taken back.".to_string());
+            0
+        }
+        Command::OpenRestartPrompt => {
+            if game_state.attempt.level.is_some() {
+                game_state.ui.prompt = Some(Prompt::RestartAt {
+                    count: String::new(),
+                    from_best: false,
+                });
+            }
+            0
+        }
+        Command::RestartAt { moves, from_best } => {
+            restart_at(game_state, moves, from_best, sink);
+            0
+        }
+        Command::MenuUp | Command::MenuDown => {
+            if let Some(scroll) = game_state.ui.whats_new_scroll {
+                let last = game_state.ui.screen.len().saturating_sub(1) as u16;
+                game_state.ui.whats_new_scroll = Some(match command {
+                    Command::MenuUp => scroll.saturating_sub(1),
+                    _ => (scroll + 1).min(last),
+                });
+            }
+            if let Some(cursor) = game_state.ui.menu_cursor {
+                let last = menu_entries(game_state).len().saturating_sub(1);
+                let moved = match command {
+                    Command::MenuUp => cursor.saturating_sub(1),
+                    _ => (cursor + 1).min(last),
+                };
+                if moved != cursor {
+                    game_state.ui.menu_cursor = Some(moved);
+                    play_ui_sound(game_state, SoundType::UiTick, sink);
+                }
+            }
+            0
+        }
+        Command::MenuConfirm => {
+            let entry = game_state
+                .ui
+                .menu_cursor
+                .and_then(|cursor| menu_entries(game_state).get(cursor).copied());
+            if let Some(entry) = entry {
+                play_ui_sound(game_state, SoundType::UiConfirm, sink);
+                match entry {
+                    MenuEntry::Level(level) => start_level(game_state, level),
+                    MenuEntry::Pack(idx) => play_pack(game_state, idx),
+                }
+            }
+            0
+        }
+        Command::PlayPack(idx) => {
+            play_pack(game_state, idx);
+            0
+        }
+        Command::MenuCancel => {
+            if game_state.ui.menu_cursor.is_some() || game_state.ui.whats_new_scroll.is_some() {
+                play_ui_sound(game_state, SoundType::UiCancel, sink);
+                show_welcome(game_state);
+            }
+            0
+        }
+        Command::OpenReport => {
+            game_state.ui.prompt = Some(Prompt::Report {
+                description: String::new(),
+            });
+            0
+        }
+        Command::FileReport(description) => {
+            let draft = issue_draft(game_state, &description);
+            let path = data_dir().map(|dir| {
+                let stamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_secs());
+                dir.join(format!("issue-{}.md", stamp))
+            });
+            game_state.ui.message = Some(match path {
+                Some(path)
+                    if fs::create_dir_all(path.parent().unwrap()).is_ok()
+                        && fs::write(&path, draft).is_ok() =>
+                {
+                    format!("Issue draft written to {}", path.display())
+                }
+                _ => "Could not write the issue draft.".to_string(),
+            });
+            0
+        }
+        Command::OpenEditor => {
+            if game_state.ui.menu_cursor.is_some() {
+                open_editor(game_state);
+            }
+            0
+        }
+        Command::ExportMoves => {
+            let Some((level, csv)) = &game_state.ui.export else {
+                return 0;
+            };
+            let path = data_dir().map(|dir| {
+                let stamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_secs());
+                dir.join(format!("moves-{:?}-{}.csv", level, stamp))
+            });
+            let message = match path {
+                Some(path)
+                    if fs::create_dir_all(path.parent().unwrap()).is_ok()
+                        && fs::write(&path, csv).is_ok() =>
+                {
+                    format!("Moves exported to {}", path.display())
+                }
+                _ => "Could not export the moves.".to_string(),
+            };
+            game_state.ui.message = Some(message);
+            0
+        }
+        Command::ExportBoard => {
+            let Some(level) = game_state.attempt.level else {
+                return 0;
+            };
+            let stamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            let path = PathBuf::from(format!("exported_{:?}_{}.xsb", level, stamp));
+            game_state.ui.message = Some(match fs::write(&path, xsb(&game_state.attempt)) {
+                Ok(()) => format!("Board exported to {}", path.display()),
+                Err(error) => format!("Could not export the board: {}.", error),
+            });
+            0
+        }
+        Command::Screenshot => {
+            let ansi = game_state.profile.settings.ansi_screenshots;
+            let shot = screenshot(&game_state.ui.last_frame.borrow(), ansi);
+            let path = data_dir().map(|dir| {
+                let stamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_secs());
+                let extension = if ansi { "ans" } else { "txt" };
+                dir.join(format!("screenshot-{}.{}", stamp, extension))
+            });
+            game_state.ui.message = Some(match path {
+                Some(path)
+                    if fs::create_dir_all(path.parent().unwrap()).is_ok()
+                        && fs::write(&path, shot).is_ok() =>
+                {
+                    format!("Screenshot saved to {}", path.display())
+                }
+                _ => "Could not save the screenshot.".to_string(),
+            });
+            0
+        }
+        Command::NextLevel | Command::PrevLevel if collection_in_play(game_state) => {
+            let Some(collection) = &game_state.profile.collection else {
+                return 0;
+            };
+            let index = match command {
+                Command::NextLevel => {
+                    Some(collection.index + 1).filter(|next| *next < collection.boards.len())
+                }
+                _ => collection.index.checked_sub(1),
+            };
+            match index {
+                Some(index) => play_board(game_state, index),
+                None => {
+                    game_state.ui.message = Some(match command {
+                        Command::NextLevel => "That was the last level of the pack.".to_string(),
+                        _ => "This is the first level of the pack.".to_string(),
+                    })
+                }
+            }
+            0
+        }
+        Command::NextLevel => {
+            if game_state.attempt.level.is_none() && game_state.ui.whats_new_scroll.is_none() {
+                play_recommended(game_state);
+            }
+            0
+        }
+        Command::PrevLevel => 0,
+        Command::Continue => {
+            if game_state.attempt.level.is_none() {
+                continue_playing(game_state);
+            }
+            0
+        }
+        Command::ArcadeScores => {
+            if game_state.attempt.level.is_none() {
+                game_state.ui.menu_cursor = None;
+                game_state.ui.export = None;
+                game_state.ui.celebration = None;
+                show_arcade_scores(game_state);
+            }
+            0
+        }
+        Command::WhatsNew => {
+            if game_state.ui.menu_cursor.is_some() {
+                show_whats_new(game_state);
+            }
+            0
+        }
+        Command::OpenFilter => {
+            if game_state.ui.menu_cursor.is_some() {
+                game_state.ui.prompt = Some(Prompt::Filter {
+                    query: String::new(),
+                });
+            }
+            0
+        }
+        Command::InstantReplay => {
+            start_instant_replay(game_state);
+            0
+        }
+        Command::ToggleRuler => {
+            game_state.ui.ruler = !game_state.ui.ruler;
+            remember_view(game_state);
+            0
+        }
+        Command::ToggleHeatmap => {
+            game_state.ui.show_heat = !game_state.ui.show_heat;
+            remember_view(game_state);
+            0
+        }
+        Command::ToggleReachable => {
+            game_state.ui.show_reachable = !game_state.ui.show_reachable;
+            0
+        }
+        Command::CenterCamera => {
+            if game_state.attempt.level.is_some() {
+                game_state.ui.camera = Some(game_state.attempt.player_position);
+            }
+            0
+        }
+        Command::ToggleKids => {
+            if game_state.attempt.level.is_none() {
+                set_kids_mode(game_state, true);
+            }
+            0
+        }
+        Command::CycleVariant => {
+            if game_state.ui.menu_cursor.is_some() {
+                game_state.ui.variant = game_state.ui.variant.next();
+                play_ui_sound(game_state, SoundType::UiTick, sink);
+                choose_level(game_state);
+            }
+            0
+        }
+        Command::StopNudge => {
+            if game_state.ui.nudges.decline().is_some() {
+                game_state.ui.message =
+                    Some("That tip will not be offered again this session.".to_string());
+            }
+            0
+        }
+        Command::OpenSaveLevel => {
+            if game_state.attempt.level.is_some() && game_state.ui.screen.is_empty() {
+                open_save_level(game_state);
+            }
+            0
+        }
+        Command::SaveLevel {
+            file,
+            title,
+            overwrite,
+        } => {
+            save_level(game_state, file, title, overwrite);
+            0
+        }
+        Command::ForgetViews => {
+            let count = game_state.profile.views.len();
+            game_state.profile.views.clear();
+            game_state.ui.message = Some(match count {
+                1 => "Forgot the ruler and heatmap settings of 1 level.".to_string(),
+                count => format!("Forgot the ruler and heatmap settings of {} levels.", count),
+            });
+            0
+        }
+        Command::ExplainDeadlock => {
+            game_state.ui.explain_deadlock =
+                !game_state.ui.explain_deadlock && game_state.attempt.deadlock.is_some();
+            0
+        }
+    }
+}
+
+/// The screens and aids open over the board, see `assist`.
+fn open_aids(game_state: &GameState) -> Vec<Aid> {
+    let ui = &game_state.ui;
+    [
+        (Aid::Prompt, ui.prompt.is_some()),
+        (Aid::InstantReplay, ui.instant_replay.is_some()),
+        (Aid::Reachable, ui.show_reachable),
+        (Aid::DeadlockExplanation, ui.explain_deadlock),
+        (
+            Aid::SolvableCheck,
+            game_state.profile.settings.check_solvable,
+        ),
+        (Aid::Heatmap, ui.show_heat),
+        (Aid::Ruler, ui.ruler),
+    ]
+    .into_iter()
+    .filter_map(|(aid, open)| open.then_some(aid))
+    .collect()
+}
+
+/// Stops or starts the attempt's clock, and notes the aids that helped, by
+/// what is open over the board now.
+fn track_aids(game_state: &mut GameState) {
+    if game_state.attempt.level.is_none() {
+        return;
+    }
+    let open = open_aids(game_state);
+    let attempt = &mut game_state.attempt;
+    let pause = open.iter().any(|aid| assist::effect(*aid) == Effect::Pause);
+    match (attempt.paused, pause) {
+        (None, true) => attempt.paused = Some(Instant::now()),
+        (Some(since), false) => {
+            attempt.paused = None;
+            attempt.resume(since.elapsed());
+        }
+        _ => {}
+    }
+    for aid in open {
+        if assist::effect(aid) == Effect::Assist && !attempt.assists.contains(&aid) {
+            attempt.assists.push(aid);
+        }
+    }
+}
+
+/// Tracks the longest gap between moves, which undos also end.
+fn note_pause(game_state: &mut GameState) {
+    let now = Instant::now();
+    if let Some(last_move) = game_state.attempt.trivia.last_move {
+        game_state.attempt.trivia.longest_pause =
+            game_state.attempt.trivia.longest_pause.max(now - last_move);
+    }
+    game_state.attempt.trivia.last_move = Some(now);
+}
+
+fn update_deadlock(game_state: &mut GameState) {
+    game_state.attempt.deadlock = game_state
+        .attempt
+        .level
+        .and_then(|_| find_deadlock(&game_state.attempt.grid));
+    if game_state.attempt.deadlock.is_none() {
+        game_state.ui.explain_deadlock = false;
+    }
+}
+
+fn new_game(args: Vec<String>) -> GameState {
+    let level_path = level_path(&args).cloned();
+    let from_stdin = args.iter().any(|arg| arg == "--stdin");
+    // Read before the terminal is taken over, which then reads keys from
+    // the controlling terminal rather than the pipe.
+    let loaded = match (level_path, from_stdin) {
+        (None, false) => None,
+        (Some(_), true) => Some(Err(
+            "Give either a level file or --stdin, not both.".to_string()
+        )),
+        (Some(path), false) => Some(
+            load_collection(Path::new(&path))
+                .map_err(|error| format!("Could not load {}: {}.", path, error)),
+        ),
+        (None, true) => Some(
+            read_collection()
+                .map_err(|error| format!("Could not read a level from stdin: {}.", error)),
+        ),
+    };
+    let mut game_state = GameState {
+        attempt: Attempt::default(),
+        profile: Profile::new(Settings::from_args(args)),
+        ui: Ui {
+            screen: vec![],
+            menu_cursor: None,
+            whats_new_scroll: None,
+            prompt: None,
+            message: None,
+            keymap: Keymap::new(DEFAULT_BINDINGS).expect("default key bindings are valid"),
+            explain_deadlock: false,
+            ruler: false,
+            show_heat: false,
+            show_reachable: false,
+            nudges: Nudges::default(),
+            pointer: None,
+            previews: RefCell::new(HashMap::new()),
+            packs: vec![],
+            variant: Variant::Normal,
+            last_frame: RefCell::new(Buffer::empty(Rect::default())),
+            last_ui_tick: None,
+            last_input: Instant::now(),
+            retry: None,
+            export: None,
+            instant_replay: None,
+            attract: None,
+            trivia_rotation: 0,
+            celebration: None,
+            arcade: None,
+            focused: true,
+            editor: None,
+            sounds: SoundPack::default(),
+            random_seed: 0,
+            daily: 0,
+            hold: None,
+            trashed: None,
+            ratings: Ratings {
+                known: load_ratings(),
+                ..Ratings::default()
+            },
+            solvability: Solvability::default(),
+            tips: Tips::new(None, 0),
+            camera: None,
+        },
+    };
+    // `--seed` pins the tips too, so that the screens come out the same.
+    let seed = game_state.profile.settings.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64)
+    });
+    let keys_file = data_dir().map(|dir| dir.join("keys.txt"));
+    match Keymap::load(keys_file.as_deref()) {
+        Ok(keymap) => game_state.ui.keymap = keymap,
+        Err(error) => {
+            game_state.ui.message = Some(format!("Using the default keys, keys.txt {}.", error))
+        }
+    }
+    let tips_file = data_dir().map(|dir| dir.join("tips.txt"));
+    game_state.ui.tips = Tips::new(tips_file.as_deref(), seed);
+    game_state.ui.sounds = match load_sounds(&game_state.profile.settings) {
+        Ok(sounds) => sounds,
+        Err(error) => {
+            game_state.ui.message = Some(format!("Playing the built-in sounds: {}.", error));
+            SoundPack::read(DEFAULT_SOUNDS).expect("the built-in sounds are a valid pack")
+        }
+    };
+    let fresh_install = game_state
+        .profile
+        .save
+        .path
+        .as_ref()
+        .is_none_or(|path| !path.exists());
+    load_progress(&mut game_state);
+    game_state.profile.save.lock = game_state.profile.save.path.as_deref().and_then(lock_save);
+    let version = env!("CARGO_PKG_VERSION");
+    if fresh_install || game_state.profile.save.read_only {
+        show_welcome(&mut game_state);
+    } else if game_state.profile.last_version.as_deref() != Some(version) {
+        show_whats_new(&mut game_state);
+    } else {
+        show_welcome(&mut game_state);
+    }
+    if game_state.profile.last_version.as_deref() != Some(version) {
+        game_state.profile.last_version = Some(version.to_string());
+        save_progress(&mut game_state);
+    }
+    match loaded {
+        Some(Ok(collection)) => {
+            game_state.profile.collection = Some(collection);
+            start_level(&mut game_state, Level::File);
+        }
+        Some(Err(problem)) => {
+            show_welcome(&mut game_state);
+            game_state.ui.screen.push(Line::from(problem).red());
+        }
+        None => {}
+    }
+    game_state
+}
+
+/// The level file named on the command line: the first argument that is
+/// neither a flag nor the value of one.
+fn level_path(args: &[String]) -> Option<&String> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if [
+            "--attract-minutes",
+            "--undo-by",
+            "--levels-dir",
+            "--scramble-seed",
+            "--sound-pack",
+            "--sounds-dir",
+            "--seed",
+            "--random-boxes",
+            "--random-size",
+        ]
+        .contains(&arg.as_str())
+        {
+            args.next();
+        } else if !arg.starts_with("--") {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+/// Reads every level of a file in the standard XSB notation, see `boards`.
+fn load_collection(path: &Path) -> Result<Collection, Error> {
+    let name = path
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let mut collection = parse_collection(name, &fs::read_to_string(path)?)?;
+    collection.path = Some(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+    let celebration = path.with_file_name(format!("{}.celebration.txt", collection.name));
+    collection.celebration = match fs::read_to_string(&celebration) {
+        Ok(text) => match celebration_frames(&text) {
+            Ok(frames) => Some(frames),
+            Err(problem) => {
+                log(&format!("{} ignored: {}", celebration.display(), problem));
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    Ok(collection)
+}
+
+/// Splits a celebration file into its frames at `%%` lines, dropping
+/// control characters. Frames must be small enough to sit above the results
+/// and few enough to play out in a few seconds.
+fn celebration_frames(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let frames: Vec<Vec<String>> = text
+        .split('\n')
+        .map(|line| line.chars().filter(|c| !c.is_control()).collect::<String>())
+        .collect::<Vec<String>>()
+        .split(|line| line.trim() == "%%")
+        .map(|frame| {
+            let mut frame = frame.to_vec();
+            while frame.last().is_some_and(|line| line.trim().is_empty()) {
+                frame.pop();
+            }
+            frame
+        })
+        .filter(|frame| !frame.is_empty())
+        .collect();
+    if frames.is_empty() {
+        return Err("it has no frames".to_string());
+    }
+    if frames.len() > CELEBRATION_MAX_FRAMES {
+        return Err(format!(
+            "it has {} frames, at most {} are played",
+            frames.len(),
+            CELEBRATION_MAX_FRAMES
+        ));
+    }
+    let too_big = frames.iter().position(|frame| {
+        frame.len() > CELEBRATION_MAX_SIZE.1
+            || frame
+                .iter()
+                .any(|line| line.chars().count() > CELEBRATION_MAX_SIZE.0)
+    });
+    if let Some(idx) = too_big {
+        return Err(format!(
+            "frame {} is bigger than {}x{}",
+            idx + 1,
+            CELEBRATION_MAX_SIZE.0,
+            CELEBRATION_MAX_SIZE.1
+        ));
+    }
+    Ok(frames)
+}
+
+/// Adds a line to `log.txt` in the data directory, for problems that are
+/// worked around rather than shown.
+fn log(line: &str) {
+    let Some(dir) = data_dir() else {
+        return;
+    };
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let file = fs::create_dir_all(&dir).and_then(|()| {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("log.txt"))
+    });
+    if let Ok(mut file) = file {
+        let _ = writeln!(file, "{} {}", stamp, line);
+    }
+}
+
+/// Reads the levels piped in for `--stdin`, see `load_collection`.
+fn read_collection() -> Result<Collection, Error> {
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Err(Error::LevelParse("nothing is piped in".to_string()));
+    }
+    let mut text = String::new();
+    stdin.read_to_string(&mut text)?;
+    parse_collection("stdin".to_string(), &text)
+}
+
+fn parse_collection(name: String, text: &str) -> Result<Collection, Error> {
+    let boards = boards(text);
+    if boards.is_empty() {
+        return Err(Error::LevelParse("the file has no board in it".to_string()));
+    }
+    let count = boards.len();
+    let boards = boards
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (rows, info))| match parse_board(rows) {
+            Ok(board) => Ok((board, info)),
+            Err(error) if count > 1 => {
+                Err(Error::LevelParse(format!("level {}: {}", idx + 1, error)))
+            }
+            Err(error) => Err(Error::LevelParse(error)),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Collection {
+        name,
+        path: None,
+        boards,
+        index: 0,
+        celebration: None,
+    })
+}
+
+impl Settings {
+    /// Reads the command-line flags:
+    ///
+    /// - `--attract-minutes N`: idle time before attract mode, `0` turns it off
+    /// - `--raw-samples`: play the goal sound at its recorded pitch
+    /// - `--show-bumps`: show the wall-bump counter under the board
+    /// - `--auto-undo`: take back any push that creates a deadlock
+    /// - `--ui-sounds`: play sounds when moving around menus
+    /// - `--mouse`: show the cell under the mouse pointer
+    /// - `--undo-by move|push|box`: how much one press of undo takes back
+    /// - `--strict`: count boxes pushed off goals as regressions
+    /// - `--pushes`: rank solves by box pushes instead of moves
+    /// - `--low-power`: tick less, redraw only on change, and turn off
+    ///   attract mode and mouse hover
+    /// - `--no-status-file`: do not write `status.json`
+    /// - `--ansi-screenshots`: save screenshots with their colours
+    /// - `--levels-dir DIR`: where to look for level packs
+    /// - `--scramble`: turn or flip every level, with a seed from the clock
+    /// - `--scramble-seed N`: the same with a given seed, to replay a session
+    /// - `--arcade`: play runs of levels for points that drain as you go
+    /// - `--no-nudges`: never offer tips after repeated failures
+    /// - `--sound-pack FILE`: play the sounds of a pack built with
+    ///   `--pack-sounds`
+    /// - `--sounds-dir DIR`: where loose sound files are looked for
+    /// - `--seed N`: the seed of the first random board, to replay one,
+    ///   and of the order tips come up in
+    /// - `--random-boxes N`: how many boxes random boards have
+    /// - `--random-size WxH`: the room of random boards, inside the walls
+    /// - `--check-solvable`: say under the board whether it can still be
+    ///   solved
+    /// - `--perf-report`: print how long the last commands took on quitting,
+    ///   in a build with the `perf` feature
+    fn from_args(args: Vec<String>) -> Settings {
+        let minutes = args
+            .iter()
+            .position(|arg| arg == "--attract-minutes")
+            .and_then(|idx| args.get(idx + 1))
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_ATTRACT_MINUTES);
+        let low_power = args.iter().any(|arg| arg == "--low-power");
+        Settings {
+            attract_idle: (minutes > 0 && !low_power).then(|| Duration::from_secs(minutes * 60)),
+            goal_pitch: !args.iter().any(|arg| arg == "--raw-samples"),
+            show_bumps: args.iter().any(|arg| arg == "--show-bumps"),
+            auto_undo: args.iter().any(|arg| arg == "--auto-undo"),
+            ui_sounds: args.iter().any(|arg| arg == "--ui-sounds"),
+            mouse: args.iter().any(|arg| arg == "--mouse") && !low_power,
+            strict: args.iter().any(|arg| arg == "--strict"),
+            undo_step: match args
+                .iter()
+                .position(|arg| arg == "--undo-by")
+                .and_then(|idx| args.get(idx + 1))
+                .map(String::as_str)
+            {
+                Some("push") => UndoStep::Push,
+                Some("box") => UndoStep::Box,
+                _ => UndoStep::Move,
+            },
+            low_power,
+            metric: if args.iter().any(|arg| arg == "--pushes") {
+                Metric::Pushes
+            } else {
+                Metric::Moves
+            },
+            status_file: !args.iter().any(|arg| arg == "--no-status-file"),
+            ansi_screenshots: args.iter().any(|arg| arg == "--ansi-screenshots"),
+            levels_dir: args
+                .iter()
+                .position(|arg| arg == "--levels-dir")
+                .and_then(|idx| args.get(idx + 1))
+                .map(PathBuf::from)
+                .or_else(|| data_dir().map(|dir| dir.join("levels"))),
+            scramble: args
+                .iter()
+                .position(|arg| arg == "--scramble-seed")
+                .and_then(|idx| args.get(idx + 1))
+                .and_then(|value| value.parse::<u64>().ok())
+                .or_else(|| {
+                    args.iter().any(|arg| arg == "--scramble").then(|| {
+                        SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |since| since.as_nanos() as u64)
+                    })
+                }),
+            arcade: args.iter().any(|arg| arg == "--arcade"),
+            nudges: !args.iter().any(|arg| arg == "--no-nudges"),
+            sound_pack: args
+                .iter()
+                .position(|arg| arg == "--sound-pack")
+                .and_then(|idx| args.get(idx + 1))
+                .map(PathBuf::from),
+            sounds_dir: args
+                .iter()
+                .position(|arg| arg == "--sounds-dir")
+                .and_then(|idx| args.get(idx + 1))
+                .map(PathBuf::from)
+                .or_else(|| data_dir().map(|dir| dir.join("sounds"))),
+            seed: args
+                .iter()
+                .position(|arg| arg == "--seed")
+                .and_then(|idx| args.get(idx + 1))
+                .and_then(|value| value.parse::<u64>().ok()),
+            random_boxes: args
+                .iter()
+                .position(|arg| arg == "--random-boxes")
+                .and_then(|idx| args.get(idx + 1))
+                .and_then(|value| value.parse::<usize>().ok())
+                .map_or(DEFAULT_RANDOM_BOXES, |boxes| {
+                    boxes.clamp(1, MAX_RANDOM_BOXES)
+                }),
+            random_size: args
+                .iter()
+                .position(|arg| arg == "--random-size")
+                .and_then(|idx| args.get(idx + 1))
+                .and_then(|value| {
+                    let (width, height) = value.split_once('x')?;
+                    Some((width.parse::<usize>().ok()?, height.parse::<usize>().ok()?))
+                })
+                .map_or(DEFAULT_RANDOM_SIZE, |(width, height)| {
+                    (
+                        width.clamp(3, EDITOR_MAX_SIZE.0 - 2),
+                        height.clamp(3, EDITOR_MAX_SIZE.1 - 2),
+                    )
+                }),
+            kids: data_dir().is_some_and(|dir| kids_marker(&dir).exists()),
+            check_solvable: args.iter().any(|arg| arg == "--check-solvable"),
+            perf_report: args.iter().any(|arg| arg == "--perf-report"),
+        }
+    }
+}
+
+impl Profile {
+    /// A profile with nothing recorded yet, saving to the file of the mode
+    /// `settings` are in.
+    fn new(settings: Settings) -> Profile {
+        Profile::saved_in(settings, data_dir())
+    }
+
+    /// `Profile::new`, with the save file in `dir` instead of the data
+    /// directory.
+    fn saved_in(settings: Settings, dir: Option<PathBuf>) -> Profile {
+        let path = dir.map(|dir| dir.join(save_file(settings.kids)));
+        Profile {
+            scores: HashMap::new(),
+            push_records: HashMap::new(),
+            best_runs: HashMap::new(),
+            first_tries: HashSet::new(),
+            clean_solves: HashSet::new(),
+            pristine_completions: 0,
+            lifetime_wall_bumps: 0,
+            solved_at: HashMap::new(),
+            used_undo: false,
+            variant_records: HashMap::new(),
+            dailies: BTreeMap::new(),
+            arcade_scores: vec![],
+            last_played: HashMap::new(),
+            views: HashMap::new(),
+            heat: HashMap::new(),
+            last_version: None,
+            settings,
+            save: Save {
+                path,
+                read_only: false,
+                lock: None,
+            },
+            collection: None,
+        }
+    }
+}
+
+/// Steps that each upgrade a save by one version: entry `n` turns the lines
+/// of a version `n + 1` file into version `n + 2`. Append a step whenever
+/// the format changes so every older file still loads.
+const MIGRATIONS: &[Migration] = &[
+    // 1 -> 2 adds `push_record` lines; older files simply have none.
+    |lines| lines,
+    // 2 -> 3 adds `heat` lines.
+    |lines| lines,
+    // 3 -> 4 adds `clean` lines.
+    |lines| lines,
+    // 4 -> 5 adds `arcade` lines.
+    |lines| lines,
+    // 5 -> 6 adds `last_played` lines.
+    |lines| lines,
+    // 6 -> 7 adds `view` lines.
+    |lines| lines,
+    // 7 -> 8 adds `variant_record` lines.
+    |lines| lines,
+    // 8 -> 9 names boards of level files as `pack:INDEX:PATH`; older files
+    // only name built-in levels.
+    |lines| lines,
+    // 9 -> 10 adds `daily` lines.
+    |lines| lines,
+    // 10 -> 11 adds the `used_undo` line.
+    |lines| lines,
+    // 11 -> 12 adds `solved_at` lines.
+    |lines| lines,
+    // 12 -> 13 keeps `heat` lines for boards of level files too, named the
+    // same way as records.
+    |lines| lines,
+];
+const SAVE_VERSION: usize = MIGRATIONS.len() + 1;
+
+type Migration = fn(Vec<String>) -> Vec<String>;
+
+/// What changed in each release, newest first, for the what's-new screen.
+const CHANGELOG: &[(&str, &[&str])] = &[(
+    "0.1.0",
+    &[
+        "Scores, best runs and first tries are saved between sessions.",
+        "Level select has a cursor, and / filters the list as you type.",
+        "Deadlocks are detected, explained with !, and can be undone automatically with --auto-undo.",
+        "Press i for an instant replay of your last ten moves.",
+        "Retry a level against a ghost of your earlier run.",
+        "The results screen shows your move and push counts plus some trivia.",
+        "Rank solves by box pushes instead of moves with --pushes.",
+    ],
+)];
+
+/// Schema version of `status.json`, see `status_json`.
+const STATUS_VERSION: u32 = 1;
+
+fn data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("sokoban_rust"));
+    }
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .map(|dir| dir.join("sokoban_rust"))
+}
+
+/// Reads the save file if there is one. Missing or unreadable files leave the
+/// fresh state alone; lines that are not understood are skipped.
+fn load_progress(game_state: &mut GameState) {
+    let Some(path) = game_state.profile.save.path.clone() else {
+        return;
+    };
+    let Some((version, lines)) = read_save(&path) else {
+        return;
+    };
+    if version > SAVE_VERSION {
+        game_state.profile.save.read_only = true;
+    }
+    merge_progress(game_state, &lines);
+    merge_save_file(game_state, &path.with_extension("pending"));
+}
+
+/// The version and lines of a save file, upgraded to the current format
+/// unless it was written by a newer release.
+fn read_save(path: &Path) -> Option<(usize, Vec<String>)> {
+    let contents = fs::read_to_string(path).ok()?;
+    let mut lines = contents.lines().map(str::to_string);
+    let version = lines
+        .next()
+        .and_then(|line| line.strip_prefix("version ")?.parse::<usize>().ok())
+        .filter(|version| *version >= 1)?;
+    let mut lines: Vec<String> = lines.collect();
+    if version <= SAVE_VERSION {
+        for step in &MIGRATIONS[version - 1..] {
+            lines = step(lines);
+        }
+    }
+    Some((version, lines))
+}
+
+/// Merges a save written by this or an older release; newer ones are left
+/// alone.
+fn merge_save_file(game_state: &mut GameState, path: &Path) {
+    if let Some((_, lines)) = read_save(path).filter(|(version, _)| *version <= SAVE_VERSION) {
+        merge_progress(game_state, &lines);
+    }
+}
+
+/// Folds the lines of a save into the game, keeping the better of the two
+/// for every record and the larger of every counter. Merging into a game
+/// with no progress is a plain load.
+fn merge_progress(game_state: &mut GameState, lines: &[String]) {
+    let metric = game_state.profile.settings.metric;
+    // Levels whose record came from `lines`, so their best run should too.
+    let mut taken = HashSet::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split(' ').collect();
+        match fields.as_slice() {
+            ["record", level, moves] => {
+                if let (Some(level), Ok(moves)) = (id_from_key(level), moves.parse()) {
+                    let score = game_state.profile.scores.get(&level);
+                    if moves > 0 && score.is_none_or(|best| moves < *best) {
+                        game_state.profile.scores.insert(level.clone(), moves);
+                        if let Metric::Moves = metric {
+                            taken.insert(level);
+                        }
+                    }
+                }
+            }
+            ["push_record", level, pushes] => {
+                if let (Some(level), Ok(pushes)) = (id_from_key(level), pushes.parse()) {
+                    let best = game_state
+                        .profile
+                        .push_records
+                        .entry(level.clone())
+                        .or_insert(pushes);
+                    if pushes < *best {
+                        *best = pushes;
+                        if let Metric::Pushes = metric {
+                            taken.insert(level);
+                        }
+                    }
+                }
+            }
+            ["best_run", level, moves] => {
+                if let (Some(level), Some(moves)) = (id_from_key(level), moves_from_lurd(moves)) {
+                    if taken.contains(&level) || !game_state.profile.best_runs.contains_key(&level)
+                    {
+                        game_state.profile.best_runs.insert(level, moves);
+                    }
+                }
+            }
+            ["first_try", level] => {
+                game_state.profile.first_tries.extend(id_from_key(level));
+            }
+            ["clean", level] => {
+                game_state.profile.clean_solves.extend(id_from_key(level));
+            }
+            ["heat", level, hash, attempts, cells @ ..] => {
+                if let (Some(id), Ok(hash), Ok(attempts)) = (
+                    id_from_key(level),
+                    u64::from_str_radix(hash, 16),
+                    attempts.parse(),
+                ) {
+                    let cells = cells
+                        .iter()
+                        .filter_map(|cell| {
+                            let (pos, count) = cell.split_once(':')?;
+                            let (x, y) = pos.split_once(',')?;
+                            Some((
+                                Pos::new(x.parse().ok()?, y.parse().ok()?),
+                                count.parse().ok()?,
+                            ))
+                        })
+                        .collect();
+                    let heat = game_state.profile.heat.entry(id).or_default();
+                    if attempts > heat.attempts {
+                        *heat = Heat {
+                            hash,
+                            attempts,
+                            cells,
+                        };
+                    }
+                }
+            }
+            ["first_try_completions", count] => {
+                let count = count.parse().unwrap_or(0);
+                game_state.profile.pristine_completions =
+                    game_state.profile.pristine_completions.max(count);
+            }
+            ["last_version", version] if game_state.profile.last_version.is_none() => {
+                game_state.profile.last_version = Some(version.to_string());
+            }
+            ["wall_bumps", count] => {
+                let count = count.parse().unwrap_or(0);
+                game_state.profile.lifetime_wall_bumps =
+                    game_state.profile.lifetime_wall_bumps.max(count);
+            }
+            ["used_undo"] => game_state.profile.used_undo = true,
+            ["solved_at", level, at] => {
+                if let (Some(level), Ok(at)) = (id_from_key(level), at.parse()) {
+                    let solved_at = game_state.profile.solved_at.entry(level).or_insert(at);
+                    *solved_at = (*solved_at).max(at);
+                }
+            }
+            ["last_played", index, completed, at, key @ ..] if !key.is_empty() => {
+                if let (Ok(index), Ok(at)) = (index.parse(), at.parse()) {
+                    let last = LastPlayed {
+                        index,
+                        completed: *completed == "1",
+                        at,
+                    };
+                    let entry = game_state
+                        .profile
+                        .last_played
+                        .entry(key.join(" "))
+                        .or_insert(last);
+                    if at > entry.at {
+                        *entry = last;
+                    }
+                }
+            }
+            ["view", hash, ruler, show_heat, used] => {
+                if let (Ok(hash), Ok(used)) = (u64::from_str_radix(hash, 16), used.parse()) {
+                    let view = View {
+                        ruler: *ruler == "1",
+                        show_heat: *show_heat == "1",
+                        used,
+                    };
+                    let views = &mut game_state.profile.views;
+                    let entry = views.entry(hash).or_insert(view);
+                    if used > entry.used {
+                        *entry = view;
+                    }
+                    trim_views(views);
+                }
+            }
+            ["arcade", points, levels, finished] => {
+                if let (Ok(points), Ok(levels), Ok(finished)) =
+                    (points.parse(), levels.parse(), finished.parse())
+                {
+                    let score = ArcadeScore {
+                        points,
+                        levels,
+                        finished,
+                    };
+                    add_arcade_score(&mut game_state.profile.arcade_scores, score);
+                }
+            }
+            ["variant_record", level, variant, moves, pushes] => {
+                let variant = VARIANTS
+                    .into_iter()
+                    .find(|known| format!("{:?}", known) == *variant);
+                if let (Some(level), Some(variant), Ok(moves), Ok(pushes)) =
+                    (id_from_key(level), variant, moves.parse(), pushes.parse())
+                {
+                    let best = game_state
+                        .profile
+                        .variant_records
+                        .entry((level, variant))
+                        .or_insert((moves, pushes));
+                    *best = (best.0.min(moves), best.1.min(pushes));
+                }
+            }
+            ["daily", day, moves] => {
+                let Some(moves) = moves_from_lurd(moves) else {
+                    continue;
+                };
+                let dailies = &mut game_state.profile.dailies;
+                if dailies
+                    .get(*day)
+                    .is_none_or(|best| moves.len() < best.len())
+                {
+                    dailies.insert(day.to_string(), moves);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Writes the save file, reporting any failure in the message line.
+fn save_progress(game_state: &mut GameState) {
+    let saved = write_progress(game_state);
+    report(game_state, saved);
+}
+
+/// Writes the save file, merged with whatever other copies of the game have
+/// saved in the meantime, and `status.json` next to it.
+fn write_progress(game_state: &mut GameState) -> Result<(), Error> {
+    let Some(path) = game_state
+        .profile
+        .save
+        .path
+        .clone()
+        .filter(|_| !game_state.profile.save.read_only)
+    else {
+        return Ok(());
+    };
+    if game_state.profile.save.lock.is_none() {
+        game_state.profile.save.lock = lock_save(&path);
+    }
+    let pending = path.with_extension("pending");
+    if game_state.profile.save.lock.is_none() {
+        merge_save_file(game_state, &pending);
+        return write_atomically(&pending, &save_text(game_state));
+    }
+    // Move the pending file aside first, so a copy that writes a fresh one
+    // while this merge runs does not have it deleted.
+    let merging = path.with_extension("merging");
+    if fs::rename(&pending, &merging).is_ok() {
+        merge_save_file(game_state, &merging);
+    }
+    merge_save_file(game_state, &path);
+    let saved = write_atomically(&path, &save_text(game_state));
+    let _ = fs::remove_file(&merging);
+    let status = match game_state.profile.settings.status_file {
+        true => write_atomically(
+            &path.with_file_name("status.json"),
+            &status_json(game_state),
+        ),
+        false => Ok(()),
+    };
+    saved.and(status)
+}
+
+/// Shows an error that play can carry on from in the message line.
+fn report(game_state: &mut GameState, result: Result<(), Error>) {
+    if let Err(error) = result {
+        game_state.ui.message = Some(format!("Error: {}", error));
+    }
+}
+
+/// Takes the lock that makes this copy of the game the one writing `path`,
+/// or `None` if another copy holds it. Where locking is not supported,
+/// every copy writes.
+fn lock_save(path: &Path) -> Option<Rc<File>> {
+    let _ = path.parent().map(fs::create_dir_all);
+    let file = File::create(path.with_extension("lock")).ok()?;
+    match file.try_lock() {
+        Ok(()) | Err(TryLockError::Error(_)) => Some(Rc::new(file)),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+fn save_text(game_state: &GameState) -> String {
+    let mut lines = vec![format!("version {}", SAVE_VERSION)];
+    let profile = &game_state.profile;
+    let ids: BTreeSet<&LevelId> = profile
+        .scores
+        .keys()
+        .chain(profile.push_records.keys())
+        .chain(profile.best_runs.keys())
+        .chain(&profile.first_tries)
+        .chain(&profile.clean_solves)
+        .chain(profile.solved_at.keys())
+        .collect();
+    for id in ids {
+        let key = id_key(id);
+        if let Some(record) = profile.scores.get(id) {
+            lines.push(format!("record {} {}", key, record));
+        }
+        if let Some(pushes) = profile.push_records.get(id) {
+            lines.push(format!("push_record {} {}", key, pushes));
+        }
+        if let Some(moves) = profile.best_runs.get(id) {
+            lines.push(format!("best_run {} {}", key, lurd(moves)));
+        }
+        if profile.first_tries.contains(id) {
+            lines.push(format!("first_try {}", key));
+        }
+        if profile.clean_solves.contains(id) {
+            lines.push(format!("clean {}", key));
+        }
+        if let Some(at) = profile.solved_at.get(id) {
+            lines.push(format!("solved_at {} {}", key, at));
+        }
+    }
+    let mut heat: Vec<(String, &Heat)> = profile
+        .heat
+        .iter()
+        .filter(|(_, heat)| heat.attempts > 0)
+        .map(|(id, heat)| (id_key(id), heat))
+        .collect();
+    heat.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, heat) in heat {
+        let mut cells: Vec<_> = heat.cells.iter().collect();
+        cells.sort();
+        let cells: Vec<String> = cells
+            .into_iter()
+            .map(|(pos, count)| format!("{},{}:{}", pos.x, pos.y, count))
+            .collect();
+        lines.push(format!(
+            "heat {} {:x} {} {}",
+            key,
+            heat.hash,
+            heat.attempts,
+            cells.join(" ")
+        ));
+    }
+    lines.push(format!(
+        "first_try_completions {}",
+        game_state.profile.pristine_completions
+    ));
+    lines.push(format!(
+        "wall_bumps {}",
+        game_state.profile.lifetime_wall_bumps
+    ));
+    if game_state.profile.used_undo {
+        lines.push("used_undo".to_string());
+    }
+    let mut last_played: Vec<_> = game_state.profile.last_played.iter().collect();
+    last_played.sort_by_key(|(key, _)| key.as_str());
+    for (key, last) in last_played {
+        lines.push(format!(
+            "last_played {} {} {} {}",
+            last.index,
+            u8::from(last.completed),
+            last.at,
+            key
+        ));
+    }
+    let mut views: Vec<_> = game_state.profile.views.iter().collect();
+    views.sort_by_key(|(hash, _)| **hash);
+    for (hash, view) in views {
+        lines.push(format!(
+            "view {:x} {} {} {}",
+            hash,
+            u8::from(view.ruler),
+            u8::from(view.show_heat),
+            view.used
+        ));
+    }
+    let mut variant_records: Vec<_> = game_state.profile.variant_records.iter().collect();
+    variant_records.sort_by_key(|((id, variant), _)| (id, *variant as u8));
+    for ((id, variant), (moves, pushes)) in variant_records {
+        lines.push(format!(
+            "variant_record {} {:?} {} {}",
+            id_key(id),
+            variant,
+            moves,
+            pushes
+        ));
+    }
+    for (day, moves) in &game_state.profile.dailies {
+        lines.push(format!("daily {} {}", day, lurd(moves)));
+    }
+    for score in &game_state.profile.arcade_scores {
+        lines.push(format!(
+            "arcade {} {} {}",
+            score.points, score.levels, score.finished
+        ));
+    }
+    if let Some(version) = &game_state.profile.last_version {
+        lines.push(format!("last_version {}", version));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Writes through a temporary file and a rename, so readers never see a
+/// half-written file.
+fn write_atomically(path: &Path, contents: &str) -> Result<(), Error> {
+    let temp = path.with_extension("tmp");
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&temp, contents))
+        .and_then(|_| fs::rename(&temp, path))
+        .map_err(Error::Save)
+}
+
+/// Progress for overlays and shell prompts. The schema is stable; fields may
+/// be added, but anything that changes or removes one bumps `version`:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "updated_at": 1760000000,
+///   "wall_bumps": 42,
+///   "packs": [
+///     {
+///       "name": "Built-in",
+///       "file": null,
+///       "levels": 5,
+///       "solved": 2,
+///       "first_tries": 1,
+///       "level_stats": [
+///         { "level": 1, "name": "Tutorial", "best_moves": 31, "best_pushes": 9, "first_try": true, "solved_at": 1759990000 },
+///         { "level": 2, "name": "Easy", "best_moves": null, "best_pushes": null, "first_try": false, "solved_at": null }
+///       ]
+///     },
+///     {
+///       "name": "microban",
+///       "file": "/home/player/levels/microban.xsb",
+///       "levels": 155,
+///       "solved": 1,
+///       "first_tries": 0,
+///       "level_stats": [
+///         { "level": 3, "name": "Level 3 of microban", "best_moves": 50, "best_pushes": 12, "first_try": false, "solved_at": 1759000000 }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// `updated_at` and `solved_at` are in seconds since the Unix epoch, and
+/// `best_moves`, `best_pushes` and `solved_at` are `null` for levels never
+/// solved. Packs follow the built-in levels, one for each file with a level
+/// that has progress, and list only those levels. Their `levels` and level
+/// names are `null` unless the file is open or on level select.
+fn status_json(game_state: &GameState) -> String {
+    let updated_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let profile = &game_state.profile;
+    let number = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+    let level_stat = |level: usize, name: Option<String>, id: &LevelId| {
+        format!(
+            "        {{ \"level\": {}, \"name\": {}, \"best_moves\": {}, \"best_pushes\": {}, \"first_try\": {}, \"solved_at\": {} }}",
+            level,
+            number(name.map(|name| format!("{:?}", name))),
+            number(profile.scores.get(id).map(i32::to_string)),
+            number(profile.push_records.get(id).map(i32::to_string)),
+            profile.first_tries.contains(id),
+            number(profile.solved_at.get(id).map(|at| (at / 1000).to_string())),
+        )
+    };
+    let pack = |name: &str,
+                file: Option<&str>,
+                levels: Option<usize>,
+                ids: &[LevelId],
+                stats: Vec<String>| {
+        [
+            "    {".to_string(),
+            format!("      \"name\": {:?},", name),
+            format!(
+                "      \"file\": {},",
+                number(file.map(|file| format!("{:?}", file)))
+            ),
+            format!(
+                "      \"levels\": {},",
+                number(levels.map(|levels| levels.to_string()))
+            ),
+            format!(
+                "      \"solved\": {},",
+                ids.iter()
+                    .filter(|id| profile.scores.contains_key(id))
+                    .count()
+            ),
+            format!(
+                "      \"first_tries\": {},",
+                ids.iter()
+                    .filter(|id| profile.first_tries.contains(id))
+                    .count()
+            ),
+            "      \"level_stats\": [".to_string(),
+            stats.join(",\n"),
+            "      ]".to_string(),
+            "    }".to_string(),
+        ]
+        .join("\n")
+    };
+    let built_in: Vec<LevelId> = LEVELS
+        .iter()
+        .map(|level| LevelId::BuiltIn(*level))
+        .collect();
+    let mut packs = vec![pack(
+        "Built-in",
+        None,
+        Some(LEVELS.len()),
+        &built_in,
+        LEVELS
+            .iter()
+            .zip(&built_in)
+            .enumerate()
+            .map(|(idx, (level, id))| {
+                let name = Some(level_name(*level)).filter(|name| !name.is_empty());
+                level_stat(idx + 1, name.map(str::to_string), id)
+            })
+            .collect(),
+    )];
+    let mut files: BTreeMap<&str, Vec<LevelId>> = BTreeMap::new();
+    let ids: BTreeSet<&LevelId> = profile
+        .scores
+        .keys()
+        .chain(profile.push_records.keys())
+        .chain(&profile.first_tries)
+        .chain(profile.solved_at.keys())
+        .collect();
+    for id in ids {
+        if let LevelId::Pack { file, .. } = id {
+            files.entry(file.as_str()).or_default().push(id.clone());
+        }
+    }
+    for (file, ids) in files {
+        let path = Path::new(file);
+        let open = profile
+            .collection
+            .as_ref()
+            .filter(|collection| collection.path.as_deref() == Some(path));
+        let levels = open.map(|collection| collection.boards.len()).or_else(|| {
+            game_state
+                .ui
+                .packs
+                .iter()
+                .find(|pack| fs::canonicalize(&pack.path).is_ok_and(|found| found == path))
+                .map(|pack| pack.levels)
+        });
+        let name = path
+            .file_stem()
+            .map_or(file.into(), |stem| stem.to_string_lossy());
+        let stats = ids
+            .iter()
+            .filter_map(|id| match id {
+                LevelId::Pack { index, .. } => Some(level_stat(
+                    index + 1,
+                    open.filter(|collection| *index < collection.boards.len())
+                        .map(|collection| collection.title(*index)),
+                    id,
+                )),
+                LevelId::BuiltIn(_) => None,
+            })
+            .collect();
+        packs.push(pack(&name, Some(file), levels, &ids, stats));
+    }
+    [
+        "{".to_string(),
+        format!("  \"version\": {},", STATUS_VERSION),
+        format!("  \"updated_at\": {},", updated_at),
+        format!("  \"wall_bumps\": {},", profile.lifetime_wall_bumps),
+        "  \"packs\": [".to_string(),
+        packs.join(",\n"),
+        "  ]".to_string(),
+        "}".to_string(),
+    ]
+    .join("\n")
+        + "\n"
+}
+
+fn level_from_key(key: &str) -> Option<Level> {
+    LEVELS
+        .into_iter()
+        .find(|level| format!("{:?}", level) == key)
+}
+
+/// How a level is named in the save: a built-in level by its name, and a
+/// board of a file as `pack:INDEX:PATH`, with `%` and spaces in the path
+/// escaped so that lines still split on spaces.
+fn id_key(id: &LevelId) -> String {
+    match id {
+        LevelId::BuiltIn(level) => format!("{:?}", level),
+        LevelId::Pack { file, index } => format!(
+            "pack:{}:{}",
+            index,
+            file.replace('%', "%25").replace(' ', "%20")
+        ),
+    }
+}
+
+fn id_from_key(key: &str) -> Option<LevelId> {
+    let Some(pack) = key.strip_prefix("pack:") else {
+        return level_from_key(key).map(LevelId::BuiltIn);
+    };
+    let (index, file) = pack.split_once(':')?;
+    Some(LevelId::Pack {
+        file: file.replace("%20", " ").replace("%25", "%"),
+        index: index.parse().ok()?,
+    })
+}
+
+/// The board of an attempt in XSB notation, after a comment line with the
+/// moves so far.
+fn xsb(attempt: &Attempt) -> String {
+    let mut text = format!("; {} moves: {}\n", attempt.move_count, lurd(&attempt.moves));
+    if let Some(transform) = attempt.scramble {
+        let original: Vec<MoveDirection> = attempt
+            .moves
+            .iter()
+            .map(|direction| transform.undo(direction))
+            .collect();
+        text.push_str(&format!(
+            "; scrambled, the same moves on the unscrambled level: {}\n",
+            lurd(&original)
+        ));
+    }
+    for row in &attempt.grid {
+        let row: String = row.iter().map(|tile| char::from(*tile)).collect();
+        text.push_str(row.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+fn finish_if_solved(game_state: &mut GameState, sink: &Sink) {
+    let Some(win) = game_state.finish_if_solved() else {
+        return;
+    };
+    let played = play_sound(SoundType::WinGame, &game_state.ui.sounds, sink);
+    report(game_state, played);
+    show_results(game_state, win);
+}
+
+/// The results screen of a solve `GameState::finish_if_solved` has already
+/// kept the records of.
+fn show_results(game_state: &mut GameState, win: Win) {
+    let Win {
+        result,
+        variant,
+        pushes,
+        high_score,
+        push_record,
+        record,
+        first_try,
+        clean,
+        daily_best,
+        ..
+    } = win;
+    let cur_level = result.level;
+    let cur_score = result.move_count;
+    let moves_line = score_line("Moves: ", cur_score, high_score);
+    let pushes_line = score_line("Pushes: ", pushes, push_record);
+    let (banner, score_lines) = match game_state.profile.settings.metric {
+        Metric::Moves => ("move", [moves_line, pushes_line]),
+        Metric::Pushes => ("push", [pushes_line, moves_line]),
+    };
+    let mut screen = vec![Line::from(if record {
+        format!("You won! New {} record!", banner)
+    } else {
+        "You won!".to_string()
+    })
+    .bold()];
+    screen.extend(score_lines);
+    screen.push(stat_line(
+        "Wall bumps: ",
+        format!("{} - {}", result.wall_bumps, bump_joke(result.wall_bumps)),
+    ));
+    if !result.assists.is_empty() {
+        let names: Vec<&str> = result.assists.iter().map(|aid| aid.name()).collect();
+        screen.push(stat_line("Assisted: ", names.join(", ")));
+    }
+    if game_state.profile.settings.strict {
+        screen.push(stat_line("Regressions: ", result.regressions.to_string()));
+        if clean {
+            screen.push(Line::from("Clean solve - no box ever left a goal!").green());
+        }
+    }
+    screen.extend(trivia_lines(game_state, &result));
+    if let Some(run) = &mut game_state.ui.arcade {
+        let left = run.left();
+        *run = ArcadeRun {
+            banked: run.banked + left,
+            levels: run.levels + 1,
+            ..ArcadeRun::default()
+        };
+        screen.push(
+            Line::from(format!(
+                "Arcade: {} points banked, {} in the run after {} levels.",
+                left, run.banked, run.levels
+            ))
+            .yellow(),
+        );
+    }
+    let frames = replay_frames(game_state, cur_level, &result.moves);
+    let csv = moves_csv(&frames, &result.moves, &result.move_times);
+    game_state.ui.export = Some((cur_level, csv));
+    game_state.ui.celebration = match &game_state.profile.collection {
+        Some(collection) if cur_level == Level::File => {
+            collection.celebration.clone().map(Animation::new)
+        }
+        _ => None,
+    }
+    .or_else(|| {
+        let frames = celebration_frames(KIDS_CELEBRATION).expect("the kids celebration is valid");
+        game_state
+            .profile
+            .settings
+            .kids
+            .then(|| Animation::new(frames))
+    });
+    if first_try {
+        screen.push(Line::from("First try - no resets, no undos!").green());
+    }
+    if cur_level == Level::Daily {
+        let day = date(game_state.ui.daily);
+        screen.push(Line::from(format!(
+            "Daily puzzle {}: solved in {} moves.",
+            day,
+            result.moves.len()
+        )));
+        screen.push(Line::from(format!("Solution: {}", lurd(&result.moves))));
+        if let Some(best) = daily_best {
+            screen
+                .push(Line::from(format!("Your best for the day stays at {} moves.", best)).dim());
+        }
+    }
+    if cur_level == Level::Random {
+        screen.push(
+            Line::from(format!(
+                "Random board {0}: play it again with --seed {0}. Its records are not kept.",
+                game_state.ui.random_seed
+            ))
+            .dim(),
+        );
+    }
+    if let Some(seed) = game_state.profile.settings.scramble {
+        screen.push(
+            Line::from(format!(
+                "Scrambled with seed {}: records are only kept for unscrambled levels.",
+                seed
+            ))
+            .dim(),
+        );
+    } else if variant != Variant::Normal {
+        screen.push(
+            Line::from(format!(
+                "Played {}: these records are kept apart from the level's own.",
+                variant.name()
+            ))
+            .dim(),
+        );
+    }
+    screen.push(Line::from(""));
+    if record {
+        screen.push(Line::from(format!(
+            "Press {} to go back to the main menu.",
+            game_state.ui.keymap.hint(&Command::LevelChoose)
+        )));
+    } else {
+        screen.push(Line::from(format!(
+            "Press {} to retry against this run or {} to go back to the main menu.",
+            game_state.ui.keymap.hint(&Command::Reset),
+            game_state.ui.keymap.hint(&Command::LevelChoose)
+        )));
+        game_state.ui.retry = Some((cur_level, result.moves));
+    }
+    match &game_state.profile.collection {
+        Some(collection) if cur_level == Level::File => {
+            let next = collection.index + 1;
+            screen.push(Line::from(if next < collection.boards.len() {
+                format!(
+                    "Press {} for the next level of the pack ({} of {}): {}.",
+                    game_state.ui.keymap.hint(&Command::NextLevel),
+                    next + 1,
+                    collection.boards.len(),
+                    collection.title(next)
+                )
+            } else {
+                "That was the last level of the pack.".to_string()
+            }));
+        }
+        _ => screen.extend(recommendation_line(game_state)),
+    }
+    screen.push(Line::from(format!(
+        "Press {} to export every move as CSV.",
+        game_state.ui.keymap.hint(&Command::ExportMoves)
+    )));
+    game_state.ui.screen = screen;
+    save_progress(game_state);
+}
+
+/// Up to three facts about the attempt just solved, starting from a different
+/// one each time.
+fn trivia_lines(game_state: &mut GameState, result: &AttemptResult) -> Vec<Line<'static>> {
+    let moves = &result.moves;
+    let mut facts = vec![];
+    if let Some((direction, run)) = longest_run(moves).filter(|(_, run)| *run > 1) {
+        facts.push(stat_line(
+            "Longest straight run: ",
+            format!("{} moves {}", run, direction_name(direction)),
+        ));
+    }
+    let turns = moves.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    facts.push(stat_line("Changes of direction: ", turns.to_string()));
+    let pushes = most_pushes(&replay_frames(game_state, result.level, moves));
+    if pushes > 0 {
+        facts.push(stat_line("Most pushes of one box: ", pushes.to_string()));
+    }
+    let pause = result.trivia.longest_pause;
+    if pause >= Duration::from_secs(1) {
+        facts.push(stat_line(
+            "Longest think: ",
+            format!("{:.1}s", pause.as_secs_f32()),
+        ));
+    }
+    let start = game_state.ui.trivia_rotation % facts.len();
+    game_state.ui.trivia_rotation += 1;
+    facts.rotate_left(start);
+    facts.truncate(3);
+    facts
+}
+
+/// The direction and length of the longest run of moves the same way,
+/// pushes included. Undone moves are not in `moves`, so they never count.
+fn longest_run(moves: &[MoveDirection]) -> Option<(&MoveDirection, usize)> {
+    moves
+        .chunk_by(|a, b| a == b)
+        .map(|run| (&run[0], run.len()))
+        .max_by_key(|(_, len)| *len)
+}
+
+fn direction_name(direction: &MoveDirection) -> &'static str {
+    match direction {
+        MoveDirection::Up => "up",
+        MoveDirection::Right => "right",
+        MoveDirection::Down => "down",
+        MoveDirection::Left => "left",
+    }
+}
+
+/// One CSV row per move of a solved attempt. Boxes are numbered in reading
+/// order on the starting board and keep their number as they are pushed.
+fn moves_csv(frames: &[Snapshot], moves: &[MoveDirection], times: &[Duration]) -> String {
+    let mut boxes: HashMap<Pos, usize> = HashMap::new();
+    for (y, row) in frames[0].0.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if cell.is_box() {
+                boxes.insert(Pos::new(x as i32, y as i32), boxes.len() + 1);
+            }
+        }
+    }
+    let mut rows = vec!["move,direction,push,box,boxes_on_goals,elapsed_ms".to_string()];
+    let mut previous = Duration::ZERO;
+    for (idx, (pair, direction)) in frames.windows(2).zip(moves).enumerate() {
+        let ((before, from), (after, to)) = (&pair[0], &pair[1]);
+        let pushed = cell_at(before, *to).is_box();
+        let id = if pushed {
+            let box_to = to.offset(to.x - from.x, to.y - from.y);
+            let id = boxes.remove(to).unwrap_or(0);
+            boxes.insert(box_to, id);
+            id.to_string()
+        } else {
+            String::new()
+        };
+        let time = times.get(idx).copied().unwrap_or(previous);
+        let fields = [
+            (idx + 1).to_string(),
+            direction_name(direction).to_string(),
+            pushed.to_string(),
+            id,
+            goals_filled(after).to_string(),
+            time.saturating_sub(previous).as_millis().to_string(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        rows.push(fields.join(","));
+        previous = time;
+    }
+    rows.join("\n") + "\n"
+}
+
+/// A CSV field as written: in quotes, with quotes doubled, if it holds a
+/// comma, a quote or a line break, and as it is otherwise.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// The most times any single box was pushed, following each box as it moves
+/// between the frames of a replay.
+fn most_pushes(frames: &[Snapshot]) -> u32 {
+    let mut pushes: HashMap<Pos, u32> = HashMap::new();
+    let mut most = 0;
+    for pair in frames.windows(2) {
+        let ((before, from), (_, to)) = (&pair[0], &pair[1]);
+        if !cell_at(before, *to).is_box() {
+            continue;
+        }
+        let box_to = to.offset(to.x - from.x, to.y - from.y);
+        let count = pushes.remove(to).unwrap_or(0) + 1;
+        pushes.insert(box_to, count);
+        most = most.max(count);
+    }
+    most
+}
+
+/// A results line for one metric: this attempt's count, and the best it was
+/// compared against if there was one.
+fn score_line(label: &'static str, count: i32, best: Option<i32>) -> Line<'static> {
+    let mut line = stat_line(label, count.to_string());
+    match best {
+        Some(best) if count < best => line.push_span(format!(" (was {})", best)),
+        Some(best) => line.push_span(format!(" (best {})", best)),
+        None => {}
+    }
+    line
+}
+
+/// A results line whose value stands out from its label.
+fn stat_line(label: &'static str, value: String) -> Line<'static> {
+    Line::from(vec![Span::from(label), Span::from(value).bold().yellow()])
+}
+
+fn bump_joke(bumps: u32) -> &'static str {
+    match bumps {
+        0 => "flawless navigation",
+        1..=4 => "just a few scuffs",
+        5..=19 => "the walls felt that",
+        _ => "the walls remember you",
+    }
+}
+
+fn open_editor(game_state: &mut GameState) {
+    game_state.ui.menu_cursor = None;
+    game_state.ui.screen = vec![];
+    let mut room = vec![Tile::Floor; 7];
+    room[0] = Tile::Wall;
+    room[6] = Tile::Wall;
+    game_state.ui.editor = Some(Editor {
+        draft: vec![
+            vec![Tile::Wall; 7],
+            room.clone(),
+            room.clone(),
+            room,
+            vec![Tile::Wall; 7],
+        ],
+        cursor: Pos::new(1, 1),
+        history: vec![],
+        playtesting: false,
+        stash: None,
+    });
+}
+
+/// Handles a command while the editor is open: the move keys move the
+/// cursor, the level keys stamp tiles, undo takes back the last stamp and
+/// confirm playtests the draft.
+fn edit(game_state: &mut GameState, command: Command) -> i32 {
+    let Some(editor) = &mut game_state.ui.editor else {
+        return 0;
+    };
+    match command {
+        Command::Quit => return 1,
+        Command::Move(direction) => move_cursor(editor, &direction),
+        Command::LevelSelect(level) => {
+            let tile = match level {
+                Level::One => Tile::Wall,
+                Level::Two => Tile::Box,
+                Level::Three => Tile::Goal,
+                Level::Four => Tile::Player,
+                _ => Tile::Floor,
+            };
+            editor.history.push(editor.draft.clone());
+            stamp(&mut editor.draft, editor.cursor, tile);
+        }
+        Command::ReverseMove => {
+            if let Some(draft) = editor.history.pop() {
+                editor.draft = draft;
+            }
+        }
+        Command::MenuConfirm => match validate_level(&editor.draft) {
+            Ok(_) => {
+                editor.playtesting = true;
+                let board = (editor.draft.clone(), LevelInfo::default());
+                editor.stash = game_state.profile.collection.replace(Collection {
+                    name: "draft".to_string(),
+                    path: None,
+                    boards: vec![board],
+                    index: 0,
+                    celebration: None,
+                });
+                play_board(game_state, 0);
+            }
+            Err(error) => {
+                game_state.ui.message = Some(format!("Cannot playtest: {}.", error));
+            }
+        },
+        Command::OpenSaveLevel => open_save_level(game_state),
+        Command::SaveLevel {
+            file,
+            title,
+            overwrite,
+        } => save_level(game_state, file, title, overwrite),
+        Command::LevelChoose | Command::MenuCancel => {
+            game_state.ui.editor = None;
+            game_state.ui.message = None;
+            choose_level(game_state);
+        }
+        _ => {}
+    }
+    0
+}
+
+/// Leaves the playtest for the editor, with the draft as it was.
+fn stop_playtest(game_state: &mut GameState) {
+    let Some(editor) = &mut game_state.ui.editor else {
+        return;
+    };
+    editor.playtesting = false;
+    game_state.profile.collection = editor.stash.take();
+    game_state.attempt = Attempt::default();
+    game_state.ui.screen = vec![];
+    game_state.ui.export = None;
+    game_state.ui.retry = None;
+    game_state.ui.celebration = None;
+}
+
+/// Moves the editor's cursor, growing the draft by a row or column of floor
+/// when it steps off an edge, up to `EDITOR_MAX_SIZE`.
+fn move_cursor(editor: &mut Editor, direction: &MoveDirection) {
+    let (width, height) = (editor.draft[0].len(), editor.draft.len());
+    let Pos { x, y } = editor.cursor;
+    match direction {
+        MoveDirection::Up if y == 0 && height < EDITOR_MAX_SIZE.1 => {
+            editor.draft.insert(0, vec![Tile::Floor; width]);
+        }
+        MoveDirection::Left if x == 0 && width < EDITOR_MAX_SIZE.0 => {
+            editor
+                .draft
+                .iter_mut()
+                .for_each(|row| row.insert(0, Tile::Floor));
+        }
+        MoveDirection::Up | MoveDirection::Left => {
+            let moved = editor.cursor.step(direction);
+            editor.cursor = Pos::new(moved.x.max(0), moved.y.max(0));
+        }
+        MoveDirection::Down => {
+            if y as usize + 1 == height && height < EDITOR_MAX_SIZE.1 {
+                editor.draft.push(vec![Tile::Floor; width]);
+            }
+            editor.cursor.y = (y + 1).min(editor.draft.len() as i32 - 1);
+        }
+        MoveDirection::Right => {
+            if x as usize + 1 == width && width < EDITOR_MAX_SIZE.0 {
+                editor
+                    .draft
+                    .iter_mut()
+                    .for_each(|row| row.push(Tile::Floor));
+            }
+            editor.cursor.x = (x + 1).min(editor.draft[0].len() as i32 - 1);
+        }
+    }
+}
+
+/// Puts `tile` at `pos`, keeping what it shares the cell with: a box or the
+/// player on a goal, or a goal under either. There is only ever one player,
+/// so stamping one moves them.
+fn stamp(draft: &mut [Vec<Tile>], pos: Pos, tile: Tile) {
+    let Some((y, x)) = pos.index(draft) else {
+        return;
+    };
+    if tile == Tile::Player {
+        for cell in draft.iter_mut().flatten() {
+            if cell.is_player() {
+                *cell = cell.emptied();
+            }
+        }
+    }
+    let cell = &mut draft[y][x];
+    *cell = match tile {
+        Tile::Box if cell.is_goal() => Tile::BoxOnGoal,
+        Tile::Player if cell.is_goal() => Tile::PlayerOnGoal,
+        Tile::Goal if cell.is_box() => Tile::BoxOnGoal,
+        Tile::Goal if cell.is_player() => Tile::PlayerOnGoal,
+        _ => tile,
+    };
+}
+
+fn open_save_level(game_state: &mut GameState) {
+    let title = match (&game_state.profile.collection, game_state.attempt.level) {
+        (Some(collection), Some(Level::File)) if game_state.ui.editor.is_none() => collection
+            .boards[collection.index]
+            .1
+            .title
+            .clone()
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    game_state.ui.prompt = Some(Prompt::SaveLevel {
+        file: String::new(),
+        title,
+        step: SaveStep::File,
+    });
+}
+
+/// Where a level saved as `file` goes: the levels directory, so that level
+/// select lists it as a pack.
+fn saved_level_path(game_state: &GameState, file: &str) -> Option<PathBuf> {
+    let dir = game_state.profile.settings.levels_dir.as_ref()?;
+    Some(dir.join(format!("{}.xsb", file)))
+}
+
+/// Writes the editor's draft, or the board being played as it stands, to
+/// the levels directory as XSB with a `Title:` line. The text is read back
+/// before it is written, so that what level select loads is exactly this
+/// board. An existing file is only replaced with `overwrite`; otherwise the
+/// prompt asks first.
+fn save_level(game_state: &mut GameState, file: String, title: String, overwrite: bool) {
+    if !overwrite && saved_level_path(game_state, &file).is_some_and(|path| path.exists()) {
+        game_state.ui.prompt = Some(Prompt::SaveLevel {
+            file,
+            title,
+            step: SaveStep::Overwrite,
+        });
+        return;
+    }
+    let grid = match &game_state.ui.editor {
+        Some(editor) if !editor.playtesting => crop(&editor.draft),
+        _ => game_state.attempt.grid.clone(),
+    };
+    let mut text: String = grid
+        .iter()
+        .map(|row| {
+            let row: String = row.iter().map(|tile| char::from(*tile)).collect();
+            format!("{}\n", row.trim_end())
+        })
+        .collect();
+    if !title.is_empty() {
+        text.push_str(&format!("Title: {}\n", title));
+    }
+    let read_back = parse_collection(file.clone(), &text);
+    let problem = match (&read_back, saved_level_path(game_state, &file)) {
+        (Err(error), _) => Some(error.to_string()),
+        (Ok(collection), _) if collection.boards.len() != 1 || collection.boards[0].0 != grid => {
+            Some("it does not read back as the same board".to_string())
+        }
+        (Ok(_), None) => Some("there is no levels directory".to_string()),
+        (Ok(_), Some(path)) => fs::create_dir_all(path.parent().unwrap())
+            .and_then(|()| fs::write(&path, &text))
+            .err()
+            .map(|error| error.to_string()),
+    };
+    game_state.ui.message = Some(match problem {
+        Some(problem) => format!("Could not save the level: {}.", problem),
+        None => {
+            let dir = game_state.profile.settings.levels_dir.as_deref();
+            game_state.ui.packs = dir.map(find_packs).unwrap_or_default();
+            request_ratings(game_state);
+            format!("Saved as {}.xsb, listed on level select.", file)
+        }
+    });
+}
+
+/// The board without rows and columns of floor around it, which level files
+/// cannot hold.
+fn crop(grid: &[Vec<Tile>]) -> Vec<Vec<Tile>> {
+    let used: Vec<usize> = (0..grid.len())
+        .filter(|y| grid[*y].iter().any(|cell| *cell != Tile::Floor))
+        .collect();
+    let (Some(top), Some(bottom)) = (used.first(), used.last()) else {
+        return vec![];
+    };
+    let rows = &grid[*top..=*bottom];
+    let left = rows
+        .iter()
+        .filter_map(|row| row.iter().position(|cell| *cell != Tile::Floor))
+        .min()
+        .unwrap_or(0);
+    let right = rows
+        .iter()
+        .filter_map(|row| row.iter().rposition(|cell| *cell != Tile::Floor))
+        .max()
+        .map_or(0, |x| x + 1);
+    rows.iter().map(|row| row[left..right].to_vec()).collect()
+}
+
+fn show_welcome(game_state: &mut GameState) {
+    game_state.ui.menu_cursor = None;
+    game_state.ui.whats_new_scroll = None;
+    game_state.attempt.grid = vec![];
+    game_state.ui.screen = vec![Line::from(format!(
+        "Welcome! Press {} to go to level select.",
+        game_state.ui.keymap.hint(&Command::LevelChoose)
+    ))];
+    game_state.ui.screen.extend(continue_line(game_state));
+    let key = game_state.ui.keymap.hint(&Command::ToggleKids);
+    game_state
+        .ui
+        .screen
+        .push(Line::from(if game_state.profile.settings.kids {
+            format!(
+                "Kids mode is on. Hold {} for {} seconds to leave it.",
+                key,
+                KIDS_HOLD.as_secs()
+            )
+        } else {
+            format!(
+                "Press {} for kids mode: small levels, easy undo and a profile of its own.",
+                key
+            )
+        }));
+    if game_state.profile.save.read_only {
+        game_state.ui.screen.push(Line::from(
+            "Your scores were saved by a newer version of the game and will not be updated.",
+        ));
+    } else if game_state.profile.save.path.is_some() && game_state.profile.save.lock.is_none() {
+        game_state.ui.screen.push(Line::from(
+            "The game is already running elsewhere. Records set here are merged into its save when either copy saves.",
+        ));
+    }
+    show_tip(game_state, true);
+}
+
+/// Lists the changes of every release, newest first. Shown once after an
+/// upgrade, and whenever asked for from level select.
+fn show_whats_new(game_state: &mut GameState) {
+    game_state.ui.menu_cursor = None;
+    game_state.ui.whats_new_scroll = Some(0);
+    game_state.attempt.grid = vec![];
+    game_state.ui.screen = vec![Line::from("What's new").bold(), Line::from("")];
+    for (version, changes) in CHANGELOG {
+        game_state
+            .ui
+            .screen
+            .push(Line::from(format!("Version {}", version)).yellow());
+        game_state.ui.screen.extend(
+            changes
+                .iter()
+                .map(|change| Line::from(format!("- {}", change))),
+        );
+        game_state.ui.screen.push(Line::from(""));
+    }
+    game_state.ui.screen.push(Line::from(format!(
+        "Up/Down to scroll, {} to close.",
+        game_state.ui.keymap.hint(&Command::MenuCancel)
+    )));
+}
+
+fn choose_level(game_state: &mut GameState) {
+    let entering = game_state.ui.menu_cursor.is_none();
+    game_state.ui.menu_cursor = Some(
+        game_state
+            .ui
+            .menu_cursor
+            .or_else(|| last_entry(game_state))
+            .unwrap_or(0),
+    );
+    game_state.ui.whats_new_scroll = None;
+    game_state.ui.export = None;
+    game_state.ui.celebration = None;
+    let mut menu = vec!["Choose level:".to_string()];
+    menu.extend(menu_entries(game_state).iter().map(|entry| {
+        let level = match entry {
+            MenuEntry::Level(level) => level,
+            MenuEntry::Pack(idx) => {
+                let pack = &game_state.ui.packs[*idx];
+                let tag = pack_rating(&game_state.ui.ratings, pack);
+                return match pack.levels {
+                    1 => format!("{} (1 level){}", pack.name, tag),
+                    levels => format!("{} ({} levels){}", pack.name, levels, tag),
+                };
+            }
+        };
+        let key = game_state.ui.keymap.hint(&Command::LevelSelect(*level));
+        let name = level_name(*level);
+        let mut entry = format!("{} - {}", key, name);
+        if LEVELS.contains(level) {
+            let hash = level_hash(&level_layout(*level));
+            entry.push_str(&rating_tag(game_state.ui.ratings.known.get(&hash)));
+        }
+        let id = LevelId::BuiltIn(*level);
+        if game_state.profile.first_tries.contains(&id) {
+            entry.push_str(" [first try]");
+        }
+        if game_state.profile.clean_solves.contains(&id) {
+            entry.push_str(" [clean]");
+        }
+        if let (Level::Daily, Some(best)) = (level, game_state.profile.dailies.get(&date(today())))
+        {
+            entry.push_str(&format!(" [solved in {} moves]", best.len()));
+        }
+        entry
+    }));
+    if game_state.profile.pristine_completions > 0 {
+        menu.push(String::new());
+        menu.push(format!(
+            "First-try completions: {}",
+            game_state.profile.pristine_completions
+        ));
+    }
+    if game_state.profile.lifetime_wall_bumps > 0 {
+        menu.push(format!(
+            "Walls bumped so far: {}",
+            game_state.profile.lifetime_wall_bumps
+        ));
+    }
+    menu.push(String::new());
+    menu.push(format!(
+        "Press {} to see what's new.",
+        game_state.ui.keymap.hint(&Command::WhatsNew)
+    ));
+    menu.push(format!(
+        "Levels are played {} - press {} to change.",
+        game_state.ui.variant.name(),
+        game_state.ui.keymap.hint(&Command::CycleVariant)
+    ));
+    menu.push(format!(
+        "Press {} to draw a level of your own.",
+        game_state.ui.keymap.hint(&Command::OpenEditor)
+    ));
+    game_state.attempt.grid = vec![];
+    game_state.ui.screen = menu.into_iter().map(Line::from).collect();
+    game_state.ui.screen.extend(recommendation_line(game_state));
+    show_tip(game_state, entering);
+}
+
+/// Adds the tip to the foot of the screen: a new one if `fresh`, else the
+/// one already up.
+fn show_tip(game_state: &mut GameState, fresh: bool) {
+    let (text, since) = match game_state.ui.tips.shown.take() {
+        Some((_, text, since)) if !fresh => (text, since),
+        _ => (next_tip(game_state), Instant::now()),
+    };
+    game_state.ui.screen.push(Line::from(""));
+    game_state.ui.screen.push(tip_line(&text));
+    game_state.ui.tips.shown = Some((game_state.ui.screen.len() - 1, text, since));
+}
+
+/// Swaps the tip for the next one once it has been up for `TIP_INTERVAL`,
+/// as long as the screen it was put on is still showing.
+fn rotate_tip(game_state: &mut GameState) {
+    let Some((idx, text, _)) = game_state.ui.tips.shown.clone() else {
+        return;
+    };
+    if !game_state.ui.tips.due() || game_state.ui.screen.get(idx) != Some(&tip_line(&text)) {
+        return;
+    }
+    let text = next_tip(game_state);
+    game_state.ui.screen[idx] = tip_line(&text);
+    game_state.ui.tips.shown = Some((idx, text, Instant::now()));
+}
+
+/// Picks a tip, more likely one on undo while it has never been used.
+fn next_tip(game_state: &mut GameState) -> String {
+    let used_undo = game_state.profile.used_undo;
+    let undo = game_state.ui.keymap.hint(&Command::ReverseMove);
+    game_state
+        .ui
+        .tips
+        .pick(|topic| topic == Topic::Undo && !used_undo)
+        .replace("{undo}", &undo)
+}
+
+fn tip_line(text: &str) -> Line<'static> {
+    Line::from(format!("Tip: {}", text)).dim()
+}
+
+/// A level that could be recommended: a built-in one or a board of a pack
+/// in the levels directory.
+struct Candidate {
+    id: LevelId,
+    name: String,
+    /// `level_hash` of its board, which its rating is kept by.
+    hash: u64,
+    /// Kids mode locks the levels it does not list.
+    unlocked: bool,
+}
+
+/// Every level level select offers, in its order: the built-in levels from
+/// easiest to hardest, then the boards of each pack. Packs with a board that
+/// does not parse cannot be played, so they are left out.
+fn candidates(game_state: &GameState) -> Vec<Candidate> {
+    let kids = game_state.profile.settings.kids;
+    let levels = LEVEL_MENU
+        .iter()
+        .map(|(level, _)| *level)
+        .filter(|level| LEVELS.contains(level))
+        .map(|level| Candidate {
+            id: LevelId::BuiltIn(level),
+            name: level_name(level).to_string(),
+            hash: level_hash(&level_layout(level)),
+            unlocked: !kids || kid_sized(level),
+        });
+    let boards = game_state
+        .ui
+        .packs
+        .iter()
+        .filter(|pack| pack.grids.len() == pack.levels)
+        .flat_map(|pack| {
+            let file = fs::canonicalize(&pack.path).unwrap_or_else(|_| pack.path.clone());
+            let file = file.to_string_lossy().into_owned();
+            pack.grids
+                .iter()
+                .enumerate()
+                .map(move |(index, grid)| Candidate {
+                    id: LevelId::Pack {
+                        file: file.clone(),
+                        index,
+                    },
+                    name: format!("level {} of {}", index + 1, pack.name),
+                    hash: level_hash(grid),
+                    unlocked: !kids,
+                })
+        });
+    levels.chain(boards).collect()
+}
+
+/// The level to suggest next among the unlocked `candidates`: the easiest
+/// one not solved yet by the solver's `ratings`, with boards not rated yet
+/// after those that are and in the order listed. Once every one is solved,
+/// the one whose push record is furthest above par, the fewest pushes the
+/// solver found, given with how far above it is.
+fn recommended_level<'a>(
+    candidates: &'a [Candidate],
+    ratings: &HashMap<u64, Option<u32>>,
+    scores: &HashMap<LevelId, i32>,
+    push_records: &HashMap<LevelId, i32>,
+) -> Option<(&'a Candidate, Option<i32>)> {
+    let unlocked = || candidates.iter().filter(|candidate| candidate.unlocked);
+    let unsolved = unlocked()
+        .filter(|candidate| !scores.contains_key(&candidate.id))
+        .min_by_key(|candidate| match ratings.get(&candidate.hash) {
+            Some(Some(pushes)) => (0, *pushes),
+            Some(None) => (1, 0),
+            None => (2, 0),
+        });
+    if let Some(candidate) = unsolved {
+        return Some((candidate, None));
+    }
+    unlocked()
+        .filter_map(|candidate| {
+            let par = (*ratings.get(&candidate.hash)?)?;
+            let above = push_records.get(&candidate.id)? - par as i32;
+            (above > 0).then_some((candidate, above))
+        })
+        .rev()
+        .max_by_key(|(_, above)| *above)
+        .map(|(candidate, above)| (candidate, Some(above)))
+}
+
+fn recommendation_line(game_state: &GameState) -> Option<Line<'static>> {
+    let candidates = candidates(game_state);
+    let profile = &game_state.profile;
+    let (candidate, above) = recommended_level(
+        &candidates,
+        &game_state.ui.ratings.known,
+        &profile.scores,
+        &profile.push_records,
+    )?;
+    let why = match above {
+        Some(1) => ", 1 push above par".to_string(),
+        Some(above) => format!(", {} pushes above par", above),
+        None => String::new(),
+    };
+    Some(Line::from(format!(
+        "Recommended next: {}{} - press {} to play it.",
+        candidate.name,
+        why,
+        game_state.ui.keymap.hint(&Command::NextLevel)
+    )))
+}
+
+/// Plays the level `recommendation_line` suggests, loading its pack if need
+/// be.
+fn play_recommended(game_state: &mut GameState) {
+    let candidates = candidates(game_state);
+    let profile = &game_state.profile;
+    let Some((candidate, _)) = recommended_level(
+        &candidates,
+        &game_state.ui.ratings.known,
+        &profile.scores,
+        &profile.push_records,
+    ) else {
+        return;
+    };
+    match &candidate.id {
+        LevelId::BuiltIn(level) => start_level(game_state, *level),
+        LevelId::Pack { file, index } => match load_collection(Path::new(file)) {
+            Ok(collection) => {
+                game_state.profile.collection = Some(collection);
+                play_board(game_state, *index);
+            }
+            Err(error) => {
+                game_state.ui.message = Some(format!("Could not load {}: {}.", file, error));
+            }
+        },
+    }
+}
+
+/// The entries listed on level select: the built-in levels then the packs,
+/// or while the filter is open, those matching it with the best match first.
+fn menu_entries(game_state: &GameState) -> Vec<MenuEntry> {
+    let kids = game_state.profile.settings.kids;
+    let levels = LEVEL_MENU
+        .iter()
+        .enumerate()
+        .filter(|(_, (level, _))| !kids || kid_sized(*level))
+        .map(|(idx, (level, name))| (MenuEntry::Level(*level), format!("{} {}", idx + 1, name)));
+    let packs = game_state
+        .ui
+        .packs
+        .iter()
+        .enumerate()
+        .filter(|_| !kids)
+        .map(|(idx, pack)| (MenuEntry::Pack(idx), pack.name.clone()));
+    let entries = levels.chain(packs);
+    let Some(Prompt::Filter { query }) = &game_state.ui.prompt else {
+        return entries.map(|(entry, _)| entry).collect();
+    };
+    let mut matches: Vec<(u32, MenuEntry)> = entries
+        .filter_map(|(entry, name)| Some((fuzzy_score(query, &name)?, entry)))
+        .collect();
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// The packs in `dir`, by name: every `.sok` or `.xsb` file with at least
+/// one board in it. A missing directory simply has none.
+fn find_packs(dir: &Path) -> Vec<Pack> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut packs: Vec<Pack> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    ["sok", "xsb"].contains(&extension.to_lowercase().as_str())
+                })
+        })
+        .filter_map(|path| {
+            let text = fs::read_to_string(&path).ok()?;
+            let found = boards(&text);
+            let levels = found.len();
+            let grids = found
+                .into_iter()
+                .filter_map(|(lines, _)| parse_board(lines).ok())
+                .collect();
+            let name = path.file_stem()?.to_string_lossy().into_owned();
+            (levels > 0).then_some(Pack {
+                name,
+                path,
+                levels,
+                grids,
+            })
+        })
+        .collect();
+    packs.sort_by(|a, b| a.name.cmp(&b.name));
+    packs
+}
+
+/// Hands the position on the board to the solver, unless it is known
+/// already, calling off any search of an earlier one. Only ever sends work
+/// off, so that a flood of moves never waits on the solver.
+fn check_solvable(game_state: &mut GameState) {
+    let attempt = &game_state.attempt;
+    let key = (game_state.profile.settings.check_solvable
+        && attempt.level.is_some()
+        && attempt.deadlock.is_none()
+        && attempt.grid.iter().flatten().any(|cell| *cell == Tile::Box))
+    .then(|| position_key(&attempt.grid, attempt.player_position));
+    let solvability = &mut game_state.ui.solvability;
+    if key == solvability.current {
+        return;
+    }
+    if let Some(cancel) = solvability.cancel.take() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+    solvability.current = key;
+    let Some(key) = key.filter(|key| !solvability.known.contains_key(key)) else {
+        return;
+    };
+    let worker = solvability.worker.get_or_insert_with(|| {
+        let (jobs, todo) = mpsc::channel::<(u64, Vec<Vec<Tile>>, Arc<AtomicBool>)>();
+        let (results, done) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            for (key, grid, cancel) in todo {
+                let verdict = solver::solvable(&grid, SOLVABLE_BUDGET, &cancel);
+                // A search called off gave up early, which says nothing.
+                if !cancel.load(Ordering::Relaxed) && results.send((key, verdict)).is_err() {
+                    break;
+                }
+            }
+        });
+        Rc::new(SolvableWorker { jobs, done, thread })
+    });
+    let cancel = Arc::new(AtomicBool::new(false));
+    let _ = worker
+        .jobs
+        .send((key, game_state.attempt.grid.clone(), cancel.clone()));
+    solvability.cancel = Some(cancel);
+}
+
+/// Takes in what the background threads have finished, then tells them to
+/// stop and gives them back to be waited for. A thread whose handle is
+/// still shared, by a copy of the game made for a replay, is only told.
+fn stop_workers(game_state: &mut GameState) -> Vec<(&'static str, JoinHandle<()>)> {
+    collect_ratings(game_state);
+    collect_solvable(game_state);
+    let mut threads = Vec::new();
+    let solvability = &mut game_state.ui.solvability;
+    if let Some(cancel) = solvability.cancel.take() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+    if let Some(worker) = solvability
+        .worker
+        .take()
+        .and_then(|worker| Rc::try_unwrap(worker).ok())
+    {
+        let SolvableWorker { jobs, thread, .. } = worker;
+        drop(jobs);
+        threads.push(("solvable", thread));
+    }
+    if let Some(worker) = game_state.ui.ratings.worker.take() {
+        worker.stop.store(true, Ordering::Relaxed);
+        if let Ok(worker) = Rc::try_unwrap(worker) {
+            let RatingWorker { jobs, thread, .. } = worker;
+            drop(jobs);
+            threads.push(("ratings", thread));
+        }
+    }
+    threads
+}
+
+fn collect_solvable(game_state: &mut GameState) {
+    let solvability = &mut game_state.ui.solvability;
+    if let Some(worker) = &solvability.worker {
+        solvability.known.extend(worker.done.try_iter());
+    }
+}
+
+/// A position's hash, the same wherever the player stands among the cells
+/// they can walk to: walking about never changes whether it can be solved.
+fn position_key(grid: &[Vec<Tile>], player: Pos) -> u64 {
+    let corner = reachable(grid, player).into_iter().min().unwrap_or(player);
+    let mut grid = grid.to_vec();
+    let cell = cell_at(&grid, player).emptied();
+    set_grid_cell(&mut grid, player, cell);
+    let cell = cell_at(&grid, corner).with_player();
+    set_grid_cell(&mut grid, corner, cell);
+    level_hash(&grid)
+}
+
+/// Hands every board level select lists that is not rated yet to the
+/// rating thread, starting it if need be.
+fn request_ratings(game_state: &mut GameState) {
+    let mut grids: Vec<Vec<Vec<Tile>>> = LEVEL_MENU
+        .iter()
+        .filter(|(level, _)| LEVELS.contains(level))
+        .map(|(level, _)| level_layout(*level))
+        .collect();
+    grids.extend(
+        game_state
+            .ui
+            .packs
+            .iter()
+            .flat_map(|pack| pack.grids.clone()),
+    );
+    let ratings = &mut game_state.ui.ratings;
+    for grid in grids {
+        let hash = level_hash(&grid);
+        if ratings.known.contains_key(&hash) || !ratings.pending.insert(hash) {
+            continue;
+        }
+        let worker = ratings.worker.get_or_insert_with(|| {
+            let (jobs, todo) = mpsc::channel::<(u64, Vec<Vec<Tile>>)>();
+            let (results, done) = mpsc::channel();
+            let stop = Arc::new(AtomicBool::new(false));
+            let stopping = stop.clone();
+            let thread = std::thread::spawn(move || {
+                for (hash, grid) in todo {
+                    let rating = solver::min_pushes(&grid, RATING_BUDGET, &stopping);
+                    // A rating called off is not kept: the board is rated
+                    // again next time.
+                    if stopping.load(Ordering::Relaxed) || results.send((hash, rating)).is_err() {
+                        break;
+                    }
+                }
+            });
+            Rc::new(RatingWorker {
+                jobs,
+                done,
+                stop,
+                thread,
+            })
+        });
+        let _ = worker.jobs.send((hash, grid));
+    }
+}
+
+/// Takes in the ratings finished since the last tick, keeping them for
+/// next time and filling them in on level select.
+fn collect_ratings(game_state: &mut GameState) {
+    let ratings = &mut game_state.ui.ratings;
+    let Some(worker) = &ratings.worker else {
+        return;
+    };
+    let finished: Vec<(u64, Option<u32>)> = worker.done.try_iter().collect();
+    if finished.is_empty() {
+        return;
+    }
+    for (hash, rating) in finished {
+        ratings.pending.remove(&hash);
+        ratings.known.insert(hash, rating);
+    }
+    if let Some(path) = ratings_path() {
+        let mut known: Vec<_> = ratings.known.iter().collect();
+        known.sort();
+        let text: String = known
+            .into_iter()
+            .map(|(hash, rating)| match rating {
+                Some(pushes) => format!("{:016x} {}\n", hash, pushes),
+                None => format!("{:016x} unknown\n", hash),
+            })
+            .collect();
+        let saved = write_atomically(&path, &text);
+        report(game_state, saved);
+    }
+    if game_state.ui.menu_cursor.is_some() && game_state.attempt.level.is_none() {
+        choose_level(game_state);
+    }
+}
+
+fn ratings_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("ratings.txt"))
+}
+
+fn load_ratings() -> HashMap<u64, Option<u32>> {
+    let Some(text) = ratings_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return HashMap::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (hash, rating) = line.split_once(' ')?;
+            let hash = u64::from_str_radix(hash, 16).ok()?;
+            match rating {
+                "unknown" => Some((hash, None)),
+                pushes => Some((hash, Some(pushes.parse().ok()?))),
+            }
+        })
+        .collect()
+}
+
+/// Up to five stars for a board's rating, `?` if the solver gave up on it,
+/// and `...` while it is still being rated.
+fn rating_tag(rating: Option<&Option<u32>>) -> String {
+    match rating {
+        Some(Some(pushes)) => format!(" [{:<5}]", "*".repeat(stars(*pushes))),
+        Some(None) => " [  ?  ]".to_string(),
+        None => " [ ... ]".to_string(),
+    }
+}
+
+fn stars(pushes: u32) -> usize {
+    RATING_STARS.iter().filter(|most| pushes > **most).count() + 1
+}
+
+/// The range of stars over the boards of a pack rated so far.
+fn pack_rating(ratings: &Ratings, pack: &Pack) -> String {
+    let rated: Vec<Option<u32>> = pack
+        .grids
+        .iter()
+        .filter_map(|grid| ratings.known.get(&level_hash(grid)).copied())
+        .collect();
+    let stars: Vec<usize> = rated
+        .iter()
+        .flatten()
+        .map(|pushes| stars(*pushes))
+        .collect();
+    let (Some(easiest), Some(hardest)) = (stars.iter().min(), stars.iter().max()) else {
+        return match (pack.grids.is_empty(), rated.is_empty()) {
+            (true, _) => String::new(),
+            (false, true) => rating_tag(None),
+            (false, false) => rating_tag(Some(&None)),
+        };
+    };
+    let range = match easiest == hardest {
+        true => "*".repeat(*easiest),
+        false => format!("{}-{}", "*".repeat(*easiest), "*".repeat(*hardest)),
+    };
+    match rated.len() < pack.grids.len() {
+        true => format!(
+            " [{}, {} of {} rated]",
+            range,
+            rated.len(),
+            pack.grids.len()
+        ),
+        false => format!(" [{}]", range),
+    }
+}
+
+/// Plays the first level of a pack, or says why it cannot be read.
+fn play_pack(game_state: &mut GameState, idx: usize) {
+    let Some(pack) = game_state.ui.packs.get(idx) else {
+        return;
+    };
+    match load_collection(&pack.path) {
+        Ok(collection) => {
+            game_state.profile.collection = Some(collection);
+            play_board(game_state, 0);
+        }
+        Err(error) => {
+            game_state.ui.message = Some(format!("Could not load {}: {}.", pack.name, error));
+        }
+    }
+}
+
+/// Whether the level being played, or whose results are showing, is from
+/// the loaded collection, so next and previous step through it.
+fn collection_in_play(game_state: &GameState) -> bool {
+    game_state.profile.collection.is_some()
+        && (game_state.attempt.level == Some(Level::File)
+            || matches!(game_state.ui.export, Some((Level::File, _))))
+}
+
+/// Plays board `index` of the collection.
+fn play_board(game_state: &mut GameState, index: usize) {
+    let Some(collection) = &mut game_state.profile.collection else {
+        return;
+    };
+    collection.index = index;
+    start_level(game_state, Level::File);
+}
+
+/// How well `query` matches `candidate` when its characters appear in order,
+/// ignoring case and accents, or `None` if they do not. Runs of adjacent
+/// characters and matches at the start of a word score higher.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let candidate = fold_text(candidate);
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+    for wanted in fold_text(query).into_iter().filter(|c| *c != ' ') {
+        let found = (next..candidate.len()).find(|idx| candidate[*idx] == wanted)?;
+        score += 1;
+        if found == 0 || candidate[found - 1] == ' ' {
+            score += 2;
+        }
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 3;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+/// Lower-cases text and strips the accents of common Latin letters.
+fn fold_text(text: &str) -> Vec<char> {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+            'ç' => 'c',
+            'è' | 'é' | 'ê' | 'ë' => 'e',
+            'ì' | 'í' | 'î' | 'ï' => 'i',
+            'ñ' => 'n',
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+            'ù' | 'ú' | 'û' | 'ü' => 'u',
+            'ý' | 'ÿ' => 'y',
+            _ => c,
+        })
+        .collect()
+}
+
+fn start_level(game_state: &mut GameState, level: Level) {
+    game_state.ui.trashed = None;
+    game_state.ui.screen = vec![];
+    game_state.ui.menu_cursor = None;
+    game_state.ui.retry = None;
+    game_state.ui.export = None;
+    game_state.ui.celebration = None;
+    game_state.ui.explain_deadlock = false;
+    game_state.ui.camera = None;
+    let settings = &game_state.profile.settings;
+    let grid = match level {
+        Level::File => match &game_state.profile.collection {
+            Some(collection) => collection.boards[collection.index].0.clone(),
+            None => vec![],
+        },
+        Level::Random => match generate::generate(
+            game_state.ui.random_seed,
+            settings.random_boxes,
+            settings.random_size,
+        ) {
+            Ok(grid) => grid,
+            Err(error) => {
+                show_unplayable(game_state, &error);
+                return;
+            }
+        },
+        Level::Daily => match generate::generate(game_state.ui.daily, DAILY_BOXES, DAILY_SIZE) {
+            Ok(grid) => grid,
+            Err(error) => {
+                show_unplayable(game_state, &error);
+                return;
+            }
+        },
+        level => level_layout(level),
+    };
+    let hash = level_hash(&grid);
+    // A scramble already turns every level, so it leaves variants out. The
+    // daily is never turned, so that everyone plays the same board.
+    let daily = level == Level::Daily;
+    let variant = if daily || game_state.profile.settings.scramble.is_some() {
+        Variant::Normal
+    } else {
+        game_state.ui.variant
+    };
+    let scramble = match game_state.profile.settings.scramble {
+        Some(seed) if !daily => Some(Transform::from_seed(seed ^ hash)),
+        _ => variant.transform(),
+    };
+    let grid = pad_rows(match scramble {
+        Some(transform) => transform.grid(&grid),
+        None => grid,
+    });
+    let player_position = match validate_level(&grid) {
+        Ok(player_position) => player_position,
+        Err(error) => {
+            show_unplayable(game_state, &error.to_string());
+            return;
+        }
+    };
+    game_state.attempt = Attempt::start(level, (grid, player_position));
+    game_state.attempt.scramble = scramble;
+    game_state.attempt.variant = variant;
+    game_state.attempt.layout_hash = hash;
+    if let Some(view) = game_state.profile.views.get_mut(&hash) {
+        view.used = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        game_state.ui.ruler = view.ruler;
+        game_state.ui.show_heat = view.show_heat;
+    }
+    if let Some((key, index)) = place(game_state, level) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        game_state.profile.last_played.insert(
+            key,
+            LastPlayed {
+                index,
+                completed: false,
+                at,
+            },
+        );
+    }
+    // Heat is collected on the level's own board only.
+    if scramble.is_some() {
+        return;
+    }
+    let Some(id) = level_id(game_state, level) else {
+        return;
+    };
+    let heat = game_state.profile.heat.entry(id).or_default();
+    if heat.hash != hash {
+        *heat = Heat {
+            hash,
+            ..Heat::default()
+        };
+    }
+}
+
+/// Where kids mode is remembered between sessions, in the data directory
+/// `dir`: the file is there while it is on.
+fn kids_marker(dir: &Path) -> PathBuf {
+    dir.join("kids_mode")
+}
+
+/// The save file of each mode, so kids mode keeps its records apart.
+fn save_file(kids: bool) -> &'static str {
+    if kids {
+        "kids.txt"
+    } else {
+        "scores.txt"
+    }
+}
+
+/// Whether kids mode lists `level`: the built-in levels with few boxes.
+fn kid_sized(level: Level) -> bool {
+    LEVELS.contains(&level)
+        && level_layout(level)
+            .iter()
+            .flatten()
+            .filter(|cell| cell.is_box())
+            .count()
+            <= KIDS_MAX_BOXES
+}
+
+/// Counts another repeat of `command` held down in kids mode, showing how
+/// far along the hold is. True once it has been held for `KIDS_HOLD`.
+fn held(game_state: &mut GameState, command: Command) -> bool {
+    let now = Instant::now();
+    let hold = match game_state.ui.hold.take() {
+        Some(hold) if hold.command == command && hold.last.elapsed() <= KIDS_HOLD_GAP => {
+            Hold { last: now, ..hold }
+        }
+        _ => Hold {
+            command: command.clone(),
+            started: now,
+            last: now,
+        },
+    };
+    let share = hold.started.elapsed().as_secs_f32() / KIDS_HOLD.as_secs_f32();
+    if share >= 1.0 {
+        return true;
+    }
+    let filled = (share * 10.0) as usize;
+    game_state.ui.message = Some(format!(
+        "Keep holding {} to {}: [{}{}]",
+        game_state.ui.keymap.hint(&command),
+        match command {
+            Command::Quit => "quit",
+            _ => "leave kids mode",
+        },
+        "#".repeat(filled),
+        "-".repeat(10 - filled)
+    ));
+    game_state.ui.hold = Some(hold);
+    false
+}
+
+/// Turns kids mode on or off and remembers it for next time. Each mode has
+/// its own profile, so progress is saved first and the other one loaded.
+fn set_kids_mode(game_state: &mut GameState, on: bool) {
+    switch_kids_mode(game_state, on, data_dir());
+}
+
+/// `set_kids_mode`, keeping the profiles and the marker in `dir`.
+fn switch_kids_mode(game_state: &mut GameState, on: bool, dir: Option<PathBuf>) {
+    save_progress(game_state);
+    let mut settings = game_state.profile.settings.clone();
+    settings.kids = on;
+    let collection = game_state.profile.collection.take();
+    game_state.profile = Profile::saved_in(settings, dir.clone());
+    game_state.profile.collection = collection;
+    load_progress(game_state);
+    game_state.profile.save.lock = game_state.profile.save.path.as_deref().and_then(lock_save);
+    let remembered = match (dir.map(|dir| kids_marker(&dir)), on) {
+        (None, _) => Ok(()),
+        (Some(marker), true) => fs::write(marker, ""),
+        (Some(marker), false) => fs::remove_file(marker).or_else(|error| match error.kind() {
+            std::io::ErrorKind::NotFound => Ok(()),
+            _ => Err(error),
+        }),
+    };
+    game_state.ui.hold = None;
+    game_state.ui.menu_cursor = None;
+    show_welcome(game_state);
+    if let Err(error) = remembered {
+        report(game_state, Err(Error::Io(error)));
+    }
+}
+
+/// Today, counted in days from 1970-01-01 in UTC.
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400)
+}
+
+/// A day counted from 1970-01-01 as `YYYY-MM-DD`, by the Gregorian
+/// calendar: days are counted in 400-year eras that start on 1 March, so
+/// leap days fall at the end of each year.
+fn date(days: u64) -> String {
+    let days = days as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn show_unplayable(game_state: &mut GameState, problem: &str) {
+    game_state.attempt = Attempt::default();
+    game_state.ui.screen = vec![
+        Line::from("This level cannot be played.").bold(),
+        Line::from(format!("Problem: {}.", problem)).red(),
+        Line::from(""),
+        Line::from(format!(
+            "Press {} to go back to the main menu.",
+            game_state.ui.keymap.hint(&Command::LevelChoose)
+        )),
+    ];
+}
+
+/// Keeps the panels open on the level in play for the next time it starts.
+fn remember_view(game_state: &mut GameState) {
+    if game_state.attempt.level.is_none() {
+        return;
+    }
+    let view = View {
+        ruler: game_state.ui.ruler,
+        show_heat: game_state.ui.show_heat,
+        used: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64),
+    };
+    let views = &mut game_state.profile.views;
+    views.insert(game_state.attempt.layout_hash, view);
+    trim_views(views);
+}
+
+/// Drops the views of the boards played longest ago beyond `VIEW_LIMIT`.
+fn trim_views(views: &mut HashMap<u64, View>) {
+    while views.len() > VIEW_LIMIT {
+        let Some(oldest) = views
+            .iter()
+            .min_by_key(|(_, view)| view.used)
+            .map(|(hash, _)| *hash)
+        else {
+            return;
+        };
+        views.remove(&oldest);
+    }
+}
+
+/// The pack or built-in level played most recently.
+fn continue_target(game_state: &GameState) -> Option<(&String, LastPlayed)> {
+    game_state
+        .profile
+        .last_played
+        .iter()
+        .max_by_key(|(_, last)| last.at)
+        .map(|(key, last)| (key, *last))
+}
+
+/// Offers to pick up where the last session left off.
+fn continue_line(game_state: &GameState) -> Option<Line<'static>> {
+    let (key, last) = continue_target(game_state)?;
+    let name = if key == BUILT_IN {
+        let level = LEVELS.get(last.index)?;
+        format!("Level {} ({})", last.index + 1, level_name(*level))
+    } else {
+        let pack = Path::new(key).file_stem()?.to_string_lossy().into_owned();
+        format!("level {} of {}", last.index + 1, pack)
+    };
+    let key = game_state.ui.keymap.hint(&Command::Continue);
+    Some(Line::from(if last.completed {
+        format!("Press {} to carry on after {}.", key, name)
+    } else {
+        format!("Press {} to continue {}.", key, name)
+    }))
+}
+
+/// Starts the level after the one last played if that was solved, or the
+/// same one again.
+fn continue_playing(game_state: &mut GameState) {
+    let Some((key, last)) = continue_target(game_state) else {
+        return;
+    };
+    let next = last.index + usize::from(last.completed);
+    if key == BUILT_IN {
+        if let Some(level) = LEVELS.get(next).or(LEVELS.get(last.index)) {
+            start_level(game_state, *level);
+        }
+        return;
+    }
+    let path = PathBuf::from(key);
+    match load_collection(&path) {
+        Ok(collection) => {
+            let index = next.min(collection.boards.len() - 1);
+            game_state.profile.collection = Some(collection);
+            play_board(game_state, index);
+        }
+        Err(error) => {
+            game_state.ui.message = Some(format!("Could not load {}: {}.", path.display(), error));
+        }
+    }
+}
+
+/// Where level select starts: on the level or pack played most recently.
+fn last_entry(game_state: &GameState) -> Option<usize> {
+    let (key, last) = continue_target(game_state)?;
+    menu_entries(game_state)
+        .iter()
+        .position(|entry| match entry {
+            MenuEntry::Level(level) => key == BUILT_IN && LEVELS.get(last.index) == Some(level),
+            MenuEntry::Pack(idx) => {
+                let path = &game_state.ui.packs[*idx].path;
+                fs::canonicalize(path).is_ok_and(|path| path.to_string_lossy() == key.as_str())
+            }
+        })
+}
+
+/// The effects of a step `player_move` took: its sounds, the bump and
+/// blocked counters, and a step that went ahead kept in the move history.
+fn play_out(game_state: &mut GameState, direction: MoveDirection, result: MoveResult, sink: &Sink) {
+    let played = match result {
+        MoveResult::Bumped => {
+            game_state.attempt.wall_bumps += 1;
+            game_state.profile.lifetime_wall_bumps += 1;
+            play_sound(SoundType::Oof, &game_state.ui.sounds, sink)
+        }
+        MoveResult::Blocked(blocked) => {
+            game_state.attempt.blocked = Some(blocked);
+            match blocked {
+                Blocked::Wall => play_sound(SoundType::BarrelOof, &game_state.ui.sounds, sink),
+                Blocked::Box(_) => {
+                    play_sound_at_speed(SoundType::BarrelOof, 1.25, &game_state.ui.sounds, sink)
+                }
+            }
+        }
+        MoveResult::Moved => play_sound(SoundType::PlayerMove, &game_state.ui.sounds, sink),
+        MoveResult::Pushed {
+            off_goal,
+            onto_goal,
+        } => {
+            let placed = goals_filled(&game_state.attempt.grid)
+                .saturating_sub(game_state.attempt.goals_filled_at_start);
+            for cue in push_cues(
+                off_goal,
+                onto_goal,
+                game_state.profile.settings.goal_pitch.then_some(placed),
+            ) {
+                let played = play_cue(cue, &game_state.ui.sounds, sink);
+                report(game_state, played);
+            }
+            Ok(())
+        }
+    };
+    report(game_state, played);
+    if let MoveResult::Moved | MoveResult::Pushed { .. } = result {
+        game_state.attempt.moves.push(direction);
+        let elapsed = game_state.attempt.started.map(|started| started.elapsed());
+        game_state
+            .attempt
+            .move_times
+            .push(elapsed.unwrap_or_default());
+        game_state.attempt.move_count += 1;
+    }
+}
+
+/// Where the player stood before and after each of `moves` from the start
+/// of `level`.
+fn ghost_trail(game_state: &GameState, level: Level, moves: &[MoveDirection]) -> Vec<Pos> {
+    replay_frames(game_state, level, moves)
+        .into_iter()
+        .map(|(_, position)| position)
+        .collect()
+}
+
+/// Plays `moves` from the start of `level` on a scratch copy of the game,
+/// without any of their sounds, and returns the board and player position
+/// before the first move and after every move.
+fn replay_frames(game_state: &GameState, level: Level, moves: &[MoveDirection]) -> Vec<Snapshot> {
+    let mut scratch = game_state.clone();
+    start_level(&mut scratch, level);
+    let mut frames = vec![(
+        scratch.attempt.grid.clone(),
+        scratch.attempt.player_position,
+    )];
+    for direction in moves {
+        player_move(direction, &mut scratch.attempt);
+        frames.push((
+            scratch.attempt.grid.clone(),
+            scratch.attempt.player_position,
+        ));
+    }
+    frames
+}
+
+/// Moves played on a level's own board, carried over to the board of the
+/// attempt in play.
+fn scrambled(game_state: &GameState, moves: Vec<MoveDirection>) -> Vec<MoveDirection> {
+    match game_state.attempt.scramble {
+        Some(transform) => moves
+            .iter()
+            .map(|direction| transform.direction(direction))
+            .collect(),
+        None => moves,
+    }
+}
+
+/// Where boxes stood each time an attempt at a level died, for the heatmap.
+#[derive(Clone, Default)]
+struct Heat {
+    /// `level_hash` of the level the counts were collected on.
+    hash: u64,
+    attempts: u32,
+    cells: HashMap<Pos, u32>,
+}
+
+impl Heat {
+    /// The fraction of failed attempts that had a box on `pos`.
+    fn share(&self, pos: Pos) -> f32 {
+        let count = self.cells.get(&pos).copied().unwrap_or(0);
+        count as f32 / self.attempts.max(1) as f32
+    }
+}
+
+/// Minimum gap between two redraws caused by mouse motion alone.
+const HOVER_REDRAW: Duration = Duration::from_millis(250);
+/// Minimum gap between two cursor ticks, so a held arrow key does not turn
+/// into a buzz.
+const UI_TICK_INTERVAL: Duration = Duration::from_millis(70);
+
+fn play_ui_sound(game_state: &mut GameState, sound_type: SoundType, sink: &Sink) {
+    if !game_state.profile.settings.ui_sounds {
+        return;
+    }
+    if let SoundType::UiTick = sound_type {
+        if game_state
+            .ui
+            .last_ui_tick
+            .is_some_and(|last| last.elapsed() < UI_TICK_INTERVAL)
+        {
+            return;
+        }
+        game_state.ui.last_ui_tick = Some(Instant::now());
+    }
+    let played = play_sound(sound_type, &game_state.ui.sounds, sink);
+    report(game_state, played);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game started with `args` and no save: the data directory is kept
+    /// apart from the player's, and saving is then turned off, so no test
+    /// sees another's records.
+    pub(crate) fn game(args: &[&str]) -> GameState {
+        let dir = std::env::temp_dir().join("sokoban_rust-tests");
+        std::env::set_var("XDG_DATA_HOME", &dir);
+        std::env::set_var("APPDATA", &dir);
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args.insert(0, "sokoban_rust".to_string());
+        let mut game_state = new_game(args);
+        game_state.profile.save = Save {
+            path: None,
+            read_only: true,
+            lock: None,
+        };
+        game_state
+    }
+
+    /// A sink nobody hears. Its queue is drained the way a device would, or
+    /// clearing it would wait for ever.
+    fn sink() -> Sink {
+        let (sink, queue) = Sink::new_idle();
+        std::thread::spawn(move || queue.for_each(drop));
+        sink
+    }
+
+    /// The app around `game_state`, playing silently.
+    fn app(game_state: GameState) -> App {
+        App {
+            game_state,
+            sink: sink(),
+            _audio: Box::new(()),
+            last_redraw: Instant::now(),
+            perf: RefCell::new(Perf::default()),
+        }
+    }
+
+    /// Runs `commands` the way `App::run` does.
+    fn play(game_state: &mut GameState, sink: &Sink, commands: Vec<Command>) {
+        for command in commands {
+            do_action(game_state, command, sink);
+            finish_if_solved(game_state, sink);
+        }
+    }
+
+    fn moves(directions: &str) -> Vec<Command> {
+        moves_from_lurd(directions)
+            .unwrap()
+            .into_iter()
+            .map(Command::Move)
+            .collect()
+    }
+
+    fn board(game_state: &GameState) -> Snapshot {
+        (
+            game_state.attempt.grid.clone(),
+            game_state.attempt.player_position,
+        )
+    }
+
+    #[test]
+    fn undo_takes_a_push_back() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::Two)],
+        );
+        // Down to the row of the box and along it, up to the box.
+        play(&mut game_state, &sink, moves("rdddrrrr"));
+        let before = board(&game_state);
+        play(&mut game_state, &sink, moves("r"));
+        assert_eq!(cell_at(&game_state.attempt.grid, Pos::new(8, 7)), Tile::Box);
+        play(&mut game_state, &sink, vec![Command::ReverseMove]);
+        assert_eq!(board(&game_state), before);
+        assert_eq!(cell_at(&game_state.attempt.grid, Pos::new(7, 7)), Tile::Box);
+        assert_eq!(game_state.attempt.player_position, Pos::new(6, 7));
+    }
+
+    #[test]
+    fn undo_takes_a_push_off_a_goal_back() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::One)],
+        );
+        let start = board(&game_state);
+        // Level one is solved by a single push, so only the move is made
+        // here and not the win.
+        do_action(&mut game_state, Command::Move(MoveDirection::Left), &sink);
+        assert_eq!(
+            cell_at(&game_state.attempt.grid, Pos::new(1, 2)),
+            Tile::BoxOnGoal
+        );
+        play(&mut game_state, &sink, vec![Command::ReverseMove]);
+        assert_eq!(board(&game_state), start);
+        assert_eq!(
+            cell_at(&game_state.attempt.grid, Pos::new(1, 2)),
+            Tile::Goal
+        );
+    }
+
+    #[test]
+    fn as_many_undos_as_moves_give_the_start_back() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::Two)],
+        );
+        let start = board(&game_state);
+        for walk in ["rdddrrrrr", "rdddrrrrulllurr", "urrrrrrl", "rdddrrrrurrdl"] {
+            play(&mut game_state, &sink, moves(walk));
+            let made = game_state.attempt.moves.len();
+            for _ in 0..made {
+                play(&mut game_state, &sink, vec![Command::ReverseMove]);
+            }
+            assert_eq!(board(&game_state), start, "after {}", walk);
+        }
+    }
+
+    #[test]
+    fn undo_leaves_the_recorded_moves_alone() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::One)],
+        );
+        play(&mut game_state, &sink, moves("u"));
+        assert_eq!(game_state.attempt.move_count, 1);
+        play(&mut game_state, &sink, vec![Command::ReverseMove]);
+        assert_eq!(game_state.attempt.move_count, 0);
+        play(&mut game_state, &sink, moves("d"));
+        assert_eq!(game_state.attempt.move_count, 1);
+        // Back where it started, then the push that solves the level.
+        play(&mut game_state, &sink, moves("ul"));
+        assert!(game_state.attempt.level.is_none());
+        let id = level_id(&game_state, Level::One).unwrap();
+        assert_eq!(game_state.profile.scores.get(&id), Some(&3));
+        assert!(game_state
+            .ui
+            .screen
+            .iter()
+            .any(|line| line.to_string() == "Moves: 3"));
+    }
+
+    /// A save as the release writing `version` made it, from `tests/saves`.
+    /// Each holds what the one before it does, and the lines its version
+    /// added.
+    fn fixture(version: usize) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/saves")
+            .join(format!("v{}.txt", version))
+    }
+
+    #[test]
+    fn every_save_version_loads() {
+        let one = LevelId::BuiltIn(Level::One);
+        let pack = LevelId::Pack {
+            file: "/home/player/levels/my pack.xsb".to_string(),
+            index: 2,
+        };
+        for version in 1..=SAVE_VERSION {
+            let (read, lines) = read_save(&fixture(version)).unwrap();
+            assert_eq!(read, version);
+            let mut game_state = game(&[]);
+            merge_progress(&mut game_state, &lines);
+            let profile = &game_state.profile;
+            let has = |since: usize| version >= since;
+            assert_eq!(profile.scores.get(&one), Some(&1), "version {}", version);
+            assert_eq!(profile.best_runs.get(&one).map(Vec::len), Some(1));
+            assert!(profile.first_tries.contains(&one));
+            assert_eq!(profile.pristine_completions, 1);
+            assert_eq!(profile.lifetime_wall_bumps, 3);
+            assert_eq!(profile.push_records.contains_key(&one), has(2));
+            assert_eq!(
+                profile.heat.get(&one).map(|heat| heat.attempts),
+                has(3).then_some(2)
+            );
+            assert_eq!(profile.clean_solves.contains(&one), has(4));
+            assert_eq!(profile.arcade_scores.len(), usize::from(has(5)));
+            assert_eq!(profile.last_played.contains_key(BUILT_IN), has(6));
+            assert_eq!(profile.views.len(), usize::from(has(7)));
+            assert_eq!(
+                profile
+                    .variant_records
+                    .get(&(one.clone(), Variant::MirrorH)),
+                has(8).then_some(&(1, 1))
+            );
+            assert_eq!(profile.scores.get(&pack), has(9).then_some(&40));
+            assert_eq!(profile.dailies.contains_key("2026-10-14"), has(10));
+            assert_eq!(profile.used_undo, has(11));
+            assert_eq!(
+                profile.solved_at.get(&one),
+                has(12).then_some(&1760400000000)
+            );
+            assert_eq!(
+                profile.heat.get(&pack).map(|heat| heat.attempts),
+                has(13).then_some(4)
+            );
+        }
+    }
+
+    #[test]
+    fn a_migrated_save_is_written_back_whole() {
+        let (_, lines) = read_save(&fixture(SAVE_VERSION)).unwrap();
+        let mut game_state = game(&[]);
+        merge_progress(&mut game_state, &lines);
+        game_state.profile.last_version = None;
+        let text = save_text(&game_state);
+        assert_eq!(
+            text.lines().next(),
+            Some(format!("version {}", SAVE_VERSION).as_str())
+        );
+        let mut written: Vec<&str> = text.lines().skip(1).collect();
+        let mut read: Vec<&str> = lines.iter().map(String::as_str).collect();
+        written.sort_unstable();
+        read.sort_unstable();
+        assert_eq!(written, read);
+    }
+
+    #[test]
+    fn attract_mode_waits_for_the_filter_prompt() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        let one = LevelId::BuiltIn(Level::One);
+        game_state
+            .profile
+            .best_runs
+            .insert(one, vec![MoveDirection::Left]);
+        game_state.profile.settings.attract_idle = Some(Duration::ZERO);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelChoose, Command::OpenFilter],
+        );
+        tick(&mut game_state, &sink);
+        assert!(game_state.ui.attract.is_none());
+        game_state.ui.prompt = None;
+        tick(&mut game_state, &sink);
+        assert!(game_state.ui.attract.is_some());
+    }
+
+    #[test]
+    fn a_reset_taken_back_is_no_first_try() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::One)],
+        );
+        play(&mut game_state, &sink, moves("u"));
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::Reset, Command::UndoReset],
+        );
+        assert_eq!(game_state.attempt.moves.len(), 1);
+        assert!(!game_state.attempt.pristine);
+    }
+
+    #[test]
+    fn centring_the_camera_scrolls_the_player_into_view() {
+        let screen = |game_state: &GameState| {
+            let mut terminal =
+                ratatui::Terminal::new(ratatui::backend::TestBackend::new(6, 20)).unwrap();
+            terminal
+                .draw(|frame| draw(frame, frame.area(), game_state))
+                .unwrap();
+            screenshot(terminal.backend().buffer(), false)
+        };
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::Three)],
+        );
+        assert!(!screen(&game_state).contains('@'));
+        play(&mut game_state, &sink, vec![Command::CenterCamera]);
+        assert!(screen(&game_state).lines().any(|line| line == " $ @##"));
+        play(&mut game_state, &sink, vec![Command::Reset]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::Three)],
+        );
+        assert!(game_state.ui.camera.is_none());
+    }
+
+    #[test]
+    fn the_right_edge_of_level_two_can_be_walked() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::Two)],
+        );
+        assert!(game_state.attempt.grid.iter().all(|row| row.len() == 10));
+        play(&mut game_state, &sink, moves("rdrrrrrrddd"));
+        assert!(game_state.attempt.player_position == Pos::new(8, 7));
+        assert_eq!(game_state.attempt.wall_bumps, 1);
+    }
+
+    /// Plays `before` on Level One, then solves it, and says whether that
+    /// was a first try.
+    fn first_try_after(before: Vec<Command>) -> bool {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::One)],
+        );
+        play(&mut game_state, &sink, before);
+        play(&mut game_state, &sink, moves("l"));
+        assert!(game_state.attempt.level.is_none(), "Level One was solved");
+        game_state.profile.pristine_completions == 1
+    }
+
+    #[test]
+    fn a_solve_with_no_resets_or_undos_is_a_first_try() {
+        assert!(first_try_after(vec![]));
+        assert!(first_try_after(moves("ud")));
+    }
+
+    #[test]
+    fn a_reset_spoils_the_first_try() {
+        assert!(!first_try_after(vec![Command::Reset]));
+    }
+
+    #[test]
+    fn an_undo_spoils_the_first_try() {
+        let mut before = moves("u");
+        before.push(Command::ReverseMove);
+        assert!(!first_try_after(before));
+    }
+
+    #[test]
+    fn a_restart_at_spoils_the_first_try() {
+        let mut before = moves("ud");
+        before.push(Command::RestartAt {
+            moves: 1,
+            from_best: false,
+        });
+        before.extend(moves("d"));
+        assert!(!first_try_after(before));
+    }
+
+    #[test]
+    fn walking_into_a_wall_is_a_bump_but_a_blocked_push_is_not() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(
+            &mut game_state,
+            &sink,
+            vec![Command::LevelSelect(Level::One)],
+        );
+        // The Oof: the wall right of the player.
+        play(&mut game_state, &sink, moves("r"));
+        assert_eq!(game_state.attempt.wall_bumps, 1);
+        // The BarrelOof: the box pushed up against the top wall, then again.
+        play(&mut game_state, &sink, moves("dluu"));
+        assert!(game_state.attempt.blocked == Some(Blocked::Wall));
+        assert_eq!(game_state.attempt.wall_bumps, 1);
+        assert_eq!(game_state.profile.lifetime_wall_bumps, 1);
+    }
+
+    #[test]
+    fn folding_drops_case_and_accents() {
+        assert_eq!(
+            fold_text("Ça Été Über"),
+            "ca ete uber".chars().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            fold_text("SØKOBAN Ñ"),
+            "sokoban n".chars().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn fuzzy_matching_ignores_accents_and_case() {
+        assert!(fuzzy_score("cafe", "Le Café Noir").is_some());
+        assert!(fuzzy_score("CAFÉ", "le cafe noir").is_some());
+        assert!(fuzzy_score("Über", "uber alles").is_some());
+        assert_eq!(
+            fuzzy_score("café", "Le Café"),
+            fuzzy_score("CAFE", "le cafe")
+        );
+    }
+
+    #[test]
+    fn fuzzy_matching_wants_the_characters_in_order() {
+        assert!(fuzzy_score("lvl", "Level Two").is_some());
+        assert!(fuzzy_score("owt", "Level Two").is_none());
+        assert!(fuzzy_score("x", "Level Two").is_none());
+        assert_eq!(fuzzy_score("", "Level Two"), Some(0));
+        assert_eq!(
+            fuzzy_score("l t", "Level Two"),
+            fuzzy_score("lt", "Level Two")
+        );
+    }
+
+    #[test]
+    fn runs_and_word_starts_score_higher() {
+        let run = fuzzy_score("two", "Level Two").unwrap();
+        let scattered = fuzzy_score("evo", "Level Two").unwrap();
+        assert!(run > scattered);
+        let start = fuzzy_score("t", "Level Two").unwrap();
+        let middle = fuzzy_score("v", "Level Two").unwrap();
+        assert!(start > middle);
+    }
+
+    /// The boards of an attempt at `rows`, in XSB, after the moves in
+    /// `lurd`.
+    fn frames_of(rows: &[&str], lurd: &str) -> Vec<Snapshot> {
+        let grid: Vec<Vec<Tile>> = rows
+            .iter()
+            .map(|row| row.chars().map(|c| Tile::try_from(c).unwrap()).collect())
+            .collect();
+        let y = rows.iter().position(|row| row.contains('@')).unwrap();
+        let x = rows[y].find('@').unwrap();
+        let mut attempt = Attempt::start(Level::File, (grid, Pos::new(x as i32, y as i32)));
+        attempt.moves = moves_from_lurd(lurd).unwrap();
+        attempt.frames()
+    }
+
+    const ROOM: [&str; 5] = ["#######", "#     #", "#@$ . #", "#     #", "#######"];
+
+    #[test]
+    fn undoing_a_push_takes_back_one_of_several_in_a_row() {
+        let frames = frames_of(&ROOM, "rr");
+        assert_eq!(undo_target(&frames, UndoStep::Push), 1);
+        assert_eq!(undo_target(&frames[..=1], UndoStep::Push), 0);
+        assert_eq!(undo_target(&frames, UndoStep::Move), 1);
+    }
+
+    #[test]
+    fn undoing_a_push_takes_the_moves_after_it_back_too() {
+        let frames = frames_of(&ROOM, "rrudu");
+        assert_eq!(undo_target(&frames, UndoStep::Push), 1);
+        assert_eq!(undo_target(&frames, UndoStep::Move), 4);
+    }
+
+    #[test]
+    fn undoing_a_push_with_none_made_goes_back_to_the_start() {
+        let frames = frames_of(&ROOM, "udud");
+        assert_eq!(undo_target(&frames, UndoStep::Push), 0);
+        assert_eq!(undo_target(&frames, UndoStep::Box), 0);
+        assert_eq!(undo_target(&frames, UndoStep::Move), 3);
+    }
+
+    #[test]
+    fn undoing_to_a_box_goes_back_to_the_last_box_placed() {
+        let frames = frames_of(&ROOM, "rrrud");
+        assert_eq!(undo_target(&frames, UndoStep::Box), 2);
+        assert_eq!(undo_target(&frames[..=2], UndoStep::Box), 0);
+    }
+
+    /// Saves `rows` as the board being played with `save_level`, and gives
+    /// back what the file holds and the board and title it reads as.
+    fn save_and_read_back(name: &str, rows: &[&str]) -> (String, Vec<Vec<Tile>>, Option<String>) {
+        let dir = std::env::temp_dir().join(format!("sokoban_rust-tests-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        let mut game_state = game(&[]);
+        game_state.profile.settings.levels_dir = Some(dir.clone());
+        game_state.attempt.grid = rows
+            .iter()
+            .map(|row| row.chars().map(|c| Tile::try_from(c).unwrap()).collect())
+            .collect();
+        save_level(
+            &mut game_state,
+            name.to_string(),
+            "Round trip".to_string(),
+            false,
+        );
+        assert_eq!(
+            game_state.ui.message.as_deref(),
+            Some(format!("Saved as {}.xsb, listed on level select.", name).as_str())
+        );
+        let text = fs::read_to_string(dir.join(format!("{}.xsb", name))).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        let collection = parse_collection(name.to_string(), &text).ok().unwrap();
+        let (grid, info) = collection.boards[0].clone();
+        (text, grid, info.title)
+    }
+
+    #[test]
+    fn a_saved_level_reads_back_tile_for_tile() {
+        let rows = ["#######", "#@$. *#", "#######"];
+        let (text, grid, title) = save_and_read_back("every-tile", &rows);
+        assert_eq!(text, "#######\n#@$. *#\n#######\nTitle: Round trip\n");
+        assert!(grid.iter().map(|row| row.len()).eq([7, 7, 7]));
+        assert_eq!(
+            grid.iter()
+                .flatten()
+                .map(|tile| char::from(*tile))
+                .collect::<String>(),
+            rows.concat()
+        );
+        assert_eq!(title.as_deref(), Some("Round trip"));
+    }
+
+    #[test]
+    fn a_saved_level_keeps_a_player_on_a_goal() {
+        let rows = ["######", "#+$*$#", "#  . #", "######"];
+        let (_, grid, _) = save_and_read_back("player-on-goal", &rows);
+        assert!(grid[1][1] == Tile::PlayerOnGoal);
+        assert!(grid[1][3] == Tile::BoxOnGoal);
+        assert_eq!(
+            grid.iter()
+                .flatten()
+                .map(|tile| char::from(*tile))
+                .collect::<String>(),
+            rows.concat()
+        );
+    }
+
+    /// A copy of the game saving to `path`, holding the lock if it is the
+    /// first to ask.
+    fn writer(path: &Path) -> GameState {
+        let mut game_state = game(&[]);
+        game_state.profile.save = Save {
+            path: Some(path.to_path_buf()),
+            read_only: false,
+            lock: lock_save(path),
+        };
+        game_state
+    }
+
+    #[test]
+    fn two_copies_saving_in_turn_keep_the_best_of_both() {
+        let dir = std::env::temp_dir().join("sokoban_rust-tests-two-writers");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("scores.txt");
+        let one = LevelId::BuiltIn(Level::One);
+        let two = LevelId::BuiltIn(Level::Two);
+
+        let mut first = writer(&path);
+        assert!(first.profile.save.lock.is_some());
+        first.profile.scores.insert(one.clone(), 12);
+        first
+            .profile
+            .best_runs
+            .insert(one.clone(), moves_from_lurd("udl").unwrap());
+        first.profile.lifetime_wall_bumps = 5;
+        write_progress(&mut first).ok().unwrap();
+
+        let mut second = writer(&path);
+        assert!(second.profile.save.lock.is_none());
+        second.profile.scores.insert(one.clone(), 9);
+        second
+            .profile
+            .best_runs
+            .insert(one.clone(), moves_from_lurd("l").unwrap());
+        second.profile.scores.insert(two.clone(), 20);
+        second.profile.lifetime_wall_bumps = 2;
+        write_progress(&mut second).ok().unwrap();
+        assert!(path.with_extension("pending").exists());
+
+        first.profile.scores.insert(two.clone(), 15);
+        write_progress(&mut first).ok().unwrap();
+        assert!(!path.with_extension("pending").exists());
+
+        let (version, lines) = read_save(&path).unwrap();
+        assert_eq!(version, SAVE_VERSION);
+        let mut fresh = game(&[]);
+        merge_progress(&mut fresh, &lines);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(fresh.profile.scores.get(&one), Some(&9));
+        assert_eq!(fresh.profile.scores.get(&two), Some(&15));
+        assert_eq!(
+            fresh.profile.best_runs.get(&one).map(|run| lurd(run)),
+            Some("l".to_string())
+        );
+        assert_eq!(fresh.profile.lifetime_wall_bumps, 5);
+    }
+
+    #[test]
+    fn status_json_lists_the_built_in_levels_then_packs() {
+        let mut game_state = game(&[]);
+        let profile = &mut game_state.profile;
+        let one = LevelId::BuiltIn(Level::One);
+        let pack = LevelId::Pack {
+            file: "/home/player/levels/microban.xsb".to_string(),
+            index: 2,
+        };
+        profile.scores.insert(one.clone(), 31);
+        profile.push_records.insert(one.clone(), 9);
+        profile.first_tries.insert(one.clone());
+        profile.solved_at.insert(one, 1759990000123);
+        profile.scores.insert(pack.clone(), 50);
+        profile.push_records.insert(pack.clone(), 12);
+        profile.solved_at.insert(pack, 1759000000000);
+        profile.lifetime_wall_bumps = 42;
+        let json = status_json(&game_state);
+        let mut lines: Vec<&str> = json.lines().collect();
+        assert!(lines[2].starts_with("  \"updated_at\": "));
+        lines[2] = "  \"updated_at\": 0,";
+        let level = |number: usize, name: &str, rest: &str| {
+            format!(
+                "        {{ \"level\": {}, \"name\": {}, {} }}",
+                number, name, rest
+            )
+        };
+        let unsolved = "\"best_moves\": null, \"best_pushes\": null, \"first_try\": false, \"solved_at\": null";
+        let expected = [
+            "{".to_string(),
+            "  \"version\": 1,".to_string(),
+            "  \"updated_at\": 0,".to_string(),
+            "  \"wall_bumps\": 42,".to_string(),
+            "  \"packs\": [".to_string(),
+            "    {".to_string(),
+            "      \"name\": \"Built-in\",".to_string(),
+            "      \"file\": null,".to_string(),
+            "      \"levels\": 5,".to_string(),
+            "      \"solved\": 1,".to_string(),
+            "      \"first_tries\": 1,".to_string(),
+            "      \"level_stats\": [".to_string(),
+            level(
+                1,
+                "\"Tutorial\"",
+                "\"best_moves\": 31, \"best_pushes\": 9, \"first_try\": true, \"solved_at\": 1759990000",
+            ) + ",",
+            level(2, "\"Easy\"", unsolved) + ",",
+            level(3, "\"Medium\"", unsolved) + ",",
+            level(4, "\"Hard\"", unsolved) + ",",
+            // Level Five is not on level select, so it has no name.
+            level(5, "null", unsolved),
+            "      ]".to_string(),
+            "    },".to_string(),
+            "    {".to_string(),
+            "      \"name\": \"microban\",".to_string(),
+            "      \"file\": \"/home/player/levels/microban.xsb\",".to_string(),
+            "      \"levels\": null,".to_string(),
+            "      \"solved\": 1,".to_string(),
+            "      \"first_tries\": 0,".to_string(),
+            "      \"level_stats\": [".to_string(),
+            level(
+                3,
+                "null",
+                "\"best_moves\": 50, \"best_pushes\": 12, \"first_try\": false, \"solved_at\": 1759000000",
+            ),
+            "      ]".to_string(),
+            "    }".to_string(),
+            "  ]".to_string(),
+            "}".to_string(),
+        ];
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn the_editor_has_a_key_of_its_own() {
+        let sink = sink();
+        let mut game_state = game(&[]);
+        play(&mut game_state, &sink, vec![Command::LevelChoose]);
+        assert!(game_state.ui.menu_cursor.is_some());
+        play(&mut game_state, &sink, vec![Command::ExportMoves]);
+        assert!(game_state.ui.editor.is_none());
+        let keys = game_state.ui.keymap.hint(&Command::OpenEditor);
+        assert_eq!(keys, "[D]");
+        assert!(game_state
+            .ui
+            .screen
+            .iter()
+            .any(|line| line.to_string() == "Press [D] to draw a level of your own."));
+        play(&mut game_state, &sink, vec![Command::OpenEditor]);
+        assert!(game_state.ui.editor.is_some());
+    }
+
+    #[test]
+    fn shutdown_stops_the_threads_and_hands_the_terminal_back() {
+        let mut game_state = game(&["--check-solvable"]);
+        // Ratings kept by earlier runs would leave nothing to rate.
+        game_state.ui.ratings.known.clear();
+        request_ratings(&mut game_state);
+        start_level(&mut game_state, Level::Two);
+        check_solvable(&mut game_state);
+        assert!(game_state.ui.ratings.worker.is_some());
+        assert!(game_state.ui.solvability.worker.is_some());
+        let mut app = app(game_state);
+        let mut restored = false;
+        let started = Instant::now();
+        assert!(app.shutdown(|| restored = true).is_ok());
+        assert!(restored);
+        assert!(started.elapsed() < SHUTDOWN_TIMEOUT);
+        assert!(app.game_state.ui.ratings.worker.is_none());
+        assert!(app.game_state.ui.solvability.worker.is_none());
+    }
+
+    #[test]
+    fn each_board_of_a_pack_keeps_its_own_heat() {
+        let dir = std::env::temp_dir().join("sokoban_rust-tests-heat");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("two.xsb");
+        fs::write(&path, "#####\n#@$.#\n#####\n\n######\n#@$ .#\n######\n").unwrap();
+        let mut game_state = game(&[path.to_str().unwrap()]);
+        let attempts = |game_state: &GameState, index| {
+            let file = fs::canonicalize(&path).unwrap();
+            let id = LevelId::Pack {
+                file: file.to_string_lossy().into_owned(),
+                index,
+            };
+            game_state.profile.heat.get(&id).map(|heat| heat.attempts)
+        };
+        play_board(&mut game_state, 0);
+        record_death(&mut game_state, Level::File);
+        play_board(&mut game_state, 1);
+        record_death(&mut game_state, Level::File);
+        play_board(&mut game_state, 0);
+        assert_eq!(attempts(&game_state, 0), Some(1));
+        assert_eq!(attempts(&game_state, 1), Some(1));
+        let text = save_text(&game_state);
+        let _ = fs::remove_dir_all(&dir);
+        let heat = text.lines().filter(|line| line.starts_with("heat pack:"));
+        assert_eq!(heat.count(), 2);
+    }
+
+    #[test]
+    fn each_tip_is_earned_at_its_number_of_failures() {
+        let offered_at = |failures: u32| {
+            let mut nudges = Nudges::default();
+            (0..failures).map(|_| nudges.failed(1)).last().flatten()
+        };
+        assert_eq!(offered_at(NUDGE_AFTER - 1), None);
+        assert_eq!(offered_at(NUDGE_AFTER), Some(Nudge::Undo));
+        assert_eq!(offered_at(NUDGE_AFTER + NUDGE_STEP - 1), Some(Nudge::Undo));
+        assert_eq!(offered_at(NUDGE_AFTER + NUDGE_STEP), Some(Nudge::Reachable));
+        assert_eq!(
+            offered_at(NUDGE_AFTER + 2 * NUDGE_STEP - 1),
+            Some(Nudge::Reachable)
+        );
+        assert_eq!(
+            offered_at(NUDGE_AFTER + 2 * NUDGE_STEP),
+            Some(Nudge::SolverHint)
+        );
+        assert_eq!(
+            offered_at(NUDGE_AFTER + 5 * NUDGE_STEP),
+            Some(Nudge::SolverHint)
+        );
+    }
+
+    #[test]
+    fn declined_tips_stay_off_for_every_level() {
+        let mut nudges = Nudges::default();
+        for _ in 0..NUDGE_AFTER + 2 * NUDGE_STEP {
+            nudges.failed(1);
+        }
+        assert_eq!(nudges.decline(), Some(Nudge::SolverHint));
+        assert_eq!(nudges.failed(1), Some(Nudge::Reachable));
+        assert_eq!(nudges.decline(), Some(Nudge::Reachable));
+        assert_eq!(nudges.failed(1), Some(Nudge::Undo));
+        assert_eq!(nudges.decline(), Some(Nudge::Undo));
+        assert_eq!(nudges.failed(1), None);
+        assert_eq!(nudges.decline(), None);
+        for _ in 0..NUDGE_AFTER + 2 * NUDGE_STEP {
+            assert_eq!(nudges.failed(2), None);
+        }
+    }
+
+    #[test]
+    fn the_strongest_tip_names_the_solvers_first_push() {
+        let mut game_state = game(&[]);
+        let start = frames_of(&ROOM, "").remove(0);
+        game_state.attempt = Attempt::start(Level::File, start);
+        let layout = game_state.attempt.layout_hash;
+        game_state
+            .ui
+            .nudges
+            .failures
+            .insert(layout, NUDGE_AFTER + 2 * NUDGE_STEP - 1);
+        offer_nudge(&mut game_state);
+        let message = game_state.ui.message.unwrap();
+        assert!(
+            message.starts_with("Hint: from the start, push the box on c3 right first."),
+            "{}",
+            message
+        );
+    }
+
+    /// Built-in levels One and Two and a board of a pack, rated 9, 3 and
+    /// not yet, all unlocked unless `locked`.
+    fn registry(locked: bool) -> (Vec<Candidate>, HashMap<u64, Option<u32>>) {
+        let candidate = |id, hash| Candidate {
+            id,
+            name: String::new(),
+            hash,
+            unlocked: !locked,
+        };
+        let pack = LevelId::Pack {
+            file: "/levels/pack.xsb".to_string(),
+            index: 0,
+        };
+        let registry = vec![
+            candidate(LevelId::BuiltIn(Level::One), 1),
+            candidate(LevelId::BuiltIn(Level::Two), 2),
+            candidate(pack, 3),
+        ];
+        (registry, HashMap::from([(1, Some(9)), (2, Some(3))]))
+    }
+
+    fn recommended(
+        registry: &[Candidate],
+        ratings: &HashMap<u64, Option<u32>>,
+        scores: &HashMap<LevelId, i32>,
+        push_records: &HashMap<LevelId, i32>,
+    ) -> Option<(LevelId, Option<i32>)> {
+        recommended_level(registry, ratings, scores, push_records)
+            .map(|(candidate, above)| (candidate.id.clone(), above))
+    }
+
+    #[test]
+    fn with_no_history_the_easiest_rated_level_comes_first() {
+        let (registry, ratings) = registry(false);
+        let none = HashMap::new();
+        assert_eq!(
+            recommended(&registry, &ratings, &none, &none),
+            Some((LevelId::BuiltIn(Level::Two), None))
+        );
+        assert_eq!(
+            recommended(&registry, &HashMap::new(), &none, &none),
+            Some((LevelId::BuiltIn(Level::One), None))
+        );
+        let solved = HashMap::from([
+            (LevelId::BuiltIn(Level::One), 20),
+            (LevelId::BuiltIn(Level::Two), 10),
+        ]);
+        assert_eq!(
+            recommended(&registry, &ratings, &solved, &none),
+            Some((registry[2].id.clone(), None))
+        );
+    }
+
+    #[test]
+    fn once_everything_is_solved_the_best_furthest_above_par_comes_next() {
+        let (registry, ratings) = registry(false);
+        let scores: HashMap<LevelId, i32> = registry.iter().map(|c| (c.id.clone(), 30)).collect();
+        let pushes = HashMap::from([
+            (LevelId::BuiltIn(Level::One), 12),
+            (LevelId::BuiltIn(Level::Two), 10),
+            (registry[2].id.clone(), 50),
+        ]);
+        assert_eq!(
+            recommended(&registry, &ratings, &scores, &pushes),
+            Some((LevelId::BuiltIn(Level::Two), Some(7)))
+        );
+        let at_par = HashMap::from([
+            (LevelId::BuiltIn(Level::One), 9),
+            (LevelId::BuiltIn(Level::Two), 3),
+        ]);
+        assert_eq!(recommended(&registry, &ratings, &scores, &at_par), None);
+    }
+
+    #[test]
+    fn locked_levels_are_never_recommended() {
+        let (registry, ratings) = registry(true);
+        let none = HashMap::new();
+        assert_eq!(recommended(&registry, &ratings, &none, &none), None);
+        let scores: HashMap<LevelId, i32> = registry.iter().map(|c| (c.id.clone(), 30)).collect();
+        let pushes: HashMap<LevelId, i32> = registry.iter().map(|c| (c.id.clone(), 99)).collect();
+        assert_eq!(recommended(&registry, &ratings, &scores, &pushes), None);
+    }
+
+    #[test]
+    fn each_kind_of_error_exits_with_its_own_code() {
+        let io = || std::io::Error::other("disk full");
+        let errors = [
+            (Error::Io(io()), 1),
+            (Error::Terminal(io()), 2),
+            (Error::Audio("no device".to_string()), 3),
+            (Error::LevelParse("no player".to_string()), 4),
+            (Error::Save(io()), 5),
+            (Error::Usage("bad flag".to_string()), 6),
+        ];
+        for (error, code) in errors {
+            assert_eq!(error.exit_code(), code, "{}", error);
+        }
+    }
+
+    fn room_game() -> GameState {
+        let mut game_state = game(&[]);
+        game_state.attempt = Attempt::start(Level::File, frames_of(&ROOM, "").remove(0));
+        game_state
+    }
+
+    #[test]
+    fn an_issue_draft_fences_the_board() {
+        let draft = issue_draft(&room_game(), "  ");
+        assert!(draft.starts_with("## (no description)\n"));
+        let lines: Vec<&str> = draft.lines().collect();
+        let board = lines.iter().position(|line| *line == "### Board").unwrap();
+        let mut fenced = vec!["", "```"];
+        fenced.extend(ROOM);
+        fenced.push("```");
+        assert_eq!(lines[board + 1..board + 1 + fenced.len()], fenced);
+    }
+
+    #[test]
+    fn an_issue_draft_keeps_the_end_of_a_long_history() {
+        let mut game_state = room_game();
+        game_state.attempt.moves = moves_from_lurd(&"ud".repeat(5)).unwrap();
+        let draft = issue_draft(&game_state, "Stuck");
+        assert!(draft.contains("### Moves\n\n`ududududud`\n"));
+        assert!(!draft.contains("Only the last"));
+        let history = format!("lr{}", "ud".repeat(ISSUE_MOVES / 2));
+        game_state.attempt.moves = moves_from_lurd(&history).unwrap();
+        let draft = issue_draft(&game_state, "Stuck");
+        let note = format!(
+            "Only the last {} of {} moves are shown.\n\n`{}`\n",
+            ISSUE_MOVES,
+            ISSUE_MOVES + 2,
+            &history[2..]
+        );
+        assert!(draft.contains(&note), "{}", draft);
+    }
+
+    #[test]
+    fn a_moves_csv_follows_each_box_as_it_is_pushed() {
+        let rows = ["########", "#@$  . #", "#      #", "#  $ . #", "########"];
+        let lurd = "rrllddrruur";
+        let frames = frames_of(&rows, lurd);
+        let times: Vec<Duration> = (1..=lurd.len() as u64)
+            .map(|idx| Duration::from_millis(idx * 100))
+            .collect();
+        let csv = moves_csv(&frames, &moves_from_lurd(lurd).unwrap(), &times);
+        assert_eq!(
+            csv,
+            "move,direction,push,box,boxes_on_goals,elapsed_ms\n\
+             1,right,true,1,0,100\n\
+             2,right,true,1,0,100\n\
+             3,left,false,,0,100\n\
+             4,left,false,,0,100\n\
+             5,down,false,,0,100\n\
+             6,down,false,,0,100\n\
+             7,right,false,,0,100\n\
+             8,right,true,2,0,100\n\
+             9,up,false,,0,100\n\
+             10,up,false,,0,100\n\
+             11,right,true,1,1,100\n"
+        );
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_they_need_it() {
+        assert_eq!(csv_field("right"), "right");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("left, then up"), "\"left, then up\"");
+        assert_eq!(csv_field("the \"hard\" one"), "\"the \"\"hard\"\" one\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("\r"), "\"\r\"");
+    }
+
+    /// An arcade run on its first level, its clock started.
+    fn arcade_game() -> GameState {
+        let mut game_state = game(&["--arcade"]);
+        game_state.attempt = Attempt::start(Level::File, frames_of(&ROOM, "").remove(0));
+        game_state.ui.screen.clear();
+        step_arcade(&mut game_state);
+        assert!(game_state.ui.arcade.is_some());
+        game_state
+    }
+
+    fn spent(game_state: &GameState) -> Duration {
+        game_state.ui.arcade.as_ref().unwrap().spent
+    }
+
+    #[test]
+    fn a_screen_over_the_board_stops_the_arcade_clock() {
+        let mut game_state = arcade_game();
+        std::thread::sleep(Duration::from_millis(10));
+        step_arcade(&mut game_state);
+        let before = spent(&game_state);
+        assert!(before >= Duration::from_millis(10));
+        game_state.ui.screen = vec![Line::from("Level select")];
+        step_arcade(&mut game_state);
+        std::thread::sleep(Duration::from_millis(50));
+        step_arcade(&mut game_state);
+        assert_eq!(spent(&game_state), before);
+        game_state.ui.screen.clear();
+        step_arcade(&mut game_state);
+        assert_eq!(spent(&game_state), before);
+        step_arcade(&mut game_state);
+        assert!(spent(&game_state) < before + Duration::from_millis(50));
+    }
+
+    #[test]
+    fn losing_focus_stops_the_arcade_clock() {
+        let mut app = app(arcade_game());
+        std::thread::sleep(Duration::from_millis(10));
+        step_arcade(&mut app.game_state);
+        let before = spent(&app.game_state);
+        assert!(app.handle_event(Event::FocusLost) == Update::Skip);
+        step_arcade(&mut app.game_state);
+        std::thread::sleep(Duration::from_millis(50));
+        step_arcade(&mut app.game_state);
+        assert_eq!(spent(&app.game_state), before);
+        app.handle_event(Event::FocusGained);
+        step_arcade(&mut app.game_state);
+        assert_eq!(spent(&app.game_state), before);
+        step_arcade(&mut app.game_state);
+        assert!(spent(&app.game_state) < before + Duration::from_millis(50));
+    }
+
+    #[test]
+    fn kids_mode_lists_only_built_in_levels_with_few_boxes() {
+        for level in LEVELS {
+            let boxes = level_layout(level)
+                .iter()
+                .flatten()
+                .filter(|cell| cell.is_box())
+                .count();
+            assert_eq!(kid_sized(level), boxes <= KIDS_MAX_BOXES, "{:?}", level);
+        }
+        assert!(LEVELS.iter().any(|level| kid_sized(*level)));
+        assert!(LEVELS.iter().any(|level| !kid_sized(*level)));
+        for level in [Level::Random, Level::Daily, Level::File] {
+            assert!(!kid_sized(level));
+        }
+    }
+
+    #[test]
+    fn quitting_kids_mode_must_be_held_down() {
+        let mut game_state = game(&[]);
+        assert!(!held(&mut game_state, Command::Quit));
+        let message = game_state.ui.message.clone().unwrap();
+        assert!(message.ends_with("to quit: [----------]"), "{}", message);
+        let hold = game_state.ui.hold.as_mut().unwrap();
+        hold.started -= KIDS_HOLD / 2;
+        assert!(!held(&mut game_state, Command::Quit));
+        let message = game_state.ui.message.clone().unwrap();
+        assert!(message.ends_with("to quit: [#####-----]"), "{}", message);
+        // Letting go, or holding another key, starts over.
+        game_state.ui.hold.as_mut().unwrap().last -= KIDS_HOLD_GAP * 2;
+        assert!(!held(&mut game_state, Command::Quit));
+        assert!(game_state
+            .ui
+            .message
+            .clone()
+            .unwrap()
+            .ends_with("[----------]"));
+        game_state.ui.hold.as_mut().unwrap().started -= KIDS_HOLD / 2;
+        assert!(!held(&mut game_state, Command::ToggleKids));
+        let message = game_state.ui.message.clone().unwrap();
+        assert!(
+            message.ends_with("to leave kids mode: [----------]"),
+            "{}",
+            message
+        );
+        game_state.ui.hold.as_mut().unwrap().started -= KIDS_HOLD;
+        assert!(held(&mut game_state, Command::ToggleKids));
+    }
+
+    #[test]
+    fn kids_mode_keeps_its_own_save() {
+        let dir = std::env::temp_dir().join("sokoban_rust-tests-kids");
+        let _ = fs::remove_dir_all(&dir);
+        let one = LevelId::BuiltIn(Level::One);
+        let mut game_state = game(&[]);
+        let settings = game_state.profile.settings.clone();
+        game_state.profile = Profile::saved_in(settings, Some(dir.clone()));
+        game_state.profile.scores.insert(one.clone(), 7);
+        switch_kids_mode(&mut game_state, true, Some(dir.clone()));
+        assert!(game_state.profile.settings.kids);
+        assert_eq!(game_state.profile.save.path, Some(dir.join("kids.txt")));
+        assert!(game_state.profile.scores.is_empty());
+        assert!(kids_marker(&dir).exists());
+        assert!(fs::read_to_string(dir.join("scores.txt"))
+            .unwrap()
+            .contains("record One 7\n"));
+        game_state.profile.scores.insert(one.clone(), 3);
+        switch_kids_mode(&mut game_state, false, Some(dir.clone()));
+        assert!(!game_state.profile.settings.kids);
+        assert_eq!(game_state.profile.save.path, Some(dir.join("scores.txt")));
+        assert_eq!(game_state.profile.scores.get(&one), Some(&7));
+        assert!(!kids_marker(&dir).exists());
+        assert!(fs::read_to_string(dir.join("kids.txt"))
+            .unwrap()
+            .contains("record One 3\n"));
+        switch_kids_mode(&mut game_state, true, Some(dir.clone()));
+        assert_eq!(game_state.profile.scores.get(&one), Some(&3));
+        game_state.profile.save.lock = None;
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn undone_moves_leave_the_longest_run() {
+        let mut game_state = room_game();
+        let sink = sink();
+        play(&mut game_state, &sink, moves("urrrr"));
+        assert_eq!(
+            longest_run(&game_state.attempt.moves),
+            Some((&MoveDirection::Right, 4))
+        );
+        let mut commands = vec![Command::ReverseMove; 3];
+        commands.extend(moves("ldd"));
+        play(&mut game_state, &sink, commands);
+        assert_eq!(lurd(&game_state.attempt.moves), "urldd");
+        assert_eq!(
+            longest_run(&game_state.attempt.moves),
+            Some((&MoveDirection::Down, 2))
+        );
+        assert_eq!(longest_run(&[]), None);
+    }
+
+    #[test]
+    fn an_undo_ends_the_gap_since_the_last_move() {
+        let mut game_state = room_game();
+        let sink = sink();
+        play(&mut game_state, &sink, moves("u"));
+        let pause = game_state.attempt.trivia.longest_pause;
+        assert!(pause < Duration::from_secs(1));
+        let thought = |game_state: &mut GameState, seconds| {
+            let last = &mut game_state.attempt.trivia.last_move;
+            *last = last.map(|last| last - Duration::from_secs(seconds));
+        };
+        thought(&mut game_state, 2);
+        play(&mut game_state, &sink, vec![Command::ReverseMove]);
+        let pause = game_state.attempt.trivia.longest_pause;
+        assert!(pause >= Duration::from_secs(2) && pause < Duration::from_secs(3));
+        // The move right after the undo starts a fresh gap.
+        play(&mut game_state, &sink, moves("u"));
+        assert_eq!(game_state.attempt.trivia.longest_pause, pause);
+        thought(&mut game_state, 5);
+        play(&mut game_state, &sink, moves("r"));
+        assert!(game_state.attempt.trivia.longest_pause >= Duration::from_secs(5));
+    }
+}
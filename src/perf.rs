@@ -0,0 +1,195 @@
+//! Timings of the last commands the game ran, split into reading the input,
+//! changing the game, queueing sounds and drawing the frame, for
+//! `--perf-report`. Without the `perf` feature nothing is timed and every
+//! call here does nothing.
+
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Whether the game was built to time itself.
+pub const ENABLED: bool = cfg!(feature = "perf");
+/// How many commands the timings are kept for.
+const SAMPLES: usize = 1000;
+
+thread_local! {
+    /// Time spent queueing sounds since it was last taken. Kept here so
+    /// that the audio code need not hand timings back to its callers.
+    static AUDIO: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// Where the time of one command, or of the commands of one event, went.
+#[derive(Clone, Copy, Default)]
+pub struct Sample {
+    pub input: Duration,
+    /// Running the commands, less the time spent on sound.
+    pub engine: Duration,
+    pub audio: Duration,
+    /// Drawing the frame that followed, not counting the terminal's output.
+    pub render: Duration,
+}
+
+#[derive(Default)]
+pub struct Perf {
+    samples: VecDeque<Sample>,
+    /// The commands last run, waiting for the frame drawn after them.
+    pending: Option<Sample>,
+}
+
+impl Perf {
+    /// Counts commands run after `input` spent reading them and `engine`
+    /// running them, sound included, until the next frame is drawn.
+    pub fn ran(&mut self, input: Duration, engine: Duration) {
+        if !ENABLED {
+            return;
+        }
+        let audio = take_audio();
+        self.pending = Some(Sample {
+            input,
+            engine: engine.saturating_sub(audio),
+            audio,
+            render: Duration::ZERO,
+        });
+    }
+
+    /// Finishes the sample of the commands last run with the time `render`
+    /// took to draw their frame.
+    pub fn rendered(&mut self, render: Duration) {
+        let Some(sample) = self.pending.take() else {
+            return;
+        };
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample { render, ..sample });
+    }
+
+    pub fn report(&self) -> String {
+        report(&self.samples.iter().copied().collect::<Vec<_>>())
+    }
+}
+
+/// The moment to time from, or `None` when nothing is timed.
+pub fn start() -> Option<Instant> {
+    ENABLED.then(Instant::now)
+}
+
+pub fn elapsed(since: Option<Instant>) -> Duration {
+    since.map_or(Duration::ZERO, |since| since.elapsed())
+}
+
+/// Counts the time since `since` as spent on sound.
+pub fn audio(since: Option<Instant>) {
+    if let Some(since) = since {
+        AUDIO.with(|audio| audio.set(audio.get() + since.elapsed()));
+    }
+}
+
+/// The time spent on sound since this was last called.
+pub fn take_audio() -> Duration {
+    AUDIO.with(|audio| audio.take())
+}
+
+/// One of the parts a sample is split into.
+type Part = fn(&Sample) -> Duration;
+
+/// The median, 90th and 99th percentiles and worst of each part of the
+/// `samples`, in microseconds.
+pub fn report(samples: &[Sample]) -> String {
+    if samples.is_empty() {
+        return "No commands were timed.".to_string();
+    }
+    let mut lines = vec![
+        format!(
+            "Timings of the last {} commands, in microseconds:",
+            samples.len()
+        ),
+        format!("{:<8}{:>8}{:>8}{:>8}{:>8}", "", "p50", "p90", "p99", "max"),
+    ];
+    let parts: [(&str, Part); 4] = [
+        ("input", |sample| sample.input),
+        ("engine", |sample| sample.engine),
+        ("audio", |sample| sample.audio),
+        ("render", |sample| sample.render),
+    ];
+    for (name, part) in parts {
+        let mut times: Vec<u128> = samples
+            .iter()
+            .map(|sample| part(sample).as_micros())
+            .collect();
+        times.sort_unstable();
+        let at = |percent: usize| times[(times.len() * percent).div_ceil(100).max(1) - 1];
+        lines.push(format!(
+            "{:<8}{:>8}{:>8}{:>8}{:>8}",
+            name,
+            at(50),
+            at(90),
+            at(99),
+            times[times.len() - 1]
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples whose input took each of `micros`, and every other part
+    /// twice as long.
+    fn samples(micros: &[u64]) -> Vec<Sample> {
+        micros
+            .iter()
+            .map(|micros| {
+                let twice = Duration::from_micros(micros * 2);
+                Sample {
+                    input: Duration::from_micros(*micros),
+                    engine: twice,
+                    audio: twice,
+                    render: twice,
+                }
+            })
+            .collect()
+    }
+
+    /// The p50, p90, p99 and max columns of the `name` row.
+    fn row(report: &str, name: &str) -> Vec<u64> {
+        let line = report
+            .lines()
+            .find(|line| line.split_whitespace().next() == Some(name))
+            .unwrap();
+        line.split_whitespace()
+            .skip(1)
+            .map(|column| column.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn nothing_timed_says_so() {
+        assert_eq!(report(&[]), "No commands were timed.");
+    }
+
+    #[test]
+    fn one_sample_is_every_percentile() {
+        let report = report(&samples(&[7]));
+        assert!(report.starts_with("Timings of the last 1 commands, in microseconds:\n"));
+        assert_eq!(row(&report, "input"), [7, 7, 7, 7]);
+        for part in ["engine", "audio", "render"] {
+            assert_eq!(row(&report, part), [14, 14, 14, 14]);
+        }
+    }
+
+    #[test]
+    fn percentiles_take_the_nearest_rank() {
+        let at = |micros: &[u64]| row(&report(&samples(micros)), "input");
+        assert_eq!(at(&[3, 1]), [1, 3, 3, 3]);
+        assert_eq!(at(&[2, 3, 1]), [2, 3, 3, 3]);
+        assert_eq!(at(&[10, 9, 8, 7, 6, 5, 4, 3, 2, 1]), [5, 9, 10, 10]);
+        let hundred: Vec<u64> = (1..=100).collect();
+        assert_eq!(at(&hundred), [50, 90, 99, 100]);
+        let hundred_and_one: Vec<u64> = (1..=101).collect();
+        assert_eq!(at(&hundred_and_one), [51, 91, 100, 101]);
+    }
+}
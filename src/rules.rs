@@ -0,0 +1,152 @@
+//! What one step of the player does to the two cells in front of them, as a
+//! table. The game itself only adds the effects: sounds, counters and the
+//! move history, see `play_out`.
+
+use super::game::Tile;
+
+/// What a step comes to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// Into a wall, or off the board. The player stays put.
+    Bump,
+    Walk,
+    Push,
+    /// A box with a wall behind it. The player stays put.
+    BlockedByWall,
+    /// A box with another box behind it. The player stays put.
+    BlockedByBox,
+}
+
+/// One row of `TABLE`: the cell ahead of the player and the one beyond it,
+/// what the step comes to, and what the two cells hold afterwards.
+#[derive(Clone, Copy, Debug)]
+pub struct Rule {
+    pub ahead: Tile,
+    pub beyond: Tile,
+    pub outcome: Outcome,
+    pub ahead_after: Tile,
+    pub beyond_after: Tile,
+}
+
+const fn rule(
+    ahead: Tile,
+    beyond: Tile,
+    outcome: Outcome,
+    ahead_after: Tile,
+    beyond_after: Tile,
+) -> Rule {
+    Rule {
+        ahead,
+        beyond,
+        outcome,
+        ahead_after,
+        beyond_after,
+    }
+}
+
+use Outcome::*;
+use Tile::*;
+
+/// Every pair of cells the player can face, off the board counting as a wall.
+#[rustfmt::skip]
+pub const TABLE: [Rule; 25] = [
+    //   ahead         beyond        outcome        after
+    rule(Floor,        Floor,        Walk,          Player,       Floor),
+    rule(Floor,        Goal,         Walk,          Player,       Goal),
+    rule(Floor,        Box,          Walk,          Player,       Box),
+    rule(Floor,        BoxOnGoal,    Walk,          Player,       BoxOnGoal),
+    rule(Floor,        Wall,         Walk,          Player,       Wall),
+    rule(Goal,         Floor,        Walk,          PlayerOnGoal, Floor),
+    rule(Goal,         Goal,         Walk,          PlayerOnGoal, Goal),
+    rule(Goal,         Box,          Walk,          PlayerOnGoal, Box),
+    rule(Goal,         BoxOnGoal,    Walk,          PlayerOnGoal, BoxOnGoal),
+    rule(Goal,         Wall,         Walk,          PlayerOnGoal, Wall),
+    rule(Box,          Floor,        Push,          Player,       Box),
+    rule(Box,          Goal,         Push,          Player,       BoxOnGoal),
+    rule(Box,          Box,          BlockedByBox,  Box,          Box),
+    rule(Box,          BoxOnGoal,    BlockedByBox,  Box,          BoxOnGoal),
+    rule(Box,          Wall,         BlockedByWall, Box,          Wall),
+    rule(BoxOnGoal,    Floor,        Push,          PlayerOnGoal, Box),
+    rule(BoxOnGoal,    Goal,         Push,          PlayerOnGoal, BoxOnGoal),
+    rule(BoxOnGoal,    Box,          BlockedByBox,  BoxOnGoal,    Box),
+    rule(BoxOnGoal,    BoxOnGoal,    BlockedByBox,  BoxOnGoal,    BoxOnGoal),
+    rule(BoxOnGoal,    Wall,         BlockedByWall, BoxOnGoal,    Wall),
+    rule(Wall,         Floor,        Bump,          Wall,         Floor),
+    rule(Wall,         Goal,         Bump,          Wall,         Goal),
+    rule(Wall,         Box,          Bump,          Wall,         Box),
+    rule(Wall,         BoxOnGoal,    Bump,          Wall,         BoxOnGoal),
+    rule(Wall,         Wall,         Bump,          Wall,         Wall),
+];
+
+/// The rule for facing `ahead` with `beyond` behind it. Anything not in the
+/// table stops the player like a wall.
+pub fn step(ahead: Tile, beyond: Tile) -> Rule {
+    TABLE
+        .iter()
+        .find(|rule| rule.ahead == ahead && rule.beyond == beyond)
+        .copied()
+        .unwrap_or(rule(ahead, beyond, Bump, ahead, beyond))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILES: [Tile; 5] = [Floor, Goal, Box, BoxOnGoal, Wall];
+
+    /// The branches `player_move` had before the table, kept to check the
+    /// table against.
+    fn branches(ahead: Tile, beyond: Tile) -> (Outcome, Tile, Tile) {
+        if ahead == Wall {
+            return (Bump, ahead, beyond);
+        }
+        if ahead == Floor {
+            return (Walk, Player, beyond);
+        }
+        if ahead == Goal {
+            return (Walk, PlayerOnGoal, beyond);
+        }
+        if beyond == Wall {
+            return (BlockedByWall, ahead, beyond);
+        }
+        if beyond == Box || beyond == BoxOnGoal {
+            return (BlockedByBox, ahead, beyond);
+        }
+        let beyond_after = if beyond == Goal { BoxOnGoal } else { Box };
+        let ahead_after = if ahead == BoxOnGoal {
+            PlayerOnGoal
+        } else {
+            Player
+        };
+        (Push, ahead_after, beyond_after)
+    }
+
+    #[test]
+    fn the_table_does_what_the_branches_did() {
+        for ahead in TILES {
+            for beyond in TILES {
+                let rule = step(ahead, beyond);
+                assert_eq!(
+                    (rule.outcome, rule.ahead_after, rule.beyond_after),
+                    branches(ahead, beyond),
+                    "{:?} then {:?}",
+                    ahead,
+                    beyond
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_pair_is_in_the_table_once() {
+        for ahead in TILES {
+            for beyond in TILES {
+                let rows = TABLE
+                    .iter()
+                    .filter(|rule| rule.ahead == ahead && rule.beyond == beyond)
+                    .count();
+                assert_eq!(rows, 1, "{:?} then {:?}", ahead, beyond);
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Ending the game in order. Whatever must be written out is registered as
+//! a flush, and every thread the game started is registered with a way to
+//! stop it; `Shutdown::run` gets through all of them within a time limit,
+//! even when one fails or panics, so that the terminal is always handed
+//! back.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long the whole of shutting down may take. A thread still running by
+/// then is left to end with the process.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a thread told to stop is looked at until it has.
+const JOIN_POLL: Duration = Duration::from_millis(5);
+
+type Flush<C, E> = Box<dyn FnOnce(&mut C) -> Result<(), E>>;
+
+/// Why one step of shutting down did not finish.
+#[derive(PartialEq, Debug)]
+pub enum Failure<E> {
+    Failed(E),
+    /// The step panicked, with this message.
+    Panicked(String),
+    /// The time ran out before the step started, or, for a thread, before
+    /// it stopped.
+    TimedOut,
+}
+
+/// The steps of shutting down, run over some context `C`: flushes in the
+/// order they were registered, then joining the threads.
+pub struct Shutdown<C, E> {
+    flushes: Vec<(&'static str, Flush<C, E>)>,
+    threads: Vec<(&'static str, JoinHandle<()>)>,
+    timeout: Duration,
+}
+
+impl<C, E> Shutdown<C, E> {
+    pub fn new(timeout: Duration) -> Self {
+        Shutdown {
+            flushes: Vec::new(),
+            threads: Vec::new(),
+            timeout,
+        }
+    }
+
+    /// Registers something to write out, under a name to report it by.
+    pub fn flush(
+        &mut self,
+        name: &'static str,
+        flush: impl FnOnce(&mut C) -> Result<(), E> + 'static,
+    ) {
+        self.flushes.push((name, Box::new(flush)));
+    }
+
+    /// Registers a thread to wait for. It must have been told to stop
+    /// already: shutting down only waits.
+    pub fn thread(&mut self, name: &'static str, thread: JoinHandle<()>) {
+        self.threads.push((name, thread));
+    }
+
+    /// Runs every step, then `finally`, which runs however the steps went.
+    /// Gives back the steps that did not finish, in order, by name.
+    pub fn run(
+        self,
+        context: &mut C,
+        finally: impl FnOnce(&mut C),
+    ) -> Vec<(&'static str, Failure<E>)> {
+        let deadline = Instant::now() + self.timeout;
+        let mut failures = Vec::new();
+        for (name, flush) in self.flushes {
+            if Instant::now() >= deadline {
+                failures.push((name, Failure::TimedOut));
+                continue;
+            }
+            match panic::catch_unwind(AssertUnwindSafe(|| flush(context))) {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => failures.push((name, Failure::Failed(error))),
+                Err(payload) => failures.push((name, Failure::Panicked(panic_message(payload)))),
+            }
+        }
+        for (name, thread) in self.threads {
+            while !thread.is_finished() && Instant::now() < deadline {
+                thread::sleep(JOIN_POLL);
+            }
+            if !thread.is_finished() {
+                failures.push((name, Failure::TimedOut));
+            } else if let Err(payload) = thread.join() {
+                failures.push((name, Failure::Panicked(panic_message(payload))));
+            }
+        }
+        finally(context);
+        failures
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    };
+
+    use super::*;
+
+    /// Stands in for the game and its terminal: what got written out, and
+    /// whether the terminal was handed back.
+    #[derive(Default)]
+    struct Host {
+        flushed: Vec<&'static str>,
+        restored: bool,
+    }
+
+    fn restore(host: &mut Host) {
+        host.restored = true;
+    }
+
+    #[test]
+    fn a_panicking_flush_still_lets_shutdown_finish() {
+        let mut shutdown = Shutdown::<Host, String>::new(SHUTDOWN_TIMEOUT);
+        shutdown.flush("progress", |_| panic!("disk on fire"));
+        shutdown.flush("ratings", |host| {
+            host.flushed.push("ratings");
+            Ok(())
+        });
+        let mut host = Host::default();
+        let failures = shutdown.run(&mut host, restore);
+        assert_eq!(
+            failures,
+            vec![("progress", Failure::Panicked("disk on fire".to_string()))]
+        );
+        assert_eq!(host.flushed, vec!["ratings"]);
+        assert!(host.restored);
+    }
+
+    #[test]
+    fn a_failed_flush_is_given_back_by_name() {
+        let mut shutdown = Shutdown::<Host, String>::new(SHUTDOWN_TIMEOUT);
+        shutdown.flush("progress", |_| Err("read-only".to_string()));
+        let mut host = Host::default();
+        let failures = shutdown.run(&mut host, restore);
+        assert_eq!(
+            failures,
+            vec![("progress", Failure::Failed("read-only".to_string()))]
+        );
+        assert!(host.restored);
+    }
+
+    #[test]
+    fn flushes_not_started_in_time_are_skipped() {
+        let mut shutdown = Shutdown::<Host, String>::new(Duration::from_millis(10));
+        shutdown.flush("slow", |host| {
+            thread::sleep(Duration::from_millis(30));
+            host.flushed.push("slow");
+            Ok(())
+        });
+        shutdown.flush("late", |host| {
+            host.flushed.push("late");
+            Ok(())
+        });
+        let mut host = Host::default();
+        let failures = shutdown.run(&mut host, restore);
+        assert_eq!(failures, vec![("late", Failure::TimedOut)]);
+        assert_eq!(host.flushed, vec!["slow"]);
+        assert!(host.restored);
+    }
+
+    #[test]
+    fn threads_told_to_stop_are_joined() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (stop, stopped) = (stop.clone(), stopped.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                thread::sleep(Duration::from_millis(20));
+                stopped.store(true, Ordering::Relaxed);
+            })
+        };
+        let mut shutdown = Shutdown::<Host, String>::new(SHUTDOWN_TIMEOUT);
+        stop.store(true, Ordering::Relaxed);
+        shutdown.thread("worker", thread);
+        let mut host = Host::default();
+        assert_eq!(shutdown.run(&mut host, restore), vec![]);
+        assert!(stopped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn a_thread_that_will_not_stop_is_left_once_time_is_up() {
+        let (hold, held) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            let _ = held.recv();
+        });
+        let mut shutdown = Shutdown::<Host, String>::new(Duration::from_millis(20));
+        shutdown.thread("stuck", thread);
+        let mut host = Host::default();
+        let started = Instant::now();
+        let failures = shutdown.run(&mut host, restore);
+        assert_eq!(failures, vec![("stuck", Failure::TimedOut)]);
+        assert!(started.elapsed() < SHUTDOWN_TIMEOUT);
+        assert!(host.restored);
+        drop(hold);
+    }
+
+    #[test]
+    fn a_panicking_thread_is_reported() {
+        let thread = thread::spawn(|| panic!("search blew up"));
+        while !thread.is_finished() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let mut shutdown = Shutdown::<Host, String>::new(SHUTDOWN_TIMEOUT);
+        shutdown.thread("solvable", thread);
+        let mut host = Host::default();
+        let failures = shutdown.run(&mut host, restore);
+        assert_eq!(
+            failures,
+            vec![("solvable", Failure::Panicked("search blew up".to_string()))]
+        );
+        assert!(host.restored);
+    }
+}
@@ -0,0 +1,329 @@
+//! A search over pushes, used to rate how hard a level is and to tell
+//! whether a position can still be solved. Positions are told apart by
+//! where the boxes are and which cells the player can reach, so walking
+//! about between pushes costs nothing.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::game::{MoveDirection, Pos, Tile};
+
+/// What a search found.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Verdict {
+    /// A solution, this many pushes long.
+    Solved(u32),
+    /// Every position reachable was looked at and none is solved.
+    Unsolvable,
+    /// The budget ran out, or the search was called off, first.
+    GaveUp,
+}
+
+/// The fewest pushes that solve `grid`, if a search of at most `budget`
+/// positions finds them. A level with no solution also gives none, as does
+/// a search called off by setting `cancel`.
+pub fn min_pushes(grid: &[Vec<Tile>], budget: usize, cancel: &AtomicBool) -> Option<u32> {
+    match search(grid, budget, true, cancel).0 {
+        Verdict::Solved(pushes) => Some(pushes),
+        Verdict::Unsolvable | Verdict::GaveUp => None,
+    }
+}
+
+/// Whether `grid` can still be solved, by any number of pushes. Going deep
+/// first finds some solution sooner than going wide would. Gives up once
+/// `cancel` is set.
+pub fn solvable(grid: &[Vec<Tile>], budget: usize, cancel: &AtomicBool) -> Verdict {
+    search(grid, budget, false, cancel).0
+}
+
+/// The box to push first, and which way, on the way to the fewest pushes
+/// that solve `grid`, if a search of at most `budget` positions finds them.
+/// A board already solved has none.
+pub fn first_push(grid: &[Vec<Tile>], budget: usize) -> Option<(Pos, MoveDirection)> {
+    let width = grid.iter().map(Vec::len).max()?;
+    let (pos, dir) = search(grid, budget, true, &AtomicBool::new(false)).1?;
+    let direction = match dir {
+        0 => MoveDirection::Up,
+        1 => MoveDirection::Right,
+        2 => MoveDirection::Down,
+        _ => MoveDirection::Left,
+    };
+    Some((
+        Pos::new((pos % width) as i32, (pos / width) as i32),
+        direction,
+    ))
+}
+
+/// The first push of a solution: the cell of the box pushed, and `dir` as
+/// `Board::step` takes it.
+type Push = (usize, usize);
+
+/// Looks at up to `budget` positions, nearest first when `shortest`, so that
+/// the first solution found is the fewest pushes. A solution comes with its
+/// first push.
+fn search(
+    grid: &[Vec<Tile>],
+    budget: usize,
+    shortest: bool,
+    cancel: &AtomicBool,
+) -> (Verdict, Option<Push>) {
+    let Some(board) = Board::new(grid) else {
+        return (Verdict::Unsolvable, None);
+    };
+    let mut boxes = board.boxes.clone();
+    boxes.sort();
+    if boxes.len() > board.goals.len() {
+        return (Verdict::Unsolvable, None);
+    }
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([(boxes, board.player, 0, None)]);
+    loop {
+        let next = match shortest {
+            true => queue.pop_front(),
+            false => queue.pop_back(),
+        };
+        let Some((boxes, player, pushes, first)) = next else {
+            return (Verdict::Unsolvable, None);
+        };
+        if boxes.iter().all(|pos| board.goals.contains(pos)) {
+            return (Verdict::Solved(pushes), first);
+        }
+        let reach = board.reach(&boxes, player);
+        let corner = reach.iter().position(|reached| *reached).unwrap_or(player);
+        if !seen.insert((boxes.clone(), corner)) {
+            continue;
+        }
+        if seen.len() > budget || cancel.load(Ordering::Relaxed) {
+            return (Verdict::GaveUp, None);
+        }
+        for (idx, pos) in boxes.iter().enumerate() {
+            for dir in 0..4 {
+                let (Some(stand), Some(to)) = (board.step(*pos, dir ^ 2), board.step(*pos, dir))
+                else {
+                    continue;
+                };
+                if !reach[stand] || board.walls[to] || board.dead[to] || boxes.contains(&to) {
+                    continue;
+                }
+                let mut next = boxes.clone();
+                next[idx] = to;
+                if board.stuck(&next, to) {
+                    continue;
+                }
+                next.sort();
+                queue.push_back((next, *pos, pushes + 1, first.or(Some((*pos, dir)))));
+            }
+        }
+    }
+}
+
+/// A level as flat cells, `y * width + x`, with everything off the board a
+/// wall.
+struct Board {
+    width: usize,
+    walls: Vec<bool>,
+    /// Cells a box can never be pushed from onto any goal.
+    dead: Vec<bool>,
+    goals: Vec<usize>,
+    boxes: Vec<usize>,
+    player: usize,
+}
+
+impl Board {
+    fn new(grid: &[Vec<Tile>]) -> Option<Board> {
+        let width = grid.iter().map(Vec::len).max()?;
+        let mut board = Board {
+            width,
+            walls: vec![true; width * grid.len()],
+            dead: vec![],
+            goals: vec![],
+            boxes: vec![],
+            player: 0,
+        };
+        let mut player = None;
+        for (y, row) in grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                let pos = y * width + x;
+                board.walls[pos] = *cell == Tile::Wall;
+                if cell.is_goal() {
+                    board.goals.push(pos);
+                }
+                if cell.is_box() {
+                    board.boxes.push(pos);
+                }
+                if cell.is_player() {
+                    player = Some(pos);
+                }
+            }
+        }
+        board.player = player?;
+        board.dead = board.dead_cells();
+        Some(board)
+    }
+
+    /// The cell next to `pos` going up, right, down or left for `dir` 0 to
+    /// 3, so that `dir ^ 2` is the way back.
+    fn step(&self, pos: usize, dir: usize) -> Option<usize> {
+        let x = pos % self.width;
+        let next = match dir {
+            0 => pos.checked_sub(self.width)?,
+            1 if x + 1 < self.width => pos + 1,
+            2 => pos + self.width,
+            3 if x > 0 => pos - 1,
+            _ => return None,
+        };
+        (next < self.walls.len() && !self.walls[next]).then_some(next)
+    }
+
+    /// The cells the player can walk to from `player` around `boxes`.
+    fn reach(&self, boxes: &[usize], player: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.walls.len()];
+        reached[player] = true;
+        let mut todo = vec![player];
+        while let Some(pos) = todo.pop() {
+            for dir in 0..4 {
+                if let Some(next) = self.step(pos, dir) {
+                    if !reached[next] && !boxes.contains(&next) {
+                        reached[next] = true;
+                        todo.push(next);
+                    }
+                }
+            }
+        }
+        reached
+    }
+
+    /// Whether the box just pushed to `pos` sits in a square of four cells
+    /// that are all walls or boxes, with some box of them off its goal:
+    /// none of those boxes can ever move again.
+    fn stuck(&self, boxes: &[usize], pos: usize) -> bool {
+        let blocked = |pos: usize| self.walls[pos] || boxes.contains(&pos);
+        let x = pos % self.width;
+        let y = pos / self.width;
+        let height = self.walls.len() / self.width;
+        [(0, 0), (1, 0), (0, 1), (1, 1)]
+            .into_iter()
+            .any(|(dx, dy)| {
+                let (Some(left), Some(top)) = (x.checked_sub(dx), y.checked_sub(dy)) else {
+                    return false;
+                };
+                if left + 1 >= self.width || top + 1 >= height {
+                    return false;
+                }
+                let square =
+                    [0, 1, self.width, self.width + 1].map(|at| top * self.width + left + at);
+                square.iter().all(|cell| blocked(*cell))
+                    && square
+                        .iter()
+                        .any(|cell| boxes.contains(cell) && !self.goals.contains(cell))
+            })
+    }
+
+    /// Pulls boxes back from every goal: any cell none of them reaches is
+    /// one a box cannot be pushed from onto a goal.
+    fn dead_cells(&self) -> Vec<bool> {
+        let mut live = vec![false; self.walls.len()];
+        let mut todo = self.goals.clone();
+        for goal in &self.goals {
+            live[*goal] = true;
+        }
+        while let Some(pos) = todo.pop() {
+            for dir in 0..4 {
+                let Some(next) = self.step(pos, dir) else {
+                    continue;
+                };
+                if self.step(next, dir).is_some() && !live[next] {
+                    live[next] = true;
+                    todo.push(next);
+                }
+            }
+        }
+        live.into_iter().map(|live| !live).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: usize = 10_000;
+
+    fn grid(rows: &[&str]) -> Vec<Vec<Tile>> {
+        rows.iter()
+            .map(|row| row.chars().map(|c| Tile::try_from(c).unwrap()).collect())
+            .collect()
+    }
+
+    fn solvable_with(rows: &[&str], budget: usize) -> Verdict {
+        solvable(&grid(rows), budget, &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn a_board_with_a_way_through_is_solved() {
+        assert!(matches!(
+            solvable_with(&["#####", "#@$.#", "#####"], BUDGET),
+            Verdict::Solved(1)
+        ));
+        assert!(matches!(
+            solvable_with(&["####", "#@*#", "####"], BUDGET),
+            Verdict::Solved(0)
+        ));
+        assert_eq!(
+            min_pushes(
+                &grid(&["#######", "#@$  .#", "#######"]),
+                BUDGET,
+                &AtomicBool::new(false)
+            ),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn a_box_with_no_way_to_its_goal_is_unsolvable() {
+        // The box sits against the end wall, so it can only be pushed
+        // further into it.
+        assert_eq!(
+            solvable_with(&["######", "#.@ $#", "######"], BUDGET),
+            Verdict::Unsolvable
+        );
+        assert_eq!(
+            solvable_with(&["######", "#@$$.#", "######"], BUDGET),
+            Verdict::Unsolvable
+        );
+    }
+
+    #[test]
+    fn the_search_gives_up_once_its_budget_is_spent() {
+        let room = [
+            "########", "#@     #", "# $  $ #", "#      #", "#   .. #", "########",
+        ];
+        assert_eq!(solvable_with(&room, 0), Verdict::GaveUp);
+        assert_eq!(min_pushes(&grid(&room), 0, &AtomicBool::new(false)), None);
+        assert!(matches!(solvable_with(&room, BUDGET), Verdict::Solved(_)));
+    }
+
+    #[test]
+    fn a_search_called_off_gives_up() {
+        let room = grid(&[
+            "########", "#@     #", "# $  $ #", "#      #", "#   .. #", "########",
+        ]);
+        let cancel = AtomicBool::new(true);
+        assert_eq!(solvable(&room, BUDGET, &cancel), Verdict::GaveUp);
+        assert_eq!(min_pushes(&room, BUDGET, &cancel), None);
+    }
+
+    #[test]
+    fn the_first_push_is_of_the_fewest_pushes_solution() {
+        assert_eq!(
+            first_push(&grid(&["#####", "#@$.#", "#####"]), BUDGET),
+            Some((Pos::new(2, 1), MoveDirection::Right))
+        );
+        assert_eq!(
+            first_push(&grid(&["###", "#.#", "#$#", "#@#", "###"]), BUDGET),
+            Some((Pos::new(1, 2), MoveDirection::Up))
+        );
+        assert_eq!(first_push(&grid(&["####", "#@*#", "####"]), BUDGET), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Sound packs: every sound of the game in one file, so that a different
+//! set of sounds can be handed around as a single download. The default
+//! sounds ship as a pack built into the game.
+//!
+//! ```text
+//! SOKOSND1                     magic, 8 bytes
+//! count                        u32, little-endian
+//! count times:
+//!     name length              u16
+//!     name                     UTF-8, the sound's file name without extension
+//!     offset, length           u32 each, into the data
+//! data                         the audio files one after another
+//! ```
+//!
+//! `sokoban_rust --pack-sounds DIR OUT` builds a pack from the files in a
+//! directory, named by their file names without the extension.
+
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use super::Error;
+
+const MAGIC: &[u8; 8] = b"SOKOSND1";
+
+/// Sounds by name, each the bytes of an audio file in any format the
+/// decoder reads. Shared, since the audio thread keeps a sound while it plays.
+#[derive(Clone, Default)]
+pub struct SoundPack {
+    sounds: HashMap<String, Arc<[u8]>>,
+}
+
+impl SoundPack {
+    /// Reads a pack, checking that the index and every sound in it lie
+    /// within the file.
+    pub fn read(bytes: &[u8]) -> Result<SoundPack, Error> {
+        let mut reader = Reader { bytes, at: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(corrupt("it is not a sound pack"));
+        }
+        let count = reader.u32()?;
+        let mut index = vec![];
+        for _ in 0..count {
+            let length = reader.u16()? as usize;
+            let name = std::str::from_utf8(reader.take(length)?)
+                .map_err(|_| corrupt("a sound name is not UTF-8"))?;
+            index.push((
+                name.to_string(),
+                reader.u32()? as usize,
+                reader.u32()? as usize,
+            ));
+        }
+        let data = &bytes[reader.at..];
+        let mut sounds = HashMap::new();
+        for (name, offset, length) in index {
+            let sound = offset
+                .checked_add(length)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| corrupt(&format!("the sound {} is cut short", name)))?;
+            if sounds.insert(name.clone(), Arc::from(sound)).is_some() {
+                return Err(corrupt(&format!("the sound {} is in it twice", name)));
+            }
+        }
+        Ok(SoundPack { sounds })
+    }
+
+    /// Reads every file in `dir` as a sound named by its file name without
+    /// the extension.
+    pub fn from_dir(dir: &Path) -> Result<SoundPack, Error> {
+        let mut sounds = HashMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if !path.is_file() || name.starts_with('.') {
+                continue;
+            }
+            let bytes = fs::read(&path)?;
+            if sounds.insert(name.to_string(), Arc::from(bytes)).is_some() {
+                return Err(Error::Usage(format!(
+                    "{}: two files are named {}",
+                    dir.display(),
+                    name
+                )));
+            }
+        }
+        Ok(SoundPack { sounds })
+    }
+
+    /// The pack as a file, sounds in name order so the same sounds always
+    /// make the same file. Fails if a name or the sounds are too long for
+    /// the fields that give their lengths.
+    pub fn write(&self) -> Result<Vec<u8>, Error> {
+        let mut names: Vec<&String> = self.sounds.keys().collect();
+        names.sort();
+        let too_long = |what: &str| Error::Usage(format!("{} is too long for a sound pack", what));
+        let mut bytes = MAGIC.to_vec();
+        let count = u32::try_from(names.len()).map_err(|_| too_long("the list of sounds"))?;
+        bytes.extend(count.to_le_bytes());
+        let mut offset: u32 = 0;
+        for name in &names {
+            let sound = || format!("the sound {}", name);
+            let length = u32::try_from(self.sounds[*name].len()).map_err(|_| too_long(&sound()))?;
+            let name_length = u16::try_from(name.len())
+                .map_err(|_| too_long(&format!("the name of {}", sound())))?;
+            bytes.extend(name_length.to_le_bytes());
+            bytes.extend(name.as_bytes());
+            bytes.extend(offset.to_le_bytes());
+            bytes.extend(length.to_le_bytes());
+            offset = offset
+                .checked_add(length)
+                .ok_or_else(|| too_long("the sounds together"))?;
+        }
+        for name in names {
+            bytes.extend(self.sounds[name].iter());
+        }
+        Ok(bytes)
+    }
+
+    /// Adds the sounds of `other`, in place of any with the same name.
+    pub fn extend(&mut self, other: SoundPack) {
+        self.sounds.extend(other.sounds);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<[u8]>> {
+        self.sounds.get(name).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sounds.is_empty()
+    }
+}
+
+/// Walks through a pack, failing at the first field that runs past its end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let field = self
+            .bytes
+            .get(self.at..self.at + length)
+            .ok_or_else(|| corrupt("the index is cut short"))?;
+        self.at += length;
+        Ok(field)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let field = self.take(2)?;
+        Ok(u16::from_le_bytes([field[0], field[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let field = self.take(4)?;
+        Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+    }
+}
+
+fn corrupt(reason: &str) -> Error {
+    Error::Audio(format!("bad sound pack: {}", reason))
+}
+
+/// Runs `--pack-sounds DIR OUT` with the arguments after the flag, giving a
+/// summary of what it wrote.
+pub fn pack_sounds(args: &[String]) -> Result<String, Error> {
+    let [dir, out] = args else {
+        return Err(Error::Usage(
+            "--pack-sounds takes the directory of sounds and the pack to write".to_string(),
+        ));
+    };
+    let pack = SoundPack::from_dir(Path::new(dir))?;
+    if pack.is_empty() {
+        return Err(Error::Usage(format!("{}: there are no sounds in it", dir)));
+    }
+    let bytes = pack.write()?;
+    fs::write(out, &bytes)?;
+    Ok(format!(
+        "Packed {} sounds into {} ({} bytes).",
+        pack.len(),
+        out,
+        bytes.len()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pack file with `index` as its entries of name, offset and length,
+    /// followed by `data`.
+    fn pack(index: &[(&str, u32, u32)], data: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((index.len() as u32).to_le_bytes());
+        for (name, offset, length) in index {
+            bytes.extend((name.len() as u16).to_le_bytes());
+            bytes.extend(name.as_bytes());
+            bytes.extend(offset.to_le_bytes());
+            bytes.extend(length.to_le_bytes());
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    fn problem(bytes: &[u8]) -> String {
+        match SoundPack::read(bytes) {
+            Err(Error::Audio(reason)) => reason,
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("a bad pack was read"),
+        }
+    }
+
+    #[test]
+    fn a_written_pack_reads_back() {
+        let bytes = pack(&[("move", 0, 3), ("win", 3, 2)], b"abcde");
+        let read = SoundPack::read(&bytes).ok().unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read.get("move").as_deref(), Some(&b"abc"[..]));
+        assert_eq!(read.get("win").as_deref(), Some(&b"de"[..]));
+        assert_eq!(read.write().ok(), Some(bytes));
+    }
+
+    #[test]
+    fn a_file_without_the_magic_is_not_a_pack() {
+        let mut bytes = pack(&[("move", 0, 3)], b"abc");
+        bytes[0] = b'X';
+        assert_eq!(problem(&bytes), "bad sound pack: it is not a sound pack");
+        assert_eq!(problem(b"SOKO"), "bad sound pack: the index is cut short");
+    }
+
+    #[test]
+    fn an_index_cut_short_is_caught() {
+        let bytes = pack(&[("move", 0, 3)], b"");
+        for end in MAGIC.len()..bytes.len() {
+            assert_eq!(
+                problem(&bytes[..end]),
+                "bad sound pack: the index is cut short",
+                "cut at {}",
+                end
+            );
+        }
+    }
+
+    #[test]
+    fn a_sound_past_the_end_of_the_data_is_caught() {
+        assert_eq!(
+            problem(&pack(&[("move", 2, 3)], b"abc")),
+            "bad sound pack: the sound move is cut short"
+        );
+        assert_eq!(
+            problem(&pack(&[("move", u32::MAX, 2)], b"abc")),
+            "bad sound pack: the sound move is cut short"
+        );
+    }
+
+    #[test]
+    fn a_name_given_twice_is_caught() {
+        assert_eq!(
+            problem(&pack(&[("move", 0, 1), ("move", 1, 1)], b"ab")),
+            "bad sound pack: the sound move is in it twice"
+        );
+    }
+
+    #[test]
+    fn a_name_too_long_for_its_field_is_not_written() {
+        let name = "a".repeat(usize::from(u16::MAX) + 1);
+        let pack = SoundPack {
+            sounds: HashMap::from([(name, Arc::from(&b"abc"[..]))]),
+        };
+        assert!(matches!(
+            pack.write(),
+            Err(Error::Usage(reason)) if reason.starts_with("the name of the sound aaa")
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! The tip under the welcome and level select screens: a piece of advice
+//! or a line of flavour, picked afresh on entering either screen and every
+//! `TIP_INTERVAL` while it stays up. A `tips.txt` in the data directory
+//! adds tips of its own, one per line.
+
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use super::generate::Rng;
+
+/// How long a tip stays up before the next one.
+pub const TIP_INTERVAL: Duration = Duration::from_secs(20);
+/// Longer lines from `tips.txt` are cut short, so a tip fits on one line.
+const TIP_MAX_LEN: usize = 100;
+/// How many times as likely a tip is to come up while the player has never
+/// done what it is about.
+const UNTRIED_WEIGHT: usize = 4;
+
+/// What a tip is about, so tips on something the player has never done can
+/// come up more often.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Topic {
+    General,
+    Undo,
+}
+
+/// The built-in tips. `{undo}` stands for the key that takes a move back.
+const BUILT_IN: &[(Topic, &str)] = &[
+    (
+        Topic::Undo,
+        "Pushed a box the wrong way? Press {undo} to take the move back.",
+    ),
+    (
+        Topic::Undo,
+        "Start with --undo-by push to have {undo} take back a whole push at once.",
+    ),
+    (
+        Topic::General,
+        "A box in a corner off its goal can never be moved again.",
+    ),
+    (
+        Topic::General,
+        "Boxes pushed against a wall can only slide along it.",
+    ),
+    (
+        Topic::General,
+        "Work out which goal each box is going to before the first push.",
+    ),
+    (
+        Topic::General,
+        "Fill the goals furthest from the way in first.",
+    ),
+    (
+        Topic::General,
+        "Sokoban means warehouse keeper in Japanese.",
+    ),
+    (
+        Topic::General,
+        "The first Sokoban was published in 1982, with twenty levels.",
+    ),
+    (
+        Topic::General,
+        "Every box you push is a promise you have to keep.",
+    ),
+];
+
+/// The tips to pick from and the state of the one on screen.
+#[derive(Clone)]
+pub struct Tips {
+    tips: Vec<(Topic, String)>,
+    rng: Rng,
+    /// The tip last picked, where it sits on the screen, and since when.
+    pub shown: Option<(usize, String, Instant)>,
+}
+
+impl Tips {
+    /// The built-in tips and those in `file`, if it can be read, picked in
+    /// an order fixed by `seed`.
+    pub fn new(file: Option<&Path>, seed: u64) -> Tips {
+        let mut tips: Vec<(Topic, String)> = BUILT_IN
+            .iter()
+            .map(|(topic, text)| (*topic, text.to_string()))
+            .collect();
+        if let Some(text) = file.and_then(|file| fs::read_to_string(file).ok()) {
+            tips.extend(
+                text.lines()
+                    .map(clean)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| (Topic::General, line)),
+            );
+        }
+        Tips {
+            tips,
+            rng: Rng(seed),
+            shown: None,
+        }
+    }
+
+    /// The next tip, with those whose topic `untried` holds for weighted up.
+    pub fn pick(&mut self, untried: impl Fn(Topic) -> bool) -> &str {
+        let weights: Vec<usize> = self
+            .tips
+            .iter()
+            .map(|(topic, _)| if untried(*topic) { UNTRIED_WEIGHT } else { 1 })
+            .collect();
+        let mut left = self.rng.below(weights.iter().sum());
+        let idx = weights
+            .iter()
+            .position(|weight| match left.checked_sub(*weight) {
+                Some(rest) => {
+                    left = rest;
+                    false
+                }
+                None => true,
+            })
+            .unwrap_or(0);
+        &self.tips[idx].1
+    }
+
+    /// Whether the tip on screen has been up long enough to change.
+    pub fn due(&self) -> bool {
+        self.shown
+            .as_ref()
+            .is_some_and(|(_, _, since)| since.elapsed() >= TIP_INTERVAL)
+    }
+}
+
+/// A line of `tips.txt` as a tip: control characters dropped, spaces
+/// trimmed, and at most `TIP_MAX_LEN` characters.
+fn clean(line: &str) -> String {
+    let line: String = line.chars().filter(|c| !c.is_control()).collect();
+    line.trim().chars().take(TIP_MAX_LEN).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picks(seed: u64, untried: impl Fn(Topic) -> bool + Copy) -> Vec<String> {
+        let mut tips = Tips::new(None, seed);
+        (0..20).map(|_| tips.pick(untried).to_string()).collect()
+    }
+
+    #[test]
+    fn a_seed_pins_the_order_tips_come_in() {
+        assert_eq!(picks(42, |_| false), picks(42, |_| false));
+        assert_ne!(picks(42, |_| false), picks(43, |_| false));
+    }
+
+    #[test]
+    fn untried_topics_come_up_more_often() {
+        let undo_tips = |untried: bool| -> usize {
+            let mut tips = Tips::new(None, 7);
+            (0..2000)
+                .filter(|_| {
+                    let tip = tips.pick(|topic| untried && topic == Topic::Undo);
+                    tip.contains("{undo}")
+                })
+                .count()
+        };
+        assert!(undo_tips(true) > 2 * undo_tips(false));
+    }
+
+    #[test]
+    fn lines_of_a_tips_file_are_cleaned() {
+        assert_eq!(clean("  a\tb\u{1b}c  "), "abc");
+        assert_eq!(clean(&"x".repeat(300)).len(), TIP_MAX_LEN);
+    }
+}
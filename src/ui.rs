@@ -0,0 +1,674 @@
+//! Drawing the game: the board and what goes around it, level previews,
+//! the editor, and screenshots of what was drawn.
+
+use std::collections::{HashMap, HashSet};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Wrap},
+    Frame,
+};
+
+use super::{
+    date,
+    game::{cell_at, level_id, reachable, Blocked, Deadlock, MoveDirection, Pos, Tile, Variant},
+    levels::{level_layout, level_name, Level},
+    menu_entries, prompt_parts, prompt_text,
+    solver::Verdict,
+    Command, Editor, GameState, Heat, MenuEntry, Prompt, UndoStep,
+};
+
+pub fn draw(frame: &mut Frame, area: Rect, game_state: &GameState) {
+    if let Some(editor) = game_state.ui.editor.as_ref().filter(|e| !e.playtesting) {
+        draw_editor(frame, area, game_state, editor);
+        return;
+    }
+    let instant_replay = game_state.ui.instant_replay.as_ref();
+    let banner = match (&game_state.ui.attract, instant_replay) {
+        (Some(_), _) => Some("Replaying a best run - press any key".to_string()),
+        (None, Some(replay)) => Some(format!(
+            "Instant replay, move {} of {} - +/- to change speed, Esc to stop",
+            replay.frame,
+            replay.frames.len() - 1
+        )),
+        (None, None) => None,
+    };
+    let (grid, player_position) = match instant_replay {
+        Some(replay) => {
+            let (grid, position) = &replay.frames[replay.frame];
+            (grid, *position)
+        }
+        None => (&game_state.attempt.grid, game_state.attempt.player_position),
+    };
+    let title = match (&banner, &game_state.profile.collection) {
+        (None, Some(collection)) if game_state.attempt.level == Some(Level::File) => {
+            Some(collection.title(collection.index))
+        }
+        _ => None,
+    };
+    let ruler = game_state.ui.ruler && game_state.attempt.level.is_some();
+    let offset = usize::from(banner.is_some() || title.is_some()) + usize::from(ruler);
+    let explained = game_state
+        .attempt
+        .deadlock
+        .as_ref()
+        .filter(|_| game_state.ui.explain_deadlock && instant_replay.is_none());
+
+    let mut footer = vec![];
+    if game_state.profile.settings.show_bumps
+        && game_state.attempt.level.is_some()
+        && banner.is_none()
+    {
+        footer.push(Line::from(format!("Wall bumps: {}", game_state.attempt.wall_bumps)).dim());
+    }
+    if game_state.profile.settings.strict && game_state.attempt.level.is_some() && banner.is_none()
+    {
+        footer.push(
+            Line::from(format!(
+                "Strict: {} regressions",
+                game_state.attempt.regressions
+            ))
+            .dim(),
+        );
+    }
+    if let (Some(seed), Some(_), None) = (
+        game_state.profile.settings.scramble,
+        game_state.attempt.scramble,
+        &banner,
+    ) {
+        footer.push(Line::from(format!("Scrambled, seed {}", seed)).dim());
+    }
+    if let (Some(Level::Daily), None) = (game_state.attempt.level, &banner) {
+        footer.push(Line::from(format!("Daily puzzle, {}", date(game_state.ui.daily))).dim());
+    }
+    if let (Some(Level::Random), None) = (game_state.attempt.level, &banner) {
+        footer.push(Line::from(format!("Random board, seed {}", game_state.ui.random_seed)).dim());
+    }
+    if game_state.attempt.variant != Variant::Normal && banner.is_none() {
+        footer.push(Line::from(format!("Variant: {}", game_state.attempt.variant.name())).dim());
+    }
+    if let (Some(run), Some(_), None) = (&game_state.ui.arcade, game_state.attempt.level, &banner) {
+        footer.push(
+            Line::from(format!(
+                "Arcade: {} points left, {} banked",
+                run.left(),
+                run.banked
+            ))
+            .dim(),
+        );
+    }
+    let prompt_row = game_state.ui.prompt.as_ref().map(|_| footer.len());
+    if let Some(prompt) = &game_state.ui.prompt {
+        footer.push(Line::from(prompt_text(prompt)).yellow());
+    }
+    if let Some(message) = &game_state.ui.message {
+        footer.push(Line::from(message.clone()).yellow());
+    }
+    if game_state.profile.settings.kids && game_state.attempt.level.is_some() && banner.is_none() {
+        footer.push(
+            Line::from(format!(
+                "Oops? Press {} to take back a move - as many as you like!",
+                game_state.ui.keymap.hint(&Command::ReverseMove)
+            ))
+            .bold()
+            .green(),
+        );
+    }
+    if let (true, Some(_), None, None) = (
+        game_state.profile.settings.check_solvable,
+        game_state.attempt.level,
+        &game_state.attempt.deadlock,
+        &banner,
+    ) {
+        let solvability = &game_state.ui.solvability;
+        footer.push(
+            match solvability
+                .current
+                .and_then(|key| solvability.known.get(&key))
+            {
+                Some(Verdict::Solved(_)) => Line::from("Solvable: yes").green(),
+                Some(Verdict::GaveUp) => Line::from("Solvable: not sure, too much to check").dim(),
+                Some(Verdict::Unsolvable) => Line::from(format!(
+                    "Solvable: no - press {} to take the push back.",
+                    game_state.ui.keymap.hint(&Command::ReverseMove)
+                ))
+                .red(),
+                None => Line::from("Solvable: checking...").dim(),
+            },
+        );
+    }
+    if let Some(deadlock) = &game_state.attempt.deadlock {
+        footer.push(match explained {
+            Some(_) => Line::from(deadlock.explanation()).yellow(),
+            None => Line::from(format!(
+                "Deadlock: a box can no longer reach a goal. Press {} to see why, {} to {}.",
+                game_state.ui.keymap.hint(&Command::ExplainDeadlock),
+                game_state.ui.keymap.hint(&Command::ReverseMove),
+                match game_state.profile.settings.undo_step {
+                    UndoStep::Move => "undo",
+                    UndoStep::Push => "undo the last push",
+                    UndoStep::Box => "undo back to the last box placed",
+                }
+            ))
+            .red(),
+        });
+    }
+    if ruler {
+        footer.push(Line::from(format!("Player at {}", coord_name(player_position))).dim());
+    }
+    let heat = game_state
+        .attempt
+        .level
+        .and_then(|level| level_id(game_state, level))
+        .and_then(|id| game_state.profile.heat.get(&id))
+        .filter(|heat| game_state.ui.show_heat && heat.attempts > 0 && instant_replay.is_none());
+    if let Some(heat) = heat {
+        footer.push(
+            Line::from(format!(
+                "Heatmap: where boxes stood in {} failed attempts",
+                heat.attempts
+            ))
+            .dim(),
+        );
+    }
+    if game_state.ui.show_reachable && game_state.attempt.level.is_some() && banner.is_none() {
+        footer.push(Line::from("Shaded: the squares you can reach without pushing").dim());
+    }
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let margin = if ruler { 3 } else { 0 };
+    let room = (
+        usize::from(area.width).saturating_sub(margin),
+        usize::from(area.height).saturating_sub(offset + footer.len()),
+    );
+    let (left, top, shown) = match game_state.ui.camera {
+        Some(camera) => {
+            let top = scroll(camera.y, grid.len(), room.1);
+            (
+                scroll(camera.x, width, room.0),
+                top,
+                (grid.len() - top).min(room.1),
+            )
+        }
+        None => (0, 0, grid.len()),
+    };
+    let hovered = game_state
+        .ui
+        .pointer
+        .filter(|_| game_state.attempt.level.is_some())
+        .and_then(|(column, row)| {
+            let x = i32::from(column) - i32::from(area.x) - margin as i32 + left as i32;
+            let y = i32::from(row) - i32::from(area.y) - offset as i32 + top as i32;
+            let pos = Pos::new(x, y);
+            pos.tile(grid).map(|tile| (pos, tile))
+        });
+    if let Some((pos, cell)) = hovered {
+        footer.push(Line::from(format!("{}: {}", coord_name(pos), cell_description(cell))).dim());
+    }
+    if let Some((key, _)) = game_state.ui.keymap.pending {
+        footer.push(Line::from(format!("{}-", key)).dim());
+    }
+    if !game_state.ui.screen.is_empty() && instant_replay.is_none() {
+        draw_screen(frame, area, game_state, footer, prompt_row);
+        return;
+    }
+    let rows = shown + offset + footer.len();
+    let areas = Layout::vertical(vec![Constraint::Length(1); rows]).split(area);
+
+    if let Some(banner) = &banner {
+        frame.render_widget(Paragraph::new(banner.clone()).yellow(), areas[0]);
+    } else if let Some(title) = title {
+        frame.render_widget(Paragraph::new(title).bold(), areas[0]);
+    }
+    if ruler {
+        let letters: String = (left as i32..width as i32).map(column_name).collect();
+        frame.render_widget(
+            Paragraph::new(format!("   {}", letters)).dim(),
+            areas[offset - 1],
+        );
+    }
+    let ghost_at = game_state
+        .attempt
+        .ghost
+        .as_ref()
+        .filter(|_| game_state.attempt.level.is_some() && instant_replay.is_none())
+        .map(|ghost| {
+            let step = game_state.attempt.moves.len().min(ghost.trail.len() - 1);
+            ghost.trail[step]
+        });
+    // use the simpler short-hand syntax
+    let blocker = match game_state.attempt.blocked {
+        Some(Blocked::Box(at)) if instant_replay.is_none() => Some(at),
+        _ => None,
+    };
+    let reach = (game_state.ui.show_reachable
+        && game_state.attempt.level.is_some()
+        && game_state.ui.attract.is_none())
+    .then(|| reachable(grid, player_position));
+    let visible = &grid[top..top + shown];
+    visible.iter().zip(top..).for_each(|(row, idx)| {
+        let row = &row[left.min(row.len())..];
+        let styled = explained.is_some()
+            || ghost_at.is_some()
+            || blocker.is_some()
+            || heat.is_some()
+            || reach.is_some();
+        let mut line = if styled {
+            styled_row(
+                row,
+                Pos::new(left as i32, idx as i32),
+                explained,
+                ghost_at,
+                blocker,
+                heat,
+                reach.as_ref(),
+            )
+        } else {
+            Line::from(row.iter().map(|tile| char::from(*tile)).collect::<String>()).blue()
+        };
+        if ruler {
+            line.spans
+                .insert(0, Span::from(format!("{:>2} ", idx + 1)).dim());
+        }
+        if game_state.ui.menu_cursor.map(|cursor| cursor + 1) == Some(idx) {
+            line = line.reversed();
+        }
+        let paragraph = Paragraph::new(line);
+        let paragraph = if game_state.ui.attract.is_some() {
+            paragraph.dim()
+        } else {
+            paragraph
+        };
+        frame.render_widget(paragraph, areas[idx - top + offset]);
+    });
+    let footer_start = rows - footer.len();
+    draw_footer(
+        frame,
+        &areas[footer_start..],
+        footer,
+        prompt_row,
+        game_state,
+    );
+}
+
+/// Draws the footer lines one to an area, the prompt among them as its own
+/// widget, see `draw_prompt`.
+pub fn draw_footer(
+    frame: &mut Frame,
+    areas: &[Rect],
+    footer: Vec<Line>,
+    prompt_row: Option<usize>,
+    game_state: &GameState,
+) {
+    for (idx, line) in footer.into_iter().enumerate() {
+        match &game_state.ui.prompt {
+            Some(prompt) if prompt_row == Some(idx) => draw_prompt(frame, areas[idx], prompt),
+            _ => frame.render_widget(Paragraph::new(line), areas[idx]),
+        }
+    }
+}
+
+/// Draws a text screen such as level select or the results, wrapped to the
+/// width of the terminal, with the footer lines pinned below it.
+pub fn draw_screen(
+    frame: &mut Frame,
+    area: Rect,
+    game_state: &GameState,
+    footer: Vec<Line>,
+    prompt_row: Option<usize>,
+) {
+    let mut constraints = vec![Constraint::Min(0)];
+    constraints.extend(vec![Constraint::Length(1); footer.len()]);
+    let areas = Layout::vertical(constraints).split(area);
+    let mut lines: Vec<Line> = game_state
+        .ui
+        .screen
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| {
+            if game_state.ui.menu_cursor.map(|cursor| cursor + 1) == Some(idx) {
+                line.clone().reversed()
+            } else {
+                line.clone()
+            }
+        })
+        .collect();
+    // The celebration stands in for the results banner.
+    if let Some(celebration) = &game_state.ui.celebration {
+        lines.splice(
+            ..1.min(lines.len()),
+            celebration
+                .frame()
+                .iter()
+                .map(|row| Line::from(row.clone()).bold()),
+        );
+    }
+    let highlighted = game_state
+        .ui
+        .menu_cursor
+        .and_then(|cursor| match menu_entries(game_state).get(cursor) {
+            Some(MenuEntry::Level(level)) => Some(*level),
+            _ => None,
+        })
+        .filter(|_| area.width >= PREVIEW_MIN_WIDTH);
+    let text_area = match highlighted {
+        Some(level) => {
+            let [text, panel] =
+                Layout::horizontal([Constraint::Min(0), Constraint::Percentage(40)])
+                    .areas(areas[0]);
+            draw_preview(frame, panel, game_state, level);
+            text
+        }
+        None => areas[0],
+    };
+    frame.render_widget(
+        Paragraph::new(lines)
+            .blue()
+            .wrap(Wrap { trim: true })
+            .scroll((game_state.ui.whats_new_scroll.unwrap_or(0), 0)),
+        text_area,
+    );
+    draw_footer(frame, &areas[1..], footer, prompt_row, game_state);
+}
+
+/// Rendered previews by level and the size they were fitted to, built the
+/// first time each is drawn.
+pub type PreviewCache = HashMap<(Level, (u16, u16)), Vec<Line<'static>>>;
+
+/// Terminals narrower than this leave out the level preview.
+pub const PREVIEW_MIN_WIDTH: u16 = 60;
+
+/// A bordered miniature of the level's starting board with its size and
+/// box and goal counts.
+pub fn draw_preview(frame: &mut Frame, area: Rect, game_state: &GameState, level: Level) {
+    let block = Block::bordered().title(level_name(level)).dim();
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let size = (inner.width, inner.height.saturating_sub(1));
+    let lines = game_state
+        .ui
+        .previews
+        .borrow_mut()
+        .entry((level, size))
+        .or_insert_with(|| preview_lines(level, size))
+        .clone();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// The lines of a preview fitting in `size`: the board, one character per
+/// cell or scaled down to fit, then its summary.
+pub fn preview_lines(level: Level, size: (u16, u16)) -> Vec<Line<'static>> {
+    let grid = level_layout(level);
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let height = grid.len();
+    let scale = width
+        .div_ceil(usize::from(size.0).max(1))
+        .max(height.div_ceil(usize::from(size.1).max(1)))
+        .max(1);
+    let mut lines: Vec<Line> = (0..height.div_ceil(scale))
+        .map(|row| {
+            let cells: String = (0..width.div_ceil(scale))
+                .map(|column| {
+                    // Of all the cells a character stands for, show the one
+                    // that matters most.
+                    (row * scale..(row + 1) * scale)
+                        .flat_map(|y| (column * scale..(column + 1) * scale).map(move |x| (x, y)))
+                        .map(|(x, y)| cell_at(&grid, Pos::new(x as i32, y as i32)))
+                        .max_by_key(|cell| " #.$*+@".find(char::from(*cell)))
+                        .map_or(' ', char::from)
+                })
+                .collect();
+            Line::from(cells).blue()
+        })
+        .collect();
+    let count = |cells: &str| {
+        grid.iter()
+            .flatten()
+            .filter(|c| cells.contains(char::from(**c)))
+            .count()
+    };
+    lines.push(
+        Line::from(format!(
+            "{}x{}, {} boxes, {} goals",
+            width,
+            height,
+            count("$*"),
+            count(".*+")
+        ))
+        .dim(),
+    );
+    lines
+}
+
+pub fn cell_description(cell: Tile) -> &'static str {
+    match cell {
+        Tile::Wall => "wall",
+        Tile::Goal => "goal",
+        Tile::Box => "box",
+        Tile::BoxOnGoal => "box on goal",
+        Tile::Player => "player",
+        Tile::PlayerOnGoal => "player on goal",
+        Tile::Floor => "floor",
+    }
+}
+
+/// The first of `len` rows or columns to draw in `room` of them so that
+/// `centre` is in the middle, or as near as the board's edges allow.
+fn scroll(centre: i32, len: usize, room: usize) -> usize {
+    (centre.max(0) as usize)
+        .saturating_sub(room / 2)
+        .min(len.saturating_sub(room))
+}
+
+/// Column label used by the ruler: `a` to `z`, then `A` to `Z`.
+pub fn column_name(x: i32) -> char {
+    match x {
+        0..26 => (b'a' + x as u8) as char,
+        26..52 => (b'A' + (x - 26) as u8) as char,
+        _ => '?',
+    }
+}
+
+/// A cell in the notation used by the ruler, such as `d4` for the fourth
+/// column of the fourth row, counted in level coordinates.
+pub fn coord_name(pos: Pos) -> String {
+    format!("{}{}", column_name(pos.x), pos.y + 1)
+}
+
+/// Renders a board row cell by cell, for when some cells need their own
+/// style: the parts of an explained deadlock, the ghost's square, the box
+/// that just blocked a push, the heatmap, or the squares within reach.
+/// `start` is where the row's first tile is on the board.
+pub fn styled_row<'a>(
+    row: &[Tile],
+    start: Pos,
+    deadlock: Option<&Deadlock>,
+    ghost: Option<Pos>,
+    blocker: Option<Pos>,
+    heat: Option<&Heat>,
+    reach: Option<&HashSet<Pos>>,
+) -> Line<'a> {
+    Line::from(
+        row.iter()
+            .enumerate()
+            .map(|(x, c)| {
+                let pos = Pos::new(start.x + x as i32, start.y);
+                let style = match deadlock {
+                    Some(deadlock) if pos == deadlock.at() => Style::new().red().bold().reversed(),
+                    Some(deadlock) if deadlock.walls().contains(&pos) => {
+                        Style::new().yellow().reversed()
+                    }
+                    _ if blocker == Some(pos) => Style::new().yellow().bold().reversed(),
+                    _ if ghost == Some(pos) && !c.is_player() => Style::new().blue().on_magenta(),
+                    _ => match heat.map(|heat| heat.share(pos)) {
+                        Some(share) if share >= 0.5 => Style::new().white().on_red(),
+                        Some(share) if share >= 0.2 => Style::new().black().on_yellow(),
+                        Some(share) if share > 0.0 => Style::new().blue().on_dark_gray(),
+                        _ if reach.is_some_and(|reach| reach.contains(&pos)) => {
+                            Style::new().blue().on_green()
+                        }
+                        _ => Style::new().blue(),
+                    },
+                };
+                Span::styled(char::from(*c).to_string(), style)
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Draws the open prompt on one line, scrolled sideways once the input
+/// runs past the edge so that where it is typed stays in view, and puts the
+/// terminal's cursor there. Without this the cursor is hidden.
+pub fn draw_prompt(frame: &mut Frame, area: Rect, prompt: &Prompt) {
+    if area.width == 0 || area.height == 0 {
+        return;
+    }
+    let column = prompt_parts(prompt).0.chars().count() as u16;
+    let scroll = (column + 1).saturating_sub(area.width);
+    frame.render_widget(
+        Paragraph::new(prompt_text(prompt))
+            .yellow()
+            .scroll((0, scroll)),
+        area,
+    );
+    frame.set_cursor_position((area.x + column - scroll, area.y));
+}
+
+/// The cells of a rendered frame as text, one line per row. With `ansi`,
+/// each change of colour or modifier becomes an SGR escape, using the same
+/// codes the terminal was sent, so `cat` shows the frame as it was drawn.
+pub fn screenshot(buffer: &Buffer, ansi: bool) -> String {
+    let area = buffer.area;
+    let mut text = String::new();
+    for y in area.top()..area.bottom() {
+        let mut line = String::new();
+        let mut style = None;
+        for x in area.left()..area.right() {
+            let cell = &buffer[(x, y)];
+            if cell.skip {
+                continue;
+            }
+            if ansi && style != Some(cell.style()) {
+                style = Some(cell.style());
+                line.push_str(&sgr(cell.fg, cell.bg, cell.modifier));
+            }
+            line.push_str(cell.symbol());
+        }
+        if ansi {
+            line.push_str("\x1b[0m");
+        } else {
+            line.truncate(line.trim_end().len());
+        }
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
+
+/// An escape that resets the terminal style and then sets this one.
+pub fn sgr(fg: Color, bg: Color, modifier: Modifier) -> String {
+    let mut codes = vec!["0".to_string()];
+    for (flag, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::SLOW_BLINK, "5"),
+        (Modifier::RAPID_BLINK, "6"),
+        (Modifier::REVERSED, "7"),
+        (Modifier::HIDDEN, "8"),
+        (Modifier::CROSSED_OUT, "9"),
+    ] {
+        if modifier.contains(flag) {
+            codes.push(code.to_string());
+        }
+    }
+    codes.extend(color_code(fg, 30));
+    codes.extend(color_code(bg, 40));
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+/// The SGR parameters for a foreground (`base` 30) or background (`base` 40)
+/// colour, or none for the terminal default.
+pub fn color_code(color: Color, base: u8) -> Option<String> {
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        Color::Indexed(index) => return Some(format!("{};5;{}", base + 8, index)),
+    };
+    Some(code.to_string())
+}
+
+pub fn draw_editor(frame: &mut Frame, area: Rect, game_state: &GameState, editor: &Editor) {
+    let mut lines = vec![Line::from("Level editor").bold()];
+    for (y, row) in editor.draft.iter().enumerate() {
+        let spans: Vec<Span> = row
+            .iter()
+            .enumerate()
+            .map(|(x, cell)| {
+                let span = Span::from(char::from(*cell).to_string());
+                if Pos::new(x as i32, y as i32) == editor.cursor {
+                    span.reversed()
+                } else {
+                    span
+                }
+            })
+            .collect();
+        lines.push(Line::from(spans).blue());
+    }
+    lines.push(Line::from(""));
+    let keymap = &game_state.ui.keymap;
+    lines.push(Line::from(format!(
+        "Move with {}{}{}{}. Stamp with {} wall, {} box, {} goal, {} player, {} floor.",
+        keymap.hint(&Command::Move(MoveDirection::Up)),
+        keymap.hint(&Command::Move(MoveDirection::Left)),
+        keymap.hint(&Command::Move(MoveDirection::Down)),
+        keymap.hint(&Command::Move(MoveDirection::Right)),
+        keymap.hint(&Command::LevelSelect(Level::One)),
+        keymap.hint(&Command::LevelSelect(Level::Two)),
+        keymap.hint(&Command::LevelSelect(Level::Three)),
+        keymap.hint(&Command::LevelSelect(Level::Four)),
+        keymap.hint(&Command::LevelSelect(Level::Five)),
+    )));
+    lines.push(Line::from(format!(
+        "{} undoes a stamp, {} playtests, {} saves, {} leaves the editor.",
+        keymap.hint(&Command::ReverseMove),
+        keymap.hint(&Command::MenuConfirm),
+        keymap.hint(&Command::OpenSaveLevel),
+        keymap.hint(&Command::LevelChoose),
+    )));
+    let prompt_row = lines.len() as u16;
+    if game_state.ui.prompt.is_some() {
+        lines.push(Line::default());
+    }
+    if let Some(message) = &game_state.ui.message {
+        lines.push(Line::from(message.clone()).yellow());
+    }
+    frame.render_widget(Paragraph::new(lines), area);
+    if let (Some(prompt), true) = (&game_state.ui.prompt, prompt_row < area.height) {
+        let row = Rect {
+            y: area.y + prompt_row,
+            height: 1,
+            ..area
+        };
+        draw_prompt(frame, row, prompt);
+    }
+}